@@ -1,11 +1,45 @@
 use tauri::{Manager, State};
 
 use crate::{
-    port::{killer::kill_pid as kill_pid_impl, scanner::scan_listening_ports},
-    store::DesiredTunnel,
+    app::{apply_tunnel_options, emit_port_changes},
+    clock::now_ms,
+    port::{
+        connections::Connection,
+        killer::kill_pid as kill_pid_impl,
+        process::{get_process_details as get_process_details_impl, ProcessDetails},
+        scanner::list_connections as list_connections_impl,
+        watcher::PortSnapshot,
+    },
+    proxy::{
+        front::TunnelOptions,
+        group::ShareGroup,
+        inspector::{CapturedRequest, RequestSummary},
+        replay::{replay, ReplayOverrides},
+        router::Route,
+    },
+    store::{DesiredTunnel, Store},
+    tunnel::{
+        bore::{BoreConfig, BoreProvider},
+        cloudflare::{CloudflareProvider, NamedTunnelConfig},
+        manager::{ExpirySpec, TunnelLogs, TunnelManager},
+        ngrok::NgrokProvider,
+        registry::{ProviderStatus, DEFAULT_PROVIDER},
+        ssh::{SshConfig, SshProvider},
+        TunnelKind, TunnelState,
+    },
     AppState,
 };
 
+/// Runs `f` on the blocking pool. Store writes, proxy listener binds and the
+/// locks taken around them don't belong on the async workers.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| e.to_string())?
+}
+
 fn open_url_impl(url: &str) -> Result<(), String> {
     if !(url.starts_with("https://") || url.starts_with("http://")) {
         return Err("only http(s) urls are allowed".to_string());
@@ -35,9 +69,79 @@ fn open_url_impl(url: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Scans immediately through the shared watcher, so a manual refresh also
+/// pushes its deltas to every `ports://changed` listener.
+#[tauri::command]
+pub async fn list_ports(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Vec<crate::port::PortInfo>, String> {
+    let watcher = state.ports.clone();
+    let (snap, changes) = tauri::async_runtime::spawn_blocking(move || watcher.rescan())
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    emit_port_changes(&app, snap.scanned_at_ms, changes);
+    Ok(snap.ports)
+}
+
+/// Returns the watcher's current snapshot; callers then apply `ports://changed`
+/// deltas on top of it instead of polling.
+#[tauri::command]
+pub async fn subscribe_ports(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<PortSnapshot, String> {
+    if let Some(snap) = state.ports.snapshot() {
+        return Ok(snap);
+    }
+    let watcher = state.ports.clone();
+    let (snap, changes) = tauri::async_runtime::spawn_blocking(move || watcher.rescan())
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    emit_port_changes(&app, snap.scanned_at_ms, changes);
+    Ok(snap)
+}
+
+#[tauri::command]
+pub fn set_scan_interval(state: State<'_, AppState>, interval_ms: u64) -> Result<u64, String> {
+    let applied = state.ports.set_interval_ms(interval_ms);
+    state
+        .store
+        .set_scan_interval_ms(applied)
+        .map_err(|e| e.to_string())?;
+    Ok(applied)
+}
+
+#[tauri::command]
+pub fn set_scan_backend(state: State<'_, AppState>, backend: String) -> Result<(), String> {
+    state
+        .ports
+        .set_backend(&backend)
+        .map_err(|e| e.to_string())?;
+    state
+        .store
+        .set_scan_backend(&backend)
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
-pub async fn list_ports() -> Result<Vec<crate::port::PortInfo>, String> {
-    tauri::async_runtime::spawn_blocking(|| scan_listening_ports())
+pub async fn get_process_details(pid: u32) -> Result<ProcessDetails, String> {
+    tauri::async_runtime::spawn_blocking(move || get_process_details_impl(pid))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// Remote peers of a listening TCP port (address, port, state).
+#[tauri::command]
+pub async fn list_connections(
+    state: State<'_, AppState>,
+    port: u16,
+) -> Result<Vec<Connection>, String> {
+    let backend = state.ports.backend();
+    tauri::async_runtime::spawn_blocking(move || list_connections_impl(&backend, port))
         .await
         .map_err(|e| e.to_string())?
         .map_err(|e| e.to_string())
@@ -49,59 +153,437 @@ pub fn list_tunnels(state: State<'_, AppState>) -> Result<Vec<crate::tunnel::Tun
     Ok(mgr.list())
 }
 
+/// What the tunnel on `port` printed, and the output of its last failed
+/// starts. New lines arrive as `tunnels://log` events.
+#[tauri::command]
+pub fn get_tunnel_logs(state: State<'_, AppState>, port: u16) -> Result<TunnelLogs, String> {
+    let mgr = state.tunnels.lock().map_err(|_| "lock tunnels failed")?;
+    Ok(mgr.logs(port))
+}
+
 #[tauri::command]
-pub async fn open_tunnel(state: State<'_, AppState>, port: u16) -> Result<String, String> {
+pub async fn open_tunnel(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    port: u16,
+    provider: Option<String>,
+    force: Option<bool>,
+    options: Option<TunnelOptions>,
+    expiry: Option<ExpirySpec>,
+) -> Result<String, String> {
+    let force = force.unwrap_or(false);
+    let options = options.unwrap_or_default();
+    let expires_at_ms = expiry.map(|e| e.resolve(now_ms())).transpose()?;
+    let (existing, renewing) = {
+        let (app, tunnels, store) = (app.clone(), state.tunnels.clone(), state.store.clone());
+        let (provider, options) = (provider.clone(), options.clone());
+        blocking(move || {
+            let mut mgr = tunnels.lock().map_err(|_| "lock tunnels failed".to_string())?;
+            // A running tunnel keeps going and a starting one is waited for
+            // as is. Changing a running tunnel's options renews it, since its
+            // proxy would go away underneath it.
+            let existing = mgr.existing(port);
+            let renewing = match &existing {
+                Some(t) if t.state != TunnelState::Starting => {
+                    let saved = store.desired_tunnels().into_iter().find(|t| t.port == port);
+                    let saved = saved.map(|t| t.options).unwrap_or_default();
+                    (saved != options).then_some((saved, t.expires_at_ms))
+                }
+                _ => None,
+            };
+            if existing.is_none() || renewing.is_some() {
+                check_tunnel_options(&mgr, provider.as_deref(), &options)?;
+                apply_tunnel_options(&app, &mut mgr, port, &options)?;
+                mgr.set_expiry(port, expires_at_ms);
+            }
+            Ok((existing, renewing))
+        })
+        .await?
+    };
+    let joining = match &existing {
+        Some(t) if renewing.is_none() && t.state != TunnelState::Starting => return Ok(t.url.clone()),
+        Some(_) => renewing.is_none(),
+        None => false,
+    };
+    let started = match renewing {
+        Some(_) => TunnelManager::renew(&state.tunnels, port, provider.as_deref()).await,
+        None => TunnelManager::start(&state.tunnels, port, provider.as_deref(), force).await,
+    };
+    let info = match started {
+        Ok(info) => info,
+        Err(e) => {
+            if !joining {
+                // Back to what the tunnel had before, if anything.
+                let (options, expires_at_ms) = renewing.unwrap_or_default();
+                let tunnels = state.tunnels.clone();
+                let _ = blocking(move || {
+                    let mut mgr = tunnels.lock().map_err(|_| "lock tunnels failed".to_string())?;
+                    mgr.set_expiry(port, expires_at_ms);
+                    apply_tunnel_options(&app, &mut mgr, port, &options)
+                })
+                .await;
+            }
+            return Err(e.to_string());
+        }
+    };
+    if joining {
+        // The call that started it saves it.
+        return Ok(info.url);
+    }
     let store = state.store.clone();
-    let tunnels = state.tunnels.clone();
-    tauri::async_runtime::spawn_blocking(move || {
-        let mut mgr = tunnels.lock().map_err(|_| "lock tunnels failed".to_string())?;
-        let info = mgr.start(port).map_err(|e| e.to_string())?;
+    let provider = info.provider.clone();
+    blocking(move || {
         store
             .enable_desired_tunnel(DesiredTunnel {
                 port,
-                provider: info.provider.clone(),
+                provider,
+                options,
+                expires_at_ms,
                 updated_at_ms: 0,
             })
-            .map_err(|e| e.to_string())?;
-        Ok::<_, String>(info.url)
+            .map_err(|e| e.to_string())
     })
-    .await
-    .map_err(|e| e.to_string())?
+    .await?;
+    Ok(info.url)
 }
 
+/// Sets, moves or (with no `expiry`) clears when the tunnel on `port`
+/// closes on its own. Returns the new deadline.
 #[tauri::command]
-pub async fn close_tunnel(state: State<'_, AppState>, port: u16) -> Result<(), String> {
+pub async fn set_tunnel_expiry(
+    state: State<'_, AppState>,
+    port: u16,
+    expiry: Option<ExpirySpec>,
+) -> Result<Option<u64>, String> {
+    let expires_at_ms = expiry.map(|e| e.resolve(now_ms())).transpose()?;
+    {
+        let mut mgr = state.tunnels.lock().map_err(|_| "lock tunnels failed")?;
+        if !mgr.list().iter().any(|t| t.port == port) {
+            return Err(format!("no tunnel is open on port {port}"));
+        }
+        mgr.set_expiry(port, expires_at_ms);
+    }
     let store = state.store.clone();
-    let tunnels = state.tunnels.clone();
-    tauri::async_runtime::spawn_blocking(move || {
+    blocking(move || {
+        store
+            .set_desired_expiry(port, expires_at_ms)
+            .map_err(|e| e.to_string())
+    })
+    .await?;
+    Ok(expires_at_ms)
+}
+
+/// Rejects options the provider cannot honour. The front proxy speaks HTTP,
+/// and only Cloudflare's edge sets a `CF-Connecting-IP` clients cannot forge.
+fn check_tunnel_options(
+    mgr: &TunnelManager,
+    provider: Option<&str>,
+    options: &TunnelOptions,
+) -> Result<(), String> {
+    if let Some(guard) = &options.guard {
+        guard.validate()?;
+    }
+    let name = provider.unwrap_or(DEFAULT_PROVIDER);
+    let Some(p) = mgr.providers().get(name) else {
+        return Ok(());
+    };
+    if options.needs_proxy() && p.kind() == TunnelKind::Tcp {
+        return Err(format!(
+            "{name} tunnels are raw TCP; inspection and access protection need an http provider"
+        ));
+    }
+    if options.guard.as_ref().is_some_and(|g| !g.allow_ips.is_empty()) && name != "cloudflare" {
+        return Err(format!(
+            "the ip allowlist relies on CF-Connecting-IP, which only cloudflare sets (not {name})"
+        ));
+    }
+    Ok(())
+}
+
+/// Requests recorded for the tunnel on `port`, newest first. Empty unless it
+/// was opened with `inspect`.
+#[tauri::command]
+pub fn list_requests(state: State<'_, AppState>, port: u16) -> Result<Vec<RequestSummary>, String> {
+    let fronts = state.fronts.lock().map_err(|_| "lock fronts failed")?;
+    Ok(fronts.inspector(port).map(|i| i.list()).unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn get_request(state: State<'_, AppState>, id: u64) -> Result<CapturedRequest, String> {
+    let fronts = state.fronts.lock().map_err(|_| "lock fronts failed")?;
+    fronts
+        .find_request(id)
+        .ok_or_else(|| format!("request {id} is no longer recorded"))
+}
+
+/// Re-sends recorded request `id` to its local port with `overrides` applied.
+/// The result is recorded next to the original and returned.
+#[tauri::command]
+pub async fn replay_request(
+    state: State<'_, AppState>,
+    id: u64,
+    overrides: Option<ReplayOverrides>,
+) -> Result<CapturedRequest, String> {
+    let inspector = state
+        .fronts
+        .lock()
+        .map_err(|_| "lock fronts failed")?
+        .inspector_of(id)
+        .ok_or_else(|| format!("request {id} is no longer recorded"))?;
+    let overrides = overrides.unwrap_or_default();
+    tauri::async_runtime::spawn_blocking(move || replay(&inspector, id, &overrides))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// Every registered provider with whether its binary is installed.
+#[tauri::command]
+pub async fn list_providers(state: State<'_, AppState>) -> Result<Vec<ProviderStatus>, String> {
+    let providers = state
+        .tunnels
+        .lock()
+        .map_err(|_| "lock tunnels failed")?
+        .providers();
+    tauri::async_runtime::spawn_blocking(move || providers.statuses())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Saves the ngrok authtoken (empty clears it) and applies it to new tunnels.
+#[tauri::command]
+pub fn set_ngrok_authtoken(state: State<'_, AppState>, token: Option<String>) -> Result<(), String> {
+    let token = token.filter(|t| !t.trim().is_empty());
+    state
+        .store
+        .set_ngrok_authtoken(token.clone())
+        .map_err(|e| e.to_string())?;
+    state
+        .tunnels
+        .lock()
+        .map_err(|_| "lock tunnels failed")?
+        .register_provider(NgrokProvider::new(token));
+    Ok(())
+}
+
+/// Saves the bastion settings for the `ssh` provider and applies them to new tunnels.
+#[tauri::command]
+pub fn set_ssh_config(state: State<'_, AppState>, config: SshConfig) -> Result<(), String> {
+    state
+        .store
+        .set_ssh_config(config.clone())
+        .map_err(|e| e.to_string())?;
+    state
+        .tunnels
+        .lock()
+        .map_err(|_| "lock tunnels failed")?
+        .register_provider(SshProvider::new(config));
+    Ok(())
+}
+
+/// Saves the bore server (and optional secret) used for TCP tunnels.
+#[tauri::command]
+pub fn set_bore_config(state: State<'_, AppState>, config: BoreConfig) -> Result<(), String> {
+    state
+        .store
+        .set_bore_config(config.clone())
+        .map_err(|e| e.to_string())?;
+    state
+        .tunnels
+        .lock()
+        .map_err(|_| "lock tunnels failed")?
+        .register_provider(BoreProvider::new(config));
+    Ok(())
+}
+
+fn cloudflare_provider(store: &Store) -> CloudflareProvider {
+    CloudflareProvider::named(
+        store.settings().cloudflare_named,
+        store.tunnel_hostnames(),
+        &store.data_dir(),
+    )
+}
+
+/// Saves the named Cloudflare tunnel (empty fields switch back to Quick
+/// Tunnels) and applies it to new tunnels. A running connector of a named
+/// tunnel is relaunched with it by the next `reroute`.
+#[tauri::command]
+pub fn set_cloudflare_named(
+    state: State<'_, AppState>,
+    config: NamedTunnelConfig,
+) -> Result<(), String> {
+    state
+        .store
+        .set_cloudflare_named(config)
+        .map_err(|e| e.to_string())?;
+    state
+        .tunnels
+        .lock()
+        .map_err(|_| "lock tunnels failed")?
+        .register_provider(cloudflare_provider(&state.store));
+    Ok(())
+}
+
+/// Maps `port` to a hostname routed to the named tunnel (e.g. with
+/// `cloudflared tunnel route dns`). Empty removes the mapping. The named
+/// tunnel's connector is relaunched with the new ingress.
+#[tauri::command]
+pub async fn set_tunnel_hostname(
+    state: State<'_, AppState>,
+    port: u16,
+    hostname: Option<String>,
+) -> Result<(), String> {
+    let hostname = hostname
+        .map(|h| {
+            let h = h.trim();
+            let h = h.strip_prefix("https://").or_else(|| h.strip_prefix("http://")).unwrap_or(h);
+            h.trim_end_matches('/').to_string()
+        })
+        .filter(|h| !h.is_empty());
+    let store = state.store.clone();
+    blocking(move || store.set_tunnel_hostname(port, hostname).map_err(|e| e.to_string())).await?;
+    state
+        .tunnels
+        .lock()
+        .map_err(|_| "lock tunnels failed")?
+        .register_provider(cloudflare_provider(&state.store));
+    TunnelManager::reroute(&state.tunnels).await;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_share_groups(state: State<'_, AppState>) -> Vec<ShareGroup> {
+    state.store.share_groups()
+}
+
+/// Publishes several local ports behind one proxy and one tunnel. Calling it
+/// again for the same `name` swaps the routes and keeps the tunnel.
+#[tauri::command]
+pub async fn set_share_group(
+    state: State<'_, AppState>,
+    name: String,
+    routes: Vec<Route>,
+    provider: Option<String>,
+) -> Result<String, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("share group needs a name".to_string());
+    }
+    if routes.is_empty() {
+        return Err("share group needs at least one route".to_string());
+    }
+    let (store, groups) = (state.store.clone(), state.groups.clone());
+    let group = blocking(move || {
+        let mut group = ShareGroup {
+            listen_port: store
+                .share_groups()
+                .iter()
+                .find(|g| g.name == name)
+                .map_or(0, |g| g.listen_port),
+            name,
+            routes,
+        };
         {
-            let mut mgr = tunnels.lock().map_err(|_| "lock tunnels failed".to_string())?;
-            mgr.stop(port).map_err(|e| e.to_string())?;
+            let mut groups = groups.lock().map_err(|_| "lock share groups failed".to_string())?;
+            if let Some(r) = group.routes.iter().find(|r| groups.is_proxy_port(r.port)) {
+                return Err(format!("port {} is a share group's proxy", r.port));
+            }
+            groups.apply(&mut group).map_err(|e| e.to_string())?;
         }
         store
-            .disable_desired_tunnel(port)
+            .save_share_group(group.clone())
+            .map_err(|e| e.to_string())?;
+        Ok(group)
+    })
+    .await?;
+
+    let provider = provider.unwrap_or_else(|| DEFAULT_PROVIDER.to_string());
+    // The proxy itself is up; it answers 502 for routes whose port is down.
+    let info = TunnelManager::start(&state.tunnels, group.listen_port, Some(&provider), true)
+        .await
+        .map_err(|e| e.to_string())?;
+    let store = state.store.clone();
+    let provider = info.provider.clone();
+    blocking(move || {
+        store
+            .enable_desired_tunnel(DesiredTunnel {
+                port: group.listen_port,
+                provider,
+                options: TunnelOptions::default(),
+                expires_at_ms: None,
+                updated_at_ms: 0,
+            })
+            .map_err(|e| e.to_string())
+    })
+    .await?;
+    Ok(info.url)
+}
+
+/// Closes the group's tunnel and stops its proxy.
+#[tauri::command]
+pub async fn remove_share_group(state: State<'_, AppState>, name: String) -> Result<(), String> {
+    let Some(group) = state.store.share_groups().into_iter().find(|g| g.name == name) else {
+        return Ok(());
+    };
+    {
+        let mut mgr = state.tunnels.lock().map_err(|_| "lock tunnels failed".to_string())?;
+        let _ = mgr.stop(group.listen_port);
+    }
+    TunnelManager::reroute(&state.tunnels).await;
+    let (store, groups) = (state.store.clone(), state.groups.clone());
+    blocking(move || {
+        store
+            .disable_desired_tunnel(group.listen_port)
             .map_err(|e| e.to_string())?;
-        Ok::<_, String>(())
+        groups
+            .lock()
+            .map_err(|_| "lock share groups failed".to_string())?
+            .remove(&name);
+        store.remove_share_group(&name).map_err(|e| e.to_string())
     })
     .await
-    .map_err(|e| e.to_string())?
 }
 
 #[tauri::command]
-pub async fn renew_tunnel(state: State<'_, AppState>, port: u16) -> Result<String, String> {
-    let store = state.store.clone();
+pub async fn close_tunnel(app: tauri::AppHandle, state: State<'_, AppState>, port: u16) -> Result<(), String> {
     let tunnels = state.tunnels.clone();
-    tauri::async_runtime::spawn_blocking(move || {
-        let url = {
-            let mut mgr = tunnels.lock().map_err(|_| "lock tunnels failed".to_string())?;
-            let info = mgr.renew(port).map_err(|e| e.to_string())?;
-            info.url
-        };
-        store.touch_desired_tunnel(port).map_err(|e| e.to_string())?;
-        Ok::<_, String>(url)
+    blocking(move || {
+        let mut mgr = tunnels.lock().map_err(|_| "lock tunnels failed".to_string())?;
+        mgr.stop(port).map_err(|e| e.to_string())?;
+        apply_tunnel_options(&app, &mut mgr, port, &TunnelOptions::default())
     })
-    .await
-    .map_err(|e| e.to_string())?
+    .await?;
+    TunnelManager::reroute(&state.tunnels).await;
+    let store = state.store.clone();
+    blocking(move || store.disable_desired_tunnel(port).map_err(|e| e.to_string())).await
+}
+
+#[tauri::command]
+pub async fn renew_tunnel(state: State<'_, AppState>, port: u16) -> Result<String, String> {
+    // A tunnel that already died is no longer tracked; fall back to the
+    // provider it was saved with.
+    let saved = state
+        .store
+        .desired_tunnels()
+        .into_iter()
+        .find(|t| t.port == port)
+        .map(|t| t.provider);
+    let provider = state
+        .tunnels
+        .lock()
+        .map_err(|_| "lock tunnels failed".to_string())?
+        .list()
+        .into_iter()
+        .find(|t| t.port == port)
+        .map(|t| t.provider)
+        .or(saved);
+    let url = TunnelManager::renew(&state.tunnels, port, provider.as_deref())
+        .await
+        .map_err(|e| e.to_string())?
+        .url;
+    let store = state.store.clone();
+    blocking(move || store.touch_desired_tunnel(port).map_err(|e| e.to_string())).await?;
+    Ok(url)
 }
 
 #[tauri::command]
@@ -145,3 +627,4 @@ pub fn quit_app(app: tauri::AppHandle) -> Result<(), String> {
     app.exit(0);
     Ok(())
 }
+
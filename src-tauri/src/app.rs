@@ -4,24 +4,75 @@ use std::{
     time::Duration,
 };
 
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Emitter, Manager};
 
-use crate::{tunnel::manager::TunnelManager, AppState};
+use crate::{
+    clock::now_ms,
+    port::watcher::{PortChange, PortWatcher, PortsChanged, PORTS_CHANGED_EVENT},
+    proxy::{front::TunnelOptions, inspector::REQUEST_CAPTURED_EVENT},
+    tunnel::manager::{TunnelManager, TUNNEL_EXPIRED_EVENT, TUNNEL_EXPIRING_EVENT},
+    AppState,
+};
+
+pub fn start_port_watcher(app: &AppHandle, watcher: Arc<PortWatcher>) {
+    let app = app.clone();
+    thread::spawn(move || loop {
+        if let Ok((snap, changes)) = watcher.rescan() {
+            emit_port_changes(&app, snap.scanned_at_ms, changes);
+        }
+        watcher.wait();
+    });
+}
+
+pub fn emit_port_changes(app: &AppHandle, scanned_at_ms: u64, changes: Vec<PortChange>) {
+    if changes.is_empty() {
+        return;
+    }
+    let _ = app.emit(
+        PORTS_CHANGED_EVENT,
+        PortsChanged {
+            scanned_at_ms,
+            changes,
+        },
+    );
+}
 
 pub fn restore_desired_tunnels(app: &AppHandle) {
     let app = app.clone();
-    thread::spawn(move || {
+    tauri::async_runtime::spawn(async move {
         let state = app.state::<AppState>();
+        // Group proxies first: their tunnels are among the desired ones.
+        restore_share_groups(&state);
         let desired = state.store.desired_tunnels();
         if desired.is_empty() {
             return;
         }
 
+        let now = now_ms();
         for t in desired {
-            let res: Result<(), String> = (|| {
-                let mut mgr = state.tunnels.lock().map_err(|_| "lock tunnels failed".to_string())?;
-                mgr.start(t.port).map(|_| ()).map_err(|e| e.to_string())
-            })();
+            // Expired while the app was closed.
+            if t.expires_at_ms.is_some_and(|at| at <= now) {
+                let _ = state.store.disable_desired_tunnel(t.port);
+                continue;
+            }
+            let applied = state
+                .tunnels
+                .lock()
+                .map_err(|_| "lock tunnels failed".to_string())
+                .and_then(|mut mgr| {
+                    apply_tunnel_options(&app, &mut mgr, t.port, &t.options)?;
+                    mgr.set_expiry(t.port, t.expires_at_ms);
+                    Ok(())
+                });
+            // The dev server may not be up yet after a restart; the user
+            // already chose to share this port, so skip the upstream check.
+            let res = match applied {
+                Ok(()) => TunnelManager::start(&state.tunnels, t.port, Some(&t.provider), true)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string()),
+                Err(e) => Err(e),
+            };
             if res.is_err() {
                 let _ = state.store.disable_desired_tunnel(t.port);
             }
@@ -29,27 +80,89 @@ pub fn restore_desired_tunnels(app: &AppHandle) {
     });
 }
 
+/// Puts the proxy `options` need in front of `port`, or removes it, and
+/// points the tunnel manager there. Recorded requests are emitted as
+/// `REQUEST_CAPTURED_EVENT`.
+pub fn apply_tunnel_options(
+    app: &AppHandle,
+    mgr: &mut TunnelManager,
+    port: u16,
+    options: &TunnelOptions,
+) -> Result<(), String> {
+    let emitter = app.clone();
+    let proxy = app
+        .state::<AppState>()
+        .fronts
+        .lock()
+        .map_err(|_| "lock fronts failed".to_string())?
+        .apply(port, options, move |r| {
+            let _ = emitter.emit(REQUEST_CAPTURED_EVENT, r.summary());
+        })
+        .map_err(|e| e.to_string())?;
+    mgr.set_front(port, proxy);
+    Ok(())
+}
+
+fn restore_share_groups(state: &AppState) {
+    let Ok(mut groups) = state.groups.lock() else {
+        return;
+    };
+    for mut g in state.store.share_groups() {
+        if groups.apply(&mut g).is_err() {
+            // Something else took the proxy's port; never tunnel that instead.
+            let _ = state.store.disable_desired_tunnel(g.listen_port);
+        }
+    }
+}
+
 pub fn start_background_renewal(app: &AppHandle, tunnels: Arc<Mutex<TunnelManager>>) {
     let app = app.clone();
-    thread::spawn(move || loop {
-        thread::sleep(Duration::from_secs(5));
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(5)).await;
 
-        let dead_ports = {
-            let mut mgr = match tunnels.lock() {
-                Ok(m) => m,
-                Err(_) => continue,
+            let (expired, warnings) = {
+                let mut mgr = match tunnels.lock() {
+                    Ok(m) => m,
+                    Err(_) => continue,
+                };
+                let now = now_ms();
+                (mgr.take_expired(now), mgr.take_expiry_warnings(now))
             };
-            mgr.take_dead_ports()
-        };
-
-        if dead_ports.is_empty() {
-            continue;
-        }
+            for w in warnings {
+                let _ = app.emit(TUNNEL_EXPIRING_EVENT, w);
+            }
+            // Before renewal, so an expired tunnel that also died stays closed.
+            for e in &expired {
+                close_expired_tunnel(&app, &tunnels, e.port);
+                let _ = app.emit(TUNNEL_EXPIRED_EVENT, e);
+            }
 
-        for port in dead_ports {
-            let _ = tunnels.lock().map(|mut mgr| mgr.renew(port));
+            TunnelManager::check_upstreams(&tunnels).await;
+            // Errors stay on the tunnel (`last_error`) and back off per
+            // `RetryPolicy`; see `TunnelManager::retry_due`.
+            let now = now_ms();
+            // A process that exits is renewed by its watcher right away; this
+            // runs the retries that came due since.
+            let renewed = TunnelManager::retry_due(&tunnels, now).await;
+            // Also picks up ports that closed, e.g. on expiry.
+            TunnelManager::reroute(&tunnels).await;
             let state = app.state::<AppState>();
-            let _ = state.store.touch_desired_tunnel(port);
+            for (port, res) in renewed {
+                if res.is_ok() {
+                    let _ = state.store.touch_desired_tunnel(port);
+                }
+            }
         }
     });
 }
+
+/// Closes a tunnel whose time is up and forgets it, like `close_tunnel`.
+fn close_expired_tunnel(app: &AppHandle, tunnels: &Mutex<TunnelManager>, port: u16) {
+    if let Ok(mut mgr) = tunnels.lock() {
+        let _ = mgr.stop(port);
+        let _ = apply_tunnel_options(app, &mut mgr, port, &TunnelOptions::default());
+    }
+    let _ = app.state::<AppState>().store.disable_desired_tunnel(port);
+}
+
@@ -1,43 +1,101 @@
 mod api;
 mod app;
+mod clock;
 mod port;
+mod proxy;
 mod store;
 mod tray;
 mod tunnel;
 
 use std::sync::{Arc, Mutex};
 
-use tauri::Manager;
+use tauri::{Emitter, Manager};
 
+use port::watcher::PortWatcher;
+use proxy::{front::Fronts, group::ShareGroups};
 use store::Store;
-use tunnel::manager::TunnelManager;
+use tunnel::{
+    bore::BoreProvider,
+    cloudflare::CloudflareProvider,
+    manager::{TunnelManager, TUNNEL_LOG_EVENT, TUNNEL_STATE_EVENT},
+    ngrok::NgrokProvider,
+    ssh::SshProvider,
+};
 
 pub struct AppState {
     pub store: Arc<Store>,
     pub tunnels: Arc<Mutex<TunnelManager>>,
+    pub ports: Arc<PortWatcher>,
+    pub groups: Arc<Mutex<ShareGroups>>,
+    pub fronts: Arc<Mutex<Fronts>>,
 }
 
 fn main() {
     tauri::Builder::default()
         .setup(|app| {
             let store = Arc::new(Store::load(app.handle())?);
-            let tunnels = Arc::new(Mutex::new(TunnelManager::new()));
+            let settings = store.settings();
+            let mut manager = TunnelManager::new();
+            manager.register_provider(CloudflareProvider::named(
+                settings.cloudflare_named.clone(),
+                store.tunnel_hostnames(),
+                &store.data_dir(),
+            ));
+            manager.register_provider(NgrokProvider::new(settings.ngrok_authtoken.clone()));
+            manager.register_provider(SshProvider::new(settings.ssh.clone()));
+            manager.register_provider(BoreProvider::new(settings.bore.clone()));
+            let emitter = app.handle().clone();
+            manager.set_listener(move |t| {
+                let _ = emitter.emit(TUNNEL_STATE_EVENT, t);
+            });
+            let emitter = app.handle().clone();
+            manager.set_log_listener(move |l| {
+                let _ = emitter.emit(TUNNEL_LOG_EVENT, l);
+            });
+            let tunnels = Arc::new(Mutex::new(manager));
+            let ports = Arc::new(PortWatcher::new(
+                settings.scan_interval_ms,
+                &settings.scan_backend,
+            ));
 
             app.manage(AppState {
                 store,
                 tunnels: tunnels.clone(),
+                ports: ports.clone(),
+                groups: Arc::new(Mutex::new(ShareGroups::default())),
+                fronts: Arc::new(Mutex::new(Fronts::default())),
             });
 
             tray::init(app.handle())?;
+            app::start_port_watcher(app.handle(), ports);
             app::start_background_renewal(app.handle(), tunnels);
             app::restore_desired_tunnels(app.handle());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             api::commands::list_ports,
+            api::commands::subscribe_ports,
+            api::commands::set_scan_interval,
+            api::commands::set_scan_backend,
+            api::commands::get_process_details,
+            api::commands::list_connections,
             api::commands::list_tunnels,
+            api::commands::get_tunnel_logs,
             api::commands::open_tunnel,
             api::commands::close_tunnel,
+            api::commands::list_requests,
+            api::commands::get_request,
+            api::commands::replay_request,
+            api::commands::set_tunnel_expiry,
+            api::commands::list_providers,
+            api::commands::set_ngrok_authtoken,
+            api::commands::set_ssh_config,
+            api::commands::set_bore_config,
+            api::commands::set_cloudflare_named,
+            api::commands::set_tunnel_hostname,
+            api::commands::list_share_groups,
+            api::commands::set_share_group,
+            api::commands::remove_share_group,
             api::commands::renew_tunnel,
             api::commands::kill_pid,
             api::commands::open_url,
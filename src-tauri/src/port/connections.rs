@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::port::PortInfo;
+
+/// A non-listening TCP socket, seen from our side: the local end is the
+/// listener's port, the remote end is the peer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Connection {
+    pub local_address: String,
+    pub local_port: u16,
+    pub remote_ip: String,
+    pub remote_port: u16,
+    /// Lower-case TCP state, e.g. `established`, `time_wait`, `close_wait`.
+    pub state: String,
+}
+
+impl Connection {
+    pub fn is_established(&self) -> bool {
+        self.state == "established"
+    }
+}
+
+/// Names for the kernel's `TCP_*` state numbers (include/net/tcp_states.h).
+pub fn tcp_state_name(state: u8) -> &'static str {
+    match state {
+        1 => "established",
+        2 => "syn_sent",
+        3 => "syn_recv",
+        4 => "fin_wait1",
+        5 => "fin_wait2",
+        6 => "time_wait",
+        7 => "close",
+        8 => "close_wait",
+        9 => "last_ack",
+        10 => "listen",
+        11 => "closing",
+        12 => "new_syn_recv",
+        _ => "unknown",
+    }
+}
+
+/// Sets `connection_count` on every TCP listener from a connection list.
+/// Outgoing client sockets never match, since their local port is ephemeral.
+pub fn attach_connection_counts(ports: &mut [PortInfo], connections: &[Connection]) {
+    let mut counts: HashMap<u16, u32> = HashMap::new();
+    for c in connections.iter().filter(|c| c.is_established()) {
+        *counts.entry(c.local_port).or_default() += 1;
+    }
+    for p in ports.iter_mut().filter(|p| p.protocol == "tcp") {
+        p.connection_count = counts.get(&p.port).copied().unwrap_or(0);
+    }
+}
+
+/// Splits `1.2.3.4:80`, `[::1]:80` or `*:80` into host and port.
+pub fn split_host_port(address: &str) -> Option<(String, u16)> {
+    let idx = address.rfind(':')?;
+    let port = address[idx + 1..].parse().ok()?;
+    let host = address[..idx].trim_start_matches('[').trim_end_matches(']');
+    Some((host.to_string(), port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_addresses() {
+        assert_eq!(split_host_port("10.0.0.5:443"), Some(("10.0.0.5".to_string(), 443)));
+        assert_eq!(split_host_port("[::1]:5432"), Some(("::1".to_string(), 5432)));
+        assert_eq!(split_host_port("nope"), None);
+    }
+}
@@ -1,7 +1,20 @@
 use serde::Serialize;
 
+use probe::ServiceInfo;
+use process::ProcessDetails;
+use project::ProjectInfo;
+
+pub mod connections;
 pub mod killer;
+#[cfg(target_os = "linux")]
+pub mod netlink;
+pub mod probe;
+pub mod process;
+#[cfg(target_os = "linux")]
+pub mod procfs;
+pub mod project;
 pub mod scanner;
+pub mod watcher;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct PortInfo {
@@ -14,4 +27,26 @@ pub struct PortInfo {
     pub fd: String,
     pub command: Option<String>,
     pub is_active: bool,
+    /// Every local address the port is bound on, e.g. `127.0.0.1:5173` and `[::1]:5173`.
+    pub addresses: Vec<String>,
+    /// Every process holding the socket (master/workers, `SO_REUSEPORT` groups).
+    pub owners: Vec<PortOwner>,
+    /// True when no bind address is reachable from outside this machine.
+    pub loopback_only: bool,
+    /// Command line, cwd, parent and resource usage of `pid`, when readable.
+    pub details: Option<ProcessDetails>,
+    /// Project root detected from the owner's cwd (manifest, framework, branch).
+    pub project: Option<ProjectInfo>,
+    /// ESTABLISHED connections accepted on this port (always 0 for UDP).
+    pub connection_count: u32,
+    /// What the listener answered when probed; `None` for UDP or unreachable ports.
+    pub service: Option<ServiceInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PortOwner {
+    pub pid: u32,
+    pub process_name: String,
+    pub user: String,
+    pub fd: String,
 }
@@ -0,0 +1,711 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream},
+    sync::{Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use regex::Regex;
+use serde::Serialize;
+
+use crate::{
+    clock::now_ms,
+    port::{connections::split_host_port, PortInfo},
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(300);
+// Server-first protocols (SSH, MySQL) greet right after accept.
+const BANNER_TIMEOUT: Duration = Duration::from_millis(250);
+const REPLY_TIMEOUT: Duration = Duration::from_millis(500);
+// Dev servers may compile the page on the first request.
+const HTTP_TIMEOUT: Duration = Duration::from_millis(1_500);
+const MAX_READ: usize = 64 * 1024;
+// Unreachable or unrecognised listeners are probed again after this long;
+// anything identified stays cached until the owning pid changes.
+const RETRY_AFTER_MS: u64 = 30_000;
+// Probes running at once; a burst of new listeners waits its turn.
+const MAX_PROBES: usize = 8;
+
+const REDIS_PING: &[u8] = b"*1\r\n$4\r\nPING\r\n";
+// Length 8, then the magic SSLRequest code 80877103.
+const POSTGRES_SSL_REQUEST: [u8; 8] = [0, 0, 0, 8, 0x04, 0xd2, 0x16, 0x2f];
+
+/// What a listener turned out to speak when we connected to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ServiceInfo {
+    Http {
+        status: u16,
+        server: Option<String>,
+        title: Option<String>,
+    },
+    /// The TLS handshake got a reply. `subject` is the leaf certificate's
+    /// subject when the server sent one in the clear (TLS 1.2).
+    Tls { subject: Option<String> },
+    Redis,
+    Postgres,
+    Mysql { version: Option<String> },
+    Ssh { banner: String },
+    /// Accepted the connection but answered none of the probes.
+    Unknown,
+}
+
+impl ServiceInfo {
+    pub fn is_http(&self) -> bool {
+        matches!(self, ServiceInfo::Http { .. })
+    }
+}
+
+/// Caches probe results per (pid, port) so each listener is only poked once.
+#[derive(Default)]
+pub struct Prober {
+    cache: Mutex<HashMap<(u32, u16), Cached>>,
+}
+
+struct Cached {
+    service: Option<ServiceInfo>,
+    probed_at_ms: u64,
+}
+
+impl Cached {
+    fn is_stale(&self, now: u64) -> bool {
+        matches!(self.service, None | Some(ServiceInfo::Unknown))
+            && now.saturating_sub(self.probed_at_ms) >= RETRY_AFTER_MS
+    }
+}
+
+impl Prober {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fills `is_active` and `service` on every TCP listener. New listeners are
+    /// probed in parallel, up to `MAX_PROBES` at once; the rest reuse the
+    /// cached result.
+    pub fn apply(&self, ports: &mut [PortInfo]) {
+        let now = now_ms();
+        let todo: Vec<((u32, u16), SocketAddr)> = {
+            let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+            cache.retain(|key, _| {
+                ports
+                    .iter()
+                    .any(|p| p.protocol == "tcp" && (p.pid, p.port) == *key)
+            });
+            ports
+                .iter()
+                .filter(|p| p.protocol == "tcp")
+                .filter(|p| cache.get(&(p.pid, p.port)).is_none_or(|c| c.is_stale(now)))
+                .filter_map(|p| Some(((p.pid, p.port), probe_target(p)?)))
+                .collect()
+        };
+
+        let workers = todo.len().min(MAX_PROBES);
+        let queue = Mutex::new(todo.into_iter());
+        let results: Vec<((u32, u16), Option<ServiceInfo>)> = thread::scope(|s| {
+            let handles: Vec<_> = (0..workers)
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut found = vec![];
+                        loop {
+                            let Some((key, addr)) = queue.lock().unwrap_or_else(|e| e.into_inner()).next() else {
+                                return found;
+                            };
+                            found.push((key, probe(addr)));
+                        }
+                    })
+                })
+                .collect();
+            handles.into_iter().filter_map(|h| h.join().ok()).flatten().collect()
+        });
+
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        for (key, service) in results {
+            cache.insert(
+                key,
+                Cached {
+                    service,
+                    probed_at_ms: now,
+                },
+            );
+        }
+        for p in ports.iter_mut().filter(|p| p.protocol == "tcp") {
+            if let Some(c) = cache.get(&(p.pid, p.port)) {
+                p.is_active = c.service.is_some();
+                p.service = c.service.clone();
+            }
+        }
+    }
+}
+
+/// Where to connect for a listener: loopback for wildcard and loopback binds,
+/// the bound address itself otherwise.
+pub fn probe_target(info: &PortInfo) -> Option<SocketAddr> {
+    let candidates: Vec<SocketAddr> = info
+        .addresses
+        .iter()
+        .filter_map(|a| {
+            let (host, port) = split_host_port(a)?;
+            let ip = match host.as_str() {
+                "*" | "0.0.0.0" => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                "::" => IpAddr::V6(Ipv6Addr::LOCALHOST),
+                h => h.parse().ok()?,
+            };
+            Some(SocketAddr::new(ip, port))
+        })
+        .collect();
+    candidates
+        .iter()
+        .find(|a| a.ip().is_loopback())
+        .or(candidates.first())
+        .copied()
+}
+
+/// Classifies whatever listens on `addr`. `None` means nothing accepted the
+/// connection. Each probe uses a fresh connection, cheapest first.
+pub fn probe(addr: SocketAddr) -> Option<ServiceInfo> {
+    let mut stream = connect(addr)?;
+    let greeting = read_until(&mut stream, BANNER_TIMEOUT, |b| !b.is_empty());
+    drop(stream);
+    if !greeting.is_empty() {
+        return Some(classify_banner(&greeting));
+    }
+
+    // TLS goes before HTTP: many TLS servers answer plain HTTP with a 400.
+    if let Some(reply) = exchange(addr, &client_hello("localhost"), REPLY_TIMEOUT, |b| {
+        !matches!(parse_server_flight(b), Flight::Incomplete)
+    }) {
+        match parse_server_flight(&reply) {
+            Flight::Tls(subject) => return Some(ServiceInfo::Tls { subject }),
+            Flight::Incomplete if reply.first() == Some(&0x16) => {
+                return Some(ServiceInfo::Tls { subject: None })
+            }
+            _ => {}
+        }
+    }
+
+    let request = format!(
+        "GET / HTTP/1.0\r\nHost: localhost:{}\r\nAccept: text/html\r\nUser-Agent: jule-connect\r\nConnection: close\r\n\r\n",
+        addr.port()
+    );
+    if let Some(reply) = exchange(addr, request.as_bytes(), HTTP_TIMEOUT, http_complete) {
+        if let Some(http) = parse_http(&reply) {
+            return Some(http);
+        }
+    }
+
+    if let Some(reply) = exchange(addr, REDIS_PING, REPLY_TIMEOUT, |b| b.ends_with(b"\r\n")) {
+        if is_redis_reply(&reply) {
+            return Some(ServiceInfo::Redis);
+        }
+    }
+
+    if let Some(reply) = exchange(addr, &POSTGRES_SSL_REQUEST, REPLY_TIMEOUT, |b| !b.is_empty()) {
+        if reply == b"S" || reply == b"N" {
+            return Some(ServiceInfo::Postgres);
+        }
+    }
+
+    Some(ServiceInfo::Unknown)
+}
+
+/// Whether anything accepts TCP connections on `addr`.
+pub fn is_listening(addr: SocketAddr) -> bool {
+    connect(addr).is_some()
+}
+
+/// Status code of a `HEAD /` on `addr`, or `None` if the reply isn't HTTP.
+pub fn http_status(addr: SocketAddr, timeout: Duration) -> Option<u16> {
+    let request = format!(
+        "HEAD / HTTP/1.0\r\nHost: localhost:{}\r\nUser-Agent: jule-connect\r\nConnection: close\r\n\r\n",
+        addr.port()
+    );
+    let reply = exchange(addr, request.as_bytes(), timeout, |b| find(b, b"\r\n").is_some())?;
+    match parse_http(&reply)? {
+        ServiceInfo::Http { status, .. } => Some(status),
+        _ => None,
+    }
+}
+
+fn connect(addr: SocketAddr) -> Option<TcpStream> {
+    let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).ok()?;
+    let _ = stream.set_write_timeout(Some(REPLY_TIMEOUT));
+    Some(stream)
+}
+
+/// Sends `request` on a fresh connection and collects the reply. `None` if
+/// the connection or the write failed.
+fn exchange(
+    addr: SocketAddr,
+    request: &[u8],
+    timeout: Duration,
+    done: impl Fn(&[u8]) -> bool,
+) -> Option<Vec<u8>> {
+    let mut stream = connect(addr)?;
+    stream.write_all(request).ok()?;
+    Some(read_until(&mut stream, timeout, done))
+}
+
+/// Reads until `done`, EOF, an error, `MAX_READ` bytes or the deadline.
+fn read_until(stream: &mut TcpStream, timeout: Duration, done: impl Fn(&[u8]) -> bool) -> Vec<u8> {
+    let deadline = Instant::now() + timeout;
+    let mut buf = vec![];
+    let mut chunk = [0u8; 4096];
+    while buf.len() < MAX_READ && !done(&buf) {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() || stream.set_read_timeout(Some(left)).is_err() {
+            break;
+        }
+        match stream.read(&mut chunk) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+    buf
+}
+
+fn classify_banner(greeting: &[u8]) -> ServiceInfo {
+    if greeting.starts_with(b"SSH-") {
+        let line = String::from_utf8_lossy(greeting);
+        let banner = line.lines().next().unwrap_or_default().trim().to_string();
+        return ServiceInfo::Ssh { banner };
+    }
+    // MySQL packet: 3-byte length, sequence 0, then protocol 10 (or 0xff for
+    // an error such as "host is not allowed to connect").
+    if greeting.len() > 4 && greeting[3] == 0 && matches!(greeting[4], 0x0a | 0xff) {
+        let version = (greeting[4] == 0x0a)
+            .then(|| {
+                let rest = &greeting[5..];
+                let end = rest.iter().position(|b| *b == 0)?;
+                Some(String::from_utf8_lossy(&rest[..end]).to_string())
+            })
+            .flatten();
+        return ServiceInfo::Mysql { version };
+    }
+    ServiceInfo::Unknown
+}
+
+fn is_redis_reply(reply: &[u8]) -> bool {
+    ["+PONG", "-NOAUTH", "-WRONGPASS", "-DENIED"]
+        .iter()
+        .any(|p| reply.starts_with(p.as_bytes()))
+}
+
+// Done once we have the headers and either the title or the whole body.
+fn http_complete(buf: &[u8]) -> bool {
+    let Some(end) = find(buf, b"\r\n\r\n") else {
+        return false;
+    };
+    let body = &buf[end + 4..];
+    if find(&body.to_ascii_lowercase(), b"</title").is_some() {
+        return true;
+    }
+    let head = String::from_utf8_lossy(&buf[..end]);
+    header(&head, "content-length")
+        .and_then(|v| v.parse::<usize>().ok())
+        .is_some_and(|len| body.len() >= len)
+}
+
+fn parse_http(reply: &[u8]) -> Option<ServiceInfo> {
+    let text = String::from_utf8_lossy(reply);
+    let (head, body) = text.split_once("\r\n\r\n").unwrap_or((&text, ""));
+    let status = head
+        .lines()
+        .next()?
+        .strip_prefix("HTTP/")?
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()?;
+
+    static TITLE: OnceLock<Regex> = OnceLock::new();
+    let title = TITLE
+        .get_or_init(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap())
+        .captures(body)
+        .map(|c| c[1].split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|t| !t.is_empty());
+
+    Some(ServiceInfo::Http {
+        status,
+        server: header(head, "server"),
+        title,
+    })
+}
+
+fn header(head: &str, name: &str) -> Option<String> {
+    head.lines().skip(1).find_map(|l| {
+        let (k, v) = l.split_once(':')?;
+        k.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| v.trim().to_string())
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// A TLS 1.2 ClientHello. Without `supported_versions` the server can't pick
+/// 1.3, so its certificate comes back unencrypted.
+fn client_hello(server_name: &str) -> Vec<u8> {
+    fn u16be(n: usize) -> [u8; 2] {
+        (n as u16).to_be_bytes()
+    }
+    fn push_ext(out: &mut Vec<u8>, kind: u16, data: &[u8]) {
+        out.extend(kind.to_be_bytes());
+        out.extend(u16be(data.len()));
+        out.extend(data);
+    }
+
+    let name = server_name.as_bytes();
+    let mut ext = vec![];
+    let mut sni = vec![];
+    sni.extend(u16be(name.len() + 3));
+    sni.push(0);
+    sni.extend(u16be(name.len()));
+    sni.extend(name);
+    push_ext(&mut ext, 0x0000, &sni);
+    // supported_groups: x25519, secp256r1, secp384r1
+    push_ext(&mut ext, 0x000a, &[0, 6, 0, 0x1d, 0, 0x17, 0, 0x18]);
+    // ec_point_formats: uncompressed
+    push_ext(&mut ext, 0x000b, &[1, 0]);
+    // signature_algorithms: ecdsa/rsa-pss/rsa with sha256 and sha384
+    push_ext(&mut ext, 0x000d, &[0, 12, 4, 3, 8, 4, 4, 1, 5, 3, 8, 5, 5, 1]);
+
+    let suites: [u16; 8] = [0xc02b, 0xc02f, 0xc02c, 0xc030, 0xcca9, 0xcca8, 0x009c, 0x002f];
+    let mut body = vec![3, 3];
+    body.extend([0x4a; 32]);
+    body.push(0);
+    body.extend(u16be(suites.len() * 2));
+    for s in suites {
+        body.extend(s.to_be_bytes());
+    }
+    body.extend([1, 0]);
+    body.extend(u16be(ext.len()));
+    body.extend(ext);
+
+    let mut hello = vec![1];
+    hello.extend(&(body.len() as u32).to_be_bytes()[1..]);
+    hello.extend(body);
+
+    let mut record = vec![0x16, 3, 1];
+    record.extend(u16be(hello.len()));
+    record.extend(hello);
+    record
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Flight {
+    Incomplete,
+    NotTls,
+    Tls(Option<String>),
+}
+
+/// Walks the server's first flight up to the Certificate message.
+fn parse_server_flight(data: &[u8]) -> Flight {
+    match data.first() {
+        None => return Flight::Incomplete,
+        Some(0x15 | 0x16) => {}
+        Some(_) => return Flight::NotTls,
+    }
+
+    let mut handshake = vec![];
+    let mut rest = data;
+    while rest.len() >= 5 {
+        let (kind, major) = (rest[0], rest[1]);
+        if major != 3 || !matches!(kind, 0x15 | 0x16) {
+            return Flight::NotTls;
+        }
+        let len = u16::from_be_bytes([rest[3], rest[4]]) as usize;
+        if rest.len() < 5 + len {
+            break;
+        }
+        if kind == 0x15 {
+            // An alert (e.g. protocol_version from a 1.3-only server) still
+            // means something speaks TLS here.
+            return Flight::Tls(None);
+        }
+        handshake.extend_from_slice(&rest[5..5 + len]);
+        rest = &rest[5 + len..];
+    }
+
+    let mut msgs = handshake.as_slice();
+    while msgs.len() >= 4 {
+        let len = u32::from_be_bytes([0, msgs[1], msgs[2], msgs[3]]) as usize;
+        if msgs.len() < 4 + len {
+            break;
+        }
+        match msgs[0] {
+            11 => return Flight::Tls(first_certificate(&msgs[4..4 + len]).and_then(cert_subject)),
+            // ServerHelloDone without a Certificate: anonymous or PSK suites.
+            14 => return Flight::Tls(None),
+            _ => {}
+        }
+        msgs = &msgs[4 + len..];
+    }
+    Flight::Incomplete
+}
+
+fn first_certificate(body: &[u8]) -> Option<&[u8]> {
+    let len = u32::from_be_bytes([0, *body.get(3)?, *body.get(4)?, *body.get(5)?]) as usize;
+    body.get(6..6 + len)
+}
+
+/// Reads the subject out of a DER certificate as `CN=…, O=…`.
+fn cert_subject(cert: &[u8]) -> Option<String> {
+    let (_, cert, _) = der(cert)?;
+    let (_, mut tbs, _) = der(cert)?;
+    // version is an optional [0] before the serial number
+    if tbs.first() == Some(&0xa0) {
+        tbs = der(tbs)?.2;
+    }
+    // serial, signature algorithm, issuer, validity
+    for _ in 0..4 {
+        tbs = der(tbs)?.2;
+    }
+    let (_, subject, _) = der(tbs)?;
+
+    let mut parts = vec![];
+    let mut rdns = subject;
+    while let Some((_, set, rest)) = der(rdns) {
+        rdns = rest;
+        let mut attrs = set;
+        while let Some((_, attr, rest)) = der(attrs) {
+            attrs = rest;
+            let Some((_, oid, value)) = der(attr) else {
+                continue;
+            };
+            let label = match oid {
+                [0x55, 4, 3] => "CN",
+                [0x55, 4, 10] => "O",
+                [0x55, 4, 11] => "OU",
+                _ => continue,
+            };
+            if let Some((_, value, _)) = der(value) {
+                parts.push(format!("{label}={}", String::from_utf8_lossy(value)));
+            }
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
+/// Splits one DER TLV off the front of `input`: (tag, contents, rest).
+fn der(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let n = (first & 0x7f) as usize;
+        if n == 0 || n > 4 || rest.len() < n {
+            return None;
+        }
+        let len = rest[..n].iter().fold(0usize, |acc, b| acc << 8 | *b as usize);
+        (len, &rest[n..])
+    };
+    if rest.len() < len {
+        return None;
+    }
+    Some((tag, &rest[..len], &rest[len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::TcpListener,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use super::*;
+
+    /// Accepts connections forever: writes `greeting`, then answers the first
+    /// chunk it reads with whatever `reply` returns.
+    fn serve(
+        greeting: &'static [u8],
+        reply: fn(&[u8]) -> Option<Vec<u8>>,
+    ) -> (SocketAddr, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        thread::spawn(move || {
+            for mut conn in listener.incoming().flatten() {
+                counter.fetch_add(1, Ordering::SeqCst);
+                thread::spawn(move || {
+                    let _ = conn.write_all(greeting);
+                    let _ = conn.set_read_timeout(Some(Duration::from_secs(2)));
+                    let mut buf = [0u8; 4096];
+                    if let Ok(n @ 1..) = conn.read(&mut buf) {
+                        if let Some(out) = reply(&buf[..n]) {
+                            let _ = conn.write_all(&out);
+                        }
+                    }
+                });
+            }
+        });
+        (addr, accepted)
+    }
+
+    fn tlv(tag: u8, body: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        match body.len() {
+            n if n < 0x80 => out.push(n as u8),
+            n if n < 0x100 => out.extend([0x81, n as u8]),
+            n => out.extend([0x82, (n >> 8) as u8, n as u8]),
+        }
+        out.extend(body);
+        out
+    }
+
+    fn certificate(cn: &str, org: &str) -> Vec<u8> {
+        let attr = |oid: u8, value: &str| {
+            tlv(0x31, &tlv(0x30, &[tlv(0x06, &[0x55, 4, oid]), tlv(0x0c, value.as_bytes())].concat()))
+        };
+        let alg = tlv(0x30, &tlv(0x06, &[0x2a, 0x86, 0x48, 0xce, 0x3d, 4, 3, 2]));
+        let tbs = tlv(
+            0x30,
+            &[
+                tlv(0xa0, &tlv(0x02, &[2])),
+                tlv(0x02, &[1]),
+                alg.clone(),
+                tlv(0x30, &attr(3, "Dev CA")),
+                tlv(0x30, &[]),
+                tlv(0x30, &[attr(10, org), attr(3, cn)].concat()),
+            ]
+            .concat(),
+        );
+        tlv(0x30, &[tbs, alg, tlv(0x03, &[0])].concat())
+    }
+
+    fn server_flight(_: &[u8]) -> Option<Vec<u8>> {
+        let cert = certificate("localhost", "mkcert development certificate");
+        let u24 = |n: usize| (n as u32).to_be_bytes()[1..].to_vec();
+        let mut hs = vec![2];
+        hs.extend(u24(38));
+        hs.extend([0u8; 38]);
+        let mut list = u24(cert.len());
+        list.extend(&cert);
+        hs.push(11);
+        hs.extend(u24(list.len() + 3));
+        hs.extend(u24(list.len()));
+        hs.extend(list);
+        let mut record = vec![0x16, 3, 3];
+        record.extend((hs.len() as u16).to_be_bytes());
+        record.extend(hs);
+        Some(record)
+    }
+
+    #[test]
+    fn classifies_http_with_server_and_title() {
+        let (addr, _) = serve(b"", |req| {
+            req.starts_with(b"GET ").then(|| {
+                b"HTTP/1.1 200 OK\r\nServer: vite\r\nContent-Type: text/html\r\n\r\n<html><head><title>\n  My   App </title></head></html>".to_vec()
+            })
+        });
+        assert_eq!(
+            probe(addr),
+            Some(ServiceInfo::Http {
+                status: 200,
+                server: Some("vite".to_string()),
+                title: Some("My App".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn classifies_server_first_banners() {
+        let (ssh, _) = serve(b"SSH-2.0-OpenSSH_9.6\r\n", |_| None);
+        assert_eq!(
+            probe(ssh),
+            Some(ServiceInfo::Ssh {
+                banner: "SSH-2.0-OpenSSH_9.6".to_string()
+            })
+        );
+
+        let (mysql, _) = serve(b"\x4a\x00\x00\x00\x0a8.0.36\x00\x08\x00\x00\x00", |_| None);
+        assert_eq!(
+            probe(mysql),
+            Some(ServiceInfo::Mysql {
+                version: Some("8.0.36".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn classifies_redis_and_postgres() {
+        let (redis, _) = serve(b"", |req| {
+            Some(if req == REDIS_PING {
+                b"+PONG\r\n".to_vec()
+            } else {
+                b"-ERR unknown command\r\n".to_vec()
+            })
+        });
+        assert_eq!(probe(redis), Some(ServiceInfo::Redis));
+
+        let (postgres, _) = serve(b"", |req| (req == POSTGRES_SSL_REQUEST).then(|| b"N".to_vec()));
+        assert_eq!(probe(postgres), Some(ServiceInfo::Postgres));
+    }
+
+    #[test]
+    fn reads_tls_certificate_subject() {
+        let (addr, _) = serve(b"", |req| {
+            if req.first() == Some(&0x16) {
+                server_flight(req)
+            } else {
+                Some(b"HTTP/1.1 400 Bad Request\r\n\r\n".to_vec())
+            }
+        });
+        assert_eq!(
+            probe(addr),
+            Some(ServiceInfo::Tls {
+                subject: Some("O=mkcert development certificate, CN=localhost".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn closed_port_is_unreachable() {
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        assert_eq!(probe(addr), None);
+    }
+
+    #[test]
+    fn caches_results_per_pid_and_port() {
+        let (addr, accepted) = serve(b"SSH-2.0-test\r\n", |_| None);
+        let mut ports = vec![PortInfo {
+            port: addr.port(),
+            pid: 42,
+            protocol: "tcp".to_string(),
+            process_name: "sshd".to_string(),
+            address: format!("*:{}", addr.port()),
+            user: "root".to_string(),
+            fd: "3".to_string(),
+            command: None,
+            is_active: false,
+            addresses: vec![format!("*:{}", addr.port())],
+            owners: vec![],
+            loopback_only: false,
+            details: None,
+            project: None,
+            connection_count: 0,
+            service: None,
+        }];
+
+        let prober = Prober::new();
+        prober.apply(&mut ports);
+        assert!(ports[0].is_active);
+        assert!(matches!(ports[0].service, Some(ServiceInfo::Ssh { .. })));
+        let probes = accepted.load(Ordering::SeqCst);
+
+        prober.apply(&mut ports);
+        assert_eq!(accepted.load(Ordering::SeqCst), probes);
+
+        ports[0].pid = 43;
+        prober.apply(&mut ports);
+        assert!(accepted.load(Ordering::SeqCst) > probes);
+    }
+}
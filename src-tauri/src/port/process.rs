@@ -0,0 +1,391 @@
+use std::{
+    collections::HashMap,
+    process::Command,
+};
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{clock::now_ms, port::scanner::lsof_command};
+
+#[derive(Debug, Error)]
+pub enum ProcessError {
+    #[error("invalid pid")]
+    InvalidPid,
+
+    #[error("process {0} not found")]
+    NotFound(u32),
+}
+
+/// Everything we can learn about a listening process beyond its name.
+/// Fields the platform can't provide (or we lack permission for) are `None`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProcessDetails {
+    pub pid: u32,
+    pub name: String,
+    pub command_line: Option<String>,
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+    pub ppid: Option<u32>,
+    pub start_time_ms: Option<u64>,
+    pub rss_bytes: Option<u64>,
+    /// Average CPU usage over the process lifetime, like `ps %cpu`.
+    pub cpu_percent: Option<f64>,
+}
+
+pub fn get_process_details(pid: u32) -> Result<ProcessDetails, ProcessError> {
+    if pid == 0 {
+        return Err(ProcessError::InvalidPid);
+    }
+    details_for(&[pid])
+        .remove(&pid)
+        .ok_or(ProcessError::NotFound(pid))
+}
+
+/// Looks up several processes at once; on platforms that shell out this keeps
+/// it to one subprocess per scan instead of one per pid.
+pub fn details_for(pids: &[u32]) -> HashMap<u32, ProcessDetails> {
+    let mut pids: Vec<u32> = pids.iter().copied().filter(|p| *p != 0).collect();
+    pids.sort_unstable();
+    pids.dedup();
+    if pids.is_empty() {
+        return HashMap::new();
+    }
+
+    if cfg!(target_os = "windows") {
+        details_windows(&pids)
+    } else if cfg!(target_os = "linux") {
+        details_linux(&pids)
+    } else {
+        details_ps(&pids)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn details_linux(pids: &[u32]) -> HashMap<u32, ProcessDetails> {
+    let root = std::path::Path::new("/proc");
+    pids.iter()
+        .filter_map(|pid| linux::read(root, *pid).map(|d| (*pid, d)))
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn details_linux(pids: &[u32]) -> HashMap<u32, ProcessDetails> {
+    details_ps(pids)
+}
+
+#[cfg(target_os = "linux")]
+pub mod linux {
+    use std::{fs, path::Path};
+
+    use super::{now_ms, ProcessDetails};
+
+    // USER_HZ is fixed at 100 for the /proc ABI regardless of the kernel's HZ.
+    const CLOCK_TICKS: u64 = 100;
+
+    pub fn read(root: &Path, pid: u32) -> Option<ProcessDetails> {
+        let dir = root.join(pid.to_string());
+        let stat = parse_stat(&fs::read_to_string(dir.join("stat")).ok()?)?;
+
+        let args: Vec<String> = fs::read(dir.join("cmdline"))
+            .map(|raw| {
+                raw.split(|b| *b == 0)
+                    .filter(|a| !a.is_empty())
+                    .map(|a| String::from_utf8_lossy(a).to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let name = fs::read_to_string(dir.join("comm"))
+            .map(|s| s.trim_end_matches('\n').to_string())
+            .unwrap_or_else(|_| stat.comm.clone());
+        let cwd = fs::read_link(dir.join("cwd"))
+            .ok()
+            .map(|p| p.to_string_lossy().to_string());
+        let rss_bytes = fs::read_to_string(dir.join("status"))
+            .ok()
+            .and_then(|s| parse_vm_rss(&s));
+
+        let boot_ms = fs::read_to_string(root.join("stat"))
+            .ok()
+            .and_then(|s| parse_btime(&s))
+            .map(|secs| secs * 1000);
+        let start_time_ms = boot_ms.map(|b| b + stat.start_ticks * 1000 / CLOCK_TICKS);
+        let cpu_percent = start_time_ms.and_then(|start| {
+            let elapsed_ms = now_ms().checked_sub(start)?;
+            if elapsed_ms == 0 {
+                return None;
+            }
+            let cpu_ms = (stat.utime + stat.stime) * 1000 / CLOCK_TICKS;
+            Some(cpu_ms as f64 * 100.0 / elapsed_ms as f64)
+        });
+
+        Some(ProcessDetails {
+            pid,
+            name,
+            command_line: (!args.is_empty()).then(|| args.join(" ")),
+            args,
+            cwd,
+            ppid: Some(stat.ppid),
+            start_time_ms,
+            rss_bytes,
+            cpu_percent,
+        })
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct Stat {
+        pub comm: String,
+        pub ppid: u32,
+        pub utime: u64,
+        pub stime: u64,
+        pub start_ticks: u64,
+    }
+
+    pub fn parse_stat(raw: &str) -> Option<Stat> {
+        // `comm` is wrapped in parens and may itself contain spaces or ')'.
+        let open = raw.find('(')?;
+        let close = raw.rfind(')')?;
+        let comm = raw.get(open + 1..close)?.to_string();
+        // Fields after comm, starting at field 3 (state).
+        let rest: Vec<&str> = raw.get(close + 1..)?.split_whitespace().collect();
+        let field = |n: usize| rest.get(n - 3).and_then(|v| v.parse::<u64>().ok());
+
+        Some(Stat {
+            comm,
+            ppid: u32::try_from(field(4)?).ok()?,
+            utime: field(14)?,
+            stime: field(15)?,
+            start_ticks: field(22)?,
+        })
+    }
+
+    fn parse_vm_rss(status: &str) -> Option<u64> {
+        let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+        let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+        Some(kb * 1024)
+    }
+
+    fn parse_btime(stat: &str) -> Option<u64> {
+        stat.lines()
+            .find_map(|l| l.strip_prefix("btime "))
+            .and_then(|v| v.trim().parse().ok())
+    }
+}
+
+fn details_ps(pids: &[u32]) -> HashMap<u32, ProcessDetails> {
+    let pid_list = pids
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    // `command` must be last: it is the only column that can contain spaces.
+    let Ok(out) = Command::new("ps")
+        .args(["-o", "pid=,ppid=,rss=,%cpu=,etime=,command=", "-p", &pid_list])
+        .output()
+    else {
+        return HashMap::new();
+    };
+
+    let now = now_ms();
+    let mut details: HashMap<u32, ProcessDetails> = HashMap::new();
+    for line in String::from_utf8_lossy(&out.stdout).lines() {
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        if cols.len() < 6 {
+            continue;
+        }
+        let Ok(pid) = cols[0].parse::<u32>() else {
+            continue;
+        };
+        let args: Vec<String> = cols[5..].iter().map(|s| s.to_string()).collect();
+        let name = cols[5].rsplit('/').next().unwrap_or(cols[5]).to_string();
+        details.insert(
+            pid,
+            ProcessDetails {
+                pid,
+                name,
+                command_line: (!args.is_empty()).then(|| args.join(" ")),
+                args,
+                cwd: None,
+                ppid: cols[1].parse().ok(),
+                start_time_ms: parse_etime(cols[4]).and_then(|secs| now.checked_sub(secs * 1000)),
+                rss_bytes: cols[2].parse::<u64>().ok().map(|kb| kb * 1024),
+                cpu_percent: cols[3].parse().ok(),
+            },
+        );
+    }
+
+    for (pid, cwd) in cwd_lsof(pids) {
+        if let Some(d) = details.get_mut(&pid) {
+            d.cwd = Some(cwd);
+        }
+    }
+    details
+}
+
+/// `ps` has no cwd column on macOS; lsof's field output gives it per pid.
+fn cwd_lsof(pids: &[u32]) -> HashMap<u32, String> {
+    let pid_list = pids
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let Ok(out) = Command::new(lsof_command())
+        .args(["-a", "-d", "cwd", "-p", &pid_list, "-Fn"])
+        .output()
+    else {
+        return HashMap::new();
+    };
+
+    let mut cwds = HashMap::new();
+    let mut current: Option<u32> = None;
+    for line in String::from_utf8_lossy(&out.stdout).lines() {
+        if let Some(pid) = line.strip_prefix('p') {
+            current = pid.parse().ok();
+        } else if let (Some(path), Some(pid)) = (line.strip_prefix('n'), current) {
+            cwds.insert(pid, path.to_string());
+        }
+    }
+    cwds
+}
+
+/// Parses `ps` elapsed time: `[[dd-]hh:]mm:ss`.
+fn parse_etime(raw: &str) -> Option<u64> {
+    let (days, clock) = match raw.split_once('-') {
+        Some((d, rest)) => (d.parse::<u64>().ok()?, rest),
+        None => (0, raw),
+    };
+    let parts: Vec<u64> = clock
+        .split(':')
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    let secs = match parts.as_slice() {
+        [m, s] => m * 60 + s,
+        [h, m, s] => h * 3600 + m * 60 + s,
+        _ => return None,
+    };
+    Some(days * 86_400 + secs)
+}
+
+fn details_windows(pids: &[u32]) -> HashMap<u32, ProcessDetails> {
+    let filter = pids
+        .iter()
+        .map(|p| format!("ProcessId={p}"))
+        .collect::<Vec<_>>()
+        .join(" OR ");
+    let script = format!(
+        "Get-CimInstance Win32_Process -Filter '{filter}' | \
+         Select-Object ProcessId,ParentProcessId,Name,CommandLine,WorkingSetSize,CreationDate | \
+         ConvertTo-Json -Compress"
+    );
+
+    let Ok(out) = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .output()
+    else {
+        return HashMap::new();
+    };
+
+    // ConvertTo-Json emits a bare object for a single result and an array otherwise.
+    let value: serde_json::Value = match serde_json::from_slice(&out.stdout) {
+        Ok(v) => v,
+        Err(_) => return HashMap::new(),
+    };
+    let items = match value {
+        serde_json::Value::Array(items) => items,
+        other => vec![other],
+    };
+
+    items
+        .iter()
+        .filter_map(|item| {
+            let pid = item.get("ProcessId")?.as_u64()? as u32;
+            let command_line = item
+                .get("CommandLine")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string());
+            Some((
+                pid,
+                ProcessDetails {
+                    pid,
+                    name: item
+                        .get("Name")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("unknown")
+                        .to_string(),
+                    args: command_line
+                        .as_deref()
+                        .map(|c| c.split_whitespace().map(|s| s.to_string()).collect())
+                        .unwrap_or_default(),
+                    command_line,
+                    cwd: None,
+                    ppid: item
+                        .get("ParentProcessId")
+                        .and_then(|v| v.as_u64())
+                        .map(|v| v as u32),
+                    start_time_ms: item
+                        .get("CreationDate")
+                        .and_then(|v| v.as_str())
+                        .and_then(parse_json_date),
+                    rss_bytes: item.get("WorkingSetSize").and_then(|v| v.as_u64()),
+                    cpu_percent: None,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Windows PowerShell 5 serializes dates as `/Date(1700000000000)/`.
+fn parse_json_date(raw: &str) -> Option<u64> {
+    let digits: String = raw
+        .trim_start_matches("/Date(")
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ps_etime() {
+        assert_eq!(parse_etime("05:07"), Some(307));
+        assert_eq!(parse_etime("01:05:07"), Some(3907));
+        assert_eq!(parse_etime("2-01:05:07"), Some(2 * 86_400 + 3907));
+        assert_eq!(parse_etime("bogus"), None);
+    }
+
+    #[test]
+    fn parses_powershell_dates() {
+        assert_eq!(parse_json_date("/Date(1700000000123)/"), Some(1_700_000_000_123));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn parses_stat_with_awkward_comm() {
+        let raw = "4242 (my) server) S 1 4242 4242 0 -1 4194304 100 0 0 0 250 50 0 0 20 0 1 0 987654 1000 10 18446744073709551615";
+        let stat = linux::parse_stat(raw).unwrap();
+        assert_eq!(stat.comm, "my) server");
+        assert_eq!(stat.ppid, 1);
+        assert_eq!(stat.utime, 250);
+        assert_eq!(stat.stime, 50);
+        assert_eq!(stat.start_ticks, 987654);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn reads_own_process() {
+        let pid = std::process::id();
+        let d = get_process_details(pid).unwrap();
+        assert_eq!(d.pid, pid);
+        assert_eq!(
+            d.cwd.as_deref(),
+            std::env::current_dir().ok().as_deref().and_then(|p| p.to_str())
+        );
+        assert!(!d.args.is_empty());
+        assert!(d.rss_bytes.unwrap_or(0) > 0);
+        assert!(d.start_time_ms.unwrap_or(0) <= now_ms());
+    }
+}
@@ -0,0 +1,453 @@
+use std::{
+    collections::HashMap,
+    fs,
+    net::{Ipv4Addr, Ipv6Addr},
+    path::Path,
+};
+
+use crate::port::{
+    connections::{tcp_state_name, Connection},
+    scanner::{Listener, ScanError},
+};
+
+pub const TCP_LISTEN: u8 = 0x0A;
+// Unconnected UDP sockets report TCP_CLOSE; connected ones report ESTABLISHED.
+pub const UDP_UNCONNECTED: u8 = 0x07;
+
+// (table, protocol, ipv6, state that marks a listener)
+const TABLES: [(&str, &str, bool, u8); 4] = [
+    ("net/tcp", "tcp", false, TCP_LISTEN),
+    ("net/tcp6", "tcp", true, TCP_LISTEN),
+    ("net/udp", "udp", false, UDP_UNCONNECTED),
+    ("net/udp6", "udp", true, UDP_UNCONNECTED),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetEntry {
+    pub address: String,
+    pub port: u16,
+    pub remote_ip: String,
+    pub remote_port: u16,
+    pub state: u8,
+    pub uid: u32,
+    pub inode: u64,
+}
+
+#[derive(Debug, Clone)]
+struct SocketOwner {
+    pid: u32,
+    fd: String,
+}
+
+/// Native Linux backend: reads `/proc/net/{tcp,udp}{,6}` and maps socket inodes
+/// to processes through `/proc/<pid>/fd`. `root` is normally `/proc`.
+///
+/// Returns one row per (socket, owning process); a listening socket inherited
+/// across `fork` (nginx workers, prefork servers) yields a row for each holder.
+pub fn scan(root: &Path) -> Result<Vec<Listener>, ScanError> {
+    let mut entries: Vec<(&'static str, NetEntry)> = vec![];
+    for (table, protocol, v6, listen_state) in TABLES {
+        let rows = match read_net_table(&root.join(table), v6) {
+            Ok(rows) => rows,
+            // The IPv4 TCP table always exists; the others may be compiled out
+            // or disabled (e.g. ipv6.disable=1).
+            Err(e) if table == "net/tcp" => return Err(e),
+            Err(_) => continue,
+        };
+        entries.extend(
+            rows.into_iter()
+                .filter(|e| e.state == listen_state && e.port != 0)
+                .map(|e| (protocol, e)),
+        );
+    }
+
+    Ok(attribute(root, entries))
+}
+
+/// Every non-listening TCP socket from `/proc/net/tcp{,6}`.
+pub fn connections(root: &Path) -> Result<Vec<Connection>, ScanError> {
+    let mut entries = read_net_table(&root.join("net/tcp"), false)?;
+    entries.extend(read_net_table(&root.join("net/tcp6"), true).unwrap_or_default());
+    Ok(entries
+        .into_iter()
+        .filter(|e| e.state != TCP_LISTEN)
+        .map(to_connection)
+        .collect())
+}
+
+pub fn to_connection(e: NetEntry) -> Connection {
+    Connection {
+        local_address: e.address,
+        local_port: e.port,
+        remote_ip: e.remote_ip,
+        remote_port: e.remote_port,
+        state: tcp_state_name(e.state).to_string(),
+    }
+}
+
+/// Turns raw socket entries (from procfs or netlink) into listener rows by
+/// resolving inode -> processes through `root/<pid>/fd` and uid -> user name.
+pub fn attribute(root: &Path, entries: Vec<(&'static str, NetEntry)>) -> Vec<Listener> {
+    let owners = socket_owners(root);
+    let users = read_passwd(Path::new("/etc/passwd"));
+    let mut names: HashMap<u32, String> = HashMap::new();
+
+    let mut rows = vec![];
+    for (protocol, e) in entries {
+        let user = users
+            .get(&e.uid)
+            .cloned()
+            .unwrap_or_else(|| e.uid.to_string());
+
+        let Some(holders) = owners.get(&e.inode) else {
+            rows.push(Listener {
+                protocol: protocol.to_string(),
+                port: e.port,
+                address: e.address,
+                pid: 0,
+                process_name: "unknown".to_string(),
+                user,
+                fd: "-".to_string(),
+            });
+            continue;
+        };
+
+        for o in holders {
+            let process_name = names
+                .entry(o.pid)
+                .or_insert_with(|| read_comm(root, o.pid).unwrap_or_else(|| "unknown".to_string()))
+                .clone();
+            rows.push(Listener {
+                protocol: protocol.to_string(),
+                port: e.port,
+                address: e.address.clone(),
+                pid: o.pid,
+                process_name,
+                user: user.clone(),
+                fd: o.fd.clone(),
+            });
+        }
+    }
+
+    rows
+}
+
+fn read_net_table(path: &Path, v6: bool) -> Result<Vec<NetEntry>, ScanError> {
+    let raw = fs::read_to_string(path)
+        .map_err(|e| ScanError::CommandFailed(format!("{}: {e}", path.display())))?;
+    Ok(parse_net_table(&raw, v6))
+}
+
+/// Parses the text of a `/proc/net/{tcp,udp}{,6}` table. Malformed rows are skipped.
+pub fn parse_net_table(raw: &str, v6: bool) -> Vec<NetEntry> {
+    raw.lines()
+        .skip(1)
+        .filter_map(|line| parse_net_line(line, v6))
+        .collect()
+}
+
+fn parse_net_line(line: &str, v6: bool) -> Option<NetEntry> {
+    // sl local_address rem_address st tx_queue:rx_queue tr:tm->when retrnsmt uid timeout inode
+    let cols: Vec<&str> = line.split_whitespace().collect();
+    if cols.len() < 10 {
+        return None;
+    }
+
+    let (addr_hex, port_hex) = cols[1].split_once(':')?;
+    let port = u16::from_str_radix(port_hex, 16).ok()?;
+    let address = if v6 {
+        format_v6(parse_v6(addr_hex)?, port)
+    } else {
+        format_v4(parse_v4(addr_hex)?, port)
+    };
+    let (remote_hex, remote_port_hex) = cols[2].split_once(':')?;
+    let remote_port = u16::from_str_radix(remote_port_hex, 16).ok()?;
+    let remote_ip = if v6 {
+        parse_v6(remote_hex)?.to_string()
+    } else {
+        parse_v4(remote_hex)?.to_string()
+    };
+    let state = u8::from_str_radix(cols[3], 16).ok()?;
+    let uid = cols[7].parse().ok()?;
+    let inode = cols[9].parse().ok()?;
+
+    Some(NetEntry {
+        address,
+        port,
+        remote_ip,
+        remote_port,
+        state,
+        uid,
+        inode,
+    })
+}
+
+// The kernel prints each 32-bit word of the address in host byte order.
+fn parse_v4(hex: &str) -> Option<Ipv4Addr> {
+    if hex.len() != 8 {
+        return None;
+    }
+    let word = u32::from_str_radix(hex, 16).ok()?;
+    Some(Ipv4Addr::from(word.to_ne_bytes()))
+}
+
+fn parse_v6(hex: &str) -> Option<Ipv6Addr> {
+    if hex.len() != 32 {
+        return None;
+    }
+    let mut bytes = [0u8; 16];
+    for i in 0..4 {
+        let word = u32::from_str_radix(&hex[i * 8..i * 8 + 8], 16).ok()?;
+        bytes[i * 4..i * 4 + 4].copy_from_slice(&word.to_ne_bytes());
+    }
+    Some(Ipv6Addr::from(bytes))
+}
+
+// Match lsof's NAME column so both backends render the same way, except that the
+// IPv6 wildcard stays `[::]` so dual-stack binds remain distinguishable.
+pub fn format_v4(ip: Ipv4Addr, port: u16) -> String {
+    if ip.is_unspecified() {
+        format!("*:{port}")
+    } else {
+        format!("{ip}:{port}")
+    }
+}
+
+pub fn format_v6(ip: Ipv6Addr, port: u16) -> String {
+    format!("[{ip}]:{port}")
+}
+
+/// Maps socket inodes to every process holding them, in pid order. Processes
+/// whose `fd` directory we can't read (other users without privileges) are skipped.
+fn socket_owners(root: &Path) -> HashMap<u64, Vec<SocketOwner>> {
+    let mut out: HashMap<u64, Vec<SocketOwner>> = HashMap::new();
+    let Ok(procs) = fs::read_dir(root) else {
+        return out;
+    };
+
+    let mut pids: Vec<u32> = procs
+        .flatten()
+        .filter_map(|e| e.file_name().to_str().and_then(|s| s.parse().ok()))
+        .collect();
+    pids.sort_unstable();
+
+    for pid in pids {
+        let Ok(fds) = fs::read_dir(root.join(pid.to_string()).join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            let Ok(target) = fs::read_link(fd.path()) else {
+                continue;
+            };
+            let Some(inode) = parse_socket_link(&target.to_string_lossy()) else {
+                continue;
+            };
+            let holders = out.entry(inode).or_default();
+            // A process can hold the same socket on several fds; keep the first.
+            if holders.last().map(|h| h.pid) != Some(pid) {
+                holders.push(SocketOwner {
+                    pid,
+                    fd: fd.file_name().to_string_lossy().to_string(),
+                });
+            }
+        }
+    }
+    out
+}
+
+fn parse_socket_link(target: &str) -> Option<u64> {
+    target
+        .strip_prefix("socket:[")?
+        .strip_suffix(']')?
+        .parse()
+        .ok()
+}
+
+fn read_comm(root: &Path, pid: u32) -> Option<String> {
+    let raw = fs::read_to_string(root.join(pid.to_string()).join("comm")).ok()?;
+    let name = raw.trim_end_matches('\n').to_string();
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+fn read_passwd(path: &Path) -> HashMap<u32, String> {
+    fs::read_to_string(path)
+        .map(|raw| parse_passwd(&raw))
+        .unwrap_or_default()
+}
+
+pub fn parse_passwd(raw: &str) -> HashMap<u32, String> {
+    raw.lines()
+        .filter(|l| !l.starts_with('#'))
+        .filter_map(|l| {
+            let mut cols = l.split(':');
+            let name = cols.next()?;
+            let uid = cols.nth(1)?.parse().ok()?;
+            Some((uid, name.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::fs::symlink, path::PathBuf};
+
+    use super::*;
+    use crate::port::scanner::group_listeners;
+
+    const TCP: &str = include_str!("../../tests/fixtures/procfs/tcp");
+    const TCP6: &str = include_str!("../../tests/fixtures/procfs/tcp6");
+    const UDP: &str = include_str!("../../tests/fixtures/procfs/udp");
+
+    fn fake_proc(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("jule-procfs-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("net")).unwrap();
+        fs::write(root.join("net/tcp"), TCP).unwrap();
+        fs::write(root.join("net/tcp6"), TCP6).unwrap();
+        fs::write(root.join("net/udp"), UDP).unwrap();
+        root
+    }
+
+    fn fake_process(root: &Path, pid: u32, comm: &str, sockets: &[(u32, u64)]) {
+        let dir = root.join(pid.to_string());
+        fs::create_dir_all(dir.join("fd")).unwrap();
+        fs::write(dir.join("comm"), format!("{comm}\n")).unwrap();
+        for (fd, inode) in sockets {
+            symlink(format!("socket:[{inode}]"), dir.join("fd").join(fd.to_string())).unwrap();
+        }
+    }
+
+    #[test]
+    fn parses_ipv4_table() {
+        let rows = parse_net_table(TCP, false);
+        assert_eq!(rows.len(), 4);
+        assert_eq!(
+            rows[0],
+            NetEntry {
+                address: "127.0.0.1:5432".to_string(),
+                port: 5432,
+                remote_ip: "0.0.0.0".to_string(),
+                remote_port: 0,
+                state: TCP_LISTEN,
+                uid: 1000,
+                inode: 41001,
+            }
+        );
+        assert_eq!(rows[1].address, "*:8080");
+        assert_eq!(rows[3].state, 0x01);
+    }
+
+    #[test]
+    fn parses_ipv6_table() {
+        let rows = parse_net_table(TCP6, true);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].address, "[::1]:5173");
+        assert_eq!(rows[0].port, 5173);
+        assert_eq!(rows[1].address, "[::]:8080");
+    }
+
+    #[test]
+    fn skips_malformed_rows() {
+        let raw = "header\n   0: zz:1F90 00000000:0000 0A\n   1: 0100007F:1F90 00000000:0000 0A 0:0 0:0 0 1000 0 77\n";
+        let rows = parse_net_table(raw, false);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].inode, 77);
+    }
+
+    #[test]
+    fn parses_passwd_entries() {
+        let users = parse_passwd("# comment\nroot:x:0:0:root:/root:/bin/sh\ndev:x:1000:1000::/home/dev:/bin/bash\n");
+        assert_eq!(users.get(&0).map(String::as_str), Some("root"));
+        assert_eq!(users.get(&1000).map(String::as_str), Some("dev"));
+    }
+
+    #[test]
+    fn maps_inodes_to_processes() {
+        let root = fake_proc("owners");
+        fake_process(&root, 3131, "systemd-resolve", &[(12, 71001)]);
+        fake_process(&root, 4242, "postgres", &[(7, 41001)]);
+        fake_process(&root, 5151, "node", &[(21, 41002), (22, 61001), (23, 71003)]);
+        // nginx master and worker share the listening socket inherited across fork.
+        fake_process(&root, 6001, "nginx", &[(6, 41003)]);
+        fake_process(&root, 6002, "nginx", &[(6, 41003)]);
+
+        let ports = group_listeners(scan(&root).unwrap());
+        let _ = fs::remove_dir_all(&root);
+
+        let ports: Vec<(&str, u16, u32, &str, Vec<u32>)> = ports
+            .iter()
+            .map(|p| {
+                (
+                    p.protocol.as_str(),
+                    p.port,
+                    p.pid,
+                    p.process_name.as_str(),
+                    p.owners.iter().map(|o| o.pid).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            ports,
+            vec![
+                ("udp", 53, 3131, "systemd-resolve", vec![3131]),
+                ("tcp", 5173, 5151, "node", vec![5151]),
+                ("tcp", 5432, 4242, "postgres", vec![4242]),
+                ("tcp", 8080, 5151, "node", vec![5151]),
+                ("udp", 8080, 5151, "node", vec![5151]),
+                ("udp", 8125, 0, "unknown", vec![]),
+                ("tcp", 9000, 6001, "nginx", vec![6001, 6002]),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_every_bind_address() {
+        let root = fake_proc("addresses");
+        let ports = group_listeners(scan(&root).unwrap());
+        let _ = fs::remove_dir_all(&root);
+
+        let find = |protocol: &str, port: u16| {
+            ports
+                .iter()
+                .find(|p| p.protocol == protocol && p.port == port)
+                .unwrap()
+        };
+        assert_eq!(find("tcp", 8080).addresses, vec!["*:8080", "[::]:8080"]);
+        assert!(!find("tcp", 8080).loopback_only);
+        assert_eq!(find("tcp", 5432).addresses, vec!["127.0.0.1:5432"]);
+        assert!(find("tcp", 5432).loopback_only);
+        assert!(find("tcp", 5173).loopback_only);
+        assert!(find("udp", 53).loopback_only);
+    }
+
+    #[test]
+    fn lists_tcp_connections() {
+        let root = fake_proc("connections");
+        let conns = connections(&root).unwrap();
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(
+            conns,
+            vec![Connection {
+                local_address: "127.0.0.1:5432".to_string(),
+                local_port: 5432,
+                remote_ip: "127.0.0.1".to_string(),
+                remote_port: 54000,
+                state: "established".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_udp_bound_sockets() {
+        let rows = parse_net_table(UDP, false);
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0].address, "127.0.0.53:53");
+        assert_eq!(rows[0].state, UDP_UNCONNECTED);
+        assert_eq!(rows[3].state, 0x01);
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+/// The repository a listener was started from, detected from its working directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProjectInfo {
+    pub root: String,
+    pub name: String,
+    /// Which marker identified the root: `node`, `rust`, `python`, `go` or `git`.
+    pub kind: String,
+    /// Best guess at the dev server, e.g. `vite`, `next`, `django`, `rails`.
+    pub framework: Option<String>,
+    pub git_branch: Option<String>,
+}
+
+// Checked in order within each directory; the first hit wins.
+const MARKERS: [(&str, &str); 5] = [
+    ("package.json", "node"),
+    ("Cargo.toml", "rust"),
+    ("pyproject.toml", "python"),
+    ("go.mod", "go"),
+    (".git", "git"),
+];
+
+/// Walks up from `cwd` to the nearest project marker. Returns `None` for `/`
+/// and for directories outside any project.
+pub fn detect_project(cwd: &Path) -> Option<ProjectInfo> {
+    // Daemons chdir to `/`; anything found above it would be a coincidence.
+    cwd.parent()?;
+
+    let (root, kind) = cwd.ancestors().find_map(|dir| {
+        MARKERS
+            .iter()
+            .find(|(marker, _)| dir.join(marker).exists())
+            .map(|(_, kind)| (dir.to_path_buf(), *kind))
+    })?;
+
+    let name = project_name(&root, kind).unwrap_or_else(|| {
+        root.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| root.to_string_lossy().to_string())
+    });
+
+    Some(ProjectInfo {
+        root: root.to_string_lossy().to_string(),
+        name,
+        kind: kind.to_string(),
+        framework: detect_framework(&root),
+        git_branch: find_git_dir(&root).and_then(|g| read_branch(&g)),
+    })
+}
+
+fn project_name(root: &Path, kind: &str) -> Option<String> {
+    match kind {
+        "node" => {
+            let raw = fs::read_to_string(root.join("package.json")).ok()?;
+            let v: serde_json::Value = serde_json::from_str(&raw).ok()?;
+            v.get("name")?.as_str().map(|s| s.to_string())
+        }
+        "rust" => toml_string(
+            &fs::read_to_string(root.join("Cargo.toml")).ok()?,
+            &["package"],
+            "name",
+        ),
+        "python" => toml_string(
+            &fs::read_to_string(root.join("pyproject.toml")).ok()?,
+            &["project", "tool.poetry"],
+            "name",
+        ),
+        "go" => fs::read_to_string(root.join("go.mod"))
+            .ok()?
+            .lines()
+            .find_map(|l| l.trim().strip_prefix("module "))
+            .map(|m| m.trim().rsplit('/').next().unwrap_or(m).to_string()),
+        _ => None,
+    }
+}
+
+/// Reads `key = "value"` from one of `sections`. Just enough TOML for manifest
+/// names; we don't need a full parser for that.
+fn toml_string(raw: &str, sections: &[&str], key: &str) -> Option<String> {
+    let mut in_section = false;
+    for line in raw.lines() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_section = sections.contains(&header.trim());
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+        let Some((k, v)) = line.split_once('=') else {
+            continue;
+        };
+        if k.trim() == key {
+            let v = v.trim().trim_matches('"').trim_matches('\'');
+            if !v.is_empty() {
+                return Some(v.to_string());
+            }
+        }
+    }
+    None
+}
+
+fn detect_framework(root: &Path) -> Option<String> {
+    let has_any = |prefix: &str| {
+        ["js", "ts", "mjs", "cjs", "mts"]
+            .iter()
+            .any(|ext| root.join(format!("{prefix}.{ext}")).exists())
+    };
+
+    let found = if has_any("next.config") {
+        "next"
+    } else if has_any("nuxt.config") {
+        "nuxt"
+    } else if has_any("svelte.config") {
+        "sveltekit"
+    } else if has_any("astro.config") {
+        "astro"
+    } else if has_any("vite.config") {
+        "vite"
+    } else if root.join("manage.py").exists() {
+        "django"
+    } else if root.join("bin/rails").exists() || root.join("config/application.rb").exists() {
+        "rails"
+    } else {
+        return node_dependency_hint(root);
+    };
+    Some(found.to_string())
+}
+
+// Projects without a config file still declare the framework as a dependency.
+fn node_dependency_hint(root: &Path) -> Option<String> {
+    let raw = fs::read_to_string(root.join("package.json")).ok()?;
+    let v: serde_json::Value = serde_json::from_str(&raw).ok()?;
+    let has = |dep: &str| {
+        ["dependencies", "devDependencies"]
+            .iter()
+            .any(|k| v.get(k).and_then(|d| d.get(dep)).is_some())
+    };
+    ["next", "nuxt", "vite", "express", "fastify"]
+        .into_iter()
+        .find(|dep| has(dep))
+        .map(|dep| dep.to_string())
+}
+
+/// Finds the git dir for `root`, following `gitdir:` files used by worktrees
+/// and submodules.
+fn find_git_dir(root: &Path) -> Option<PathBuf> {
+    let dot_git = root.ancestors().map(|d| d.join(".git")).find(|p| p.exists())?;
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let raw = fs::read_to_string(&dot_git).ok()?;
+    let target = raw.trim().strip_prefix("gitdir:")?.trim();
+    let path = PathBuf::from(target);
+    Some(if path.is_absolute() {
+        path
+    } else {
+        dot_git.parent()?.join(path)
+    })
+}
+
+fn read_branch(git_dir: &Path) -> Option<String> {
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref: ") {
+        Some(r) => Some(r.strip_prefix("refs/heads/").unwrap_or(r).to_string()),
+        // Detached HEAD: show the short hash like `git status` does.
+        None => head.get(..7).map(|s| s.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("jule-project-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn detects_node_project_from_subdirectory() {
+        let root = scratch("node");
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join(".git/HEAD"), "ref: refs/heads/feature/login\n").unwrap();
+        fs::write(root.join("package.json"), r#"{"name":"web-app","devDependencies":{"vite":"^5"}}"#).unwrap();
+        fs::write(root.join("vite.config.ts"), "").unwrap();
+        fs::create_dir_all(root.join("src/pages")).unwrap();
+
+        let p = detect_project(&root.join("src/pages")).unwrap();
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(p.name, "web-app");
+        assert_eq!(p.kind, "node");
+        assert_eq!(p.framework.as_deref(), Some("vite"));
+        assert_eq!(p.git_branch.as_deref(), Some("feature/login"));
+    }
+
+    #[test]
+    fn detects_django_in_python_project() {
+        let root = scratch("python");
+        fs::write(root.join("pyproject.toml"), "[tool.poetry]\nname = \"billing\"\nversion = \"0.1.0\"\n").unwrap();
+        fs::write(root.join("manage.py"), "").unwrap();
+
+        let p = detect_project(&root).unwrap();
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(p.name, "billing");
+        assert_eq!(p.kind, "python");
+        assert_eq!(p.framework.as_deref(), Some("django"));
+    }
+
+    #[test]
+    fn reads_cargo_package_name_and_detached_head() {
+        let root = scratch("rust");
+        fs::write(root.join("Cargo.toml"), "[workspace]\nname = \"nope\"\n\n[package]\nname = \"api-server\"\n").unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join(".git/HEAD"), "4d8875f0c1e2a3b4c5d6e7f8091a2b3c4d5e6f70\n").unwrap();
+
+        let p = detect_project(&root).unwrap();
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(p.name, "api-server");
+        assert_eq!(p.framework, None);
+        assert_eq!(p.git_branch.as_deref(), Some("4d8875f"));
+    }
+
+    #[test]
+    fn ignores_filesystem_root() {
+        assert_eq!(detect_project(Path::new("/")), None);
+    }
+}
@@ -1,28 +1,228 @@
 use std::collections::HashMap;
+use std::net::IpAddr;
 use std::path::Path;
 use std::process::Command;
 
 use thiserror::Error;
 
-use crate::port::PortInfo;
+use crate::port::{
+    connections::{attach_connection_counts, split_host_port, Connection},
+    process::details_for,
+    project::detect_project,
+    PortInfo, PortOwner,
+};
 
 #[derive(Debug, Error)]
 pub enum ScanError {
     #[error("command failed: {0}")]
     CommandFailed(String),
+
+    #[error("unknown scan backend: {0}")]
+    UnknownBackend(String),
 }
 
-pub fn scan_listening_ports() -> Result<Vec<PortInfo>, ScanError> {
-    if cfg!(target_os = "windows") {
-        scan_windows()
-    } else {
+/// A source of listening sockets. Backends only report raw rows; grouping and
+/// process enrichment are shared.
+pub trait ScanBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn scan(&self) -> Result<Vec<Listener>, ScanError>;
+    /// Every non-listening TCP socket, used for per-port client lists.
+    fn connections(&self) -> Result<Vec<Connection>, ScanError>;
+}
+
+pub const AUTO_BACKEND: &str = "auto";
+
+pub struct LsofBackend;
+
+impl ScanBackend for LsofBackend {
+    fn name(&self) -> &'static str {
+        "lsof"
+    }
+
+    fn scan(&self) -> Result<Vec<Listener>, ScanError> {
         scan_unix()
     }
+
+    fn connections(&self) -> Result<Vec<Connection>, ScanError> {
+        connections_unix()
+    }
+}
+
+pub struct NetstatBackend;
+
+impl ScanBackend for NetstatBackend {
+    fn name(&self) -> &'static str {
+        "netstat"
+    }
+
+    fn scan(&self) -> Result<Vec<Listener>, ScanError> {
+        scan_windows()
+    }
+
+    fn connections(&self) -> Result<Vec<Connection>, ScanError> {
+        connections_windows()
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub struct ProcfsBackend {
+    pub root: std::path::PathBuf,
+}
+
+#[cfg(target_os = "linux")]
+impl ScanBackend for ProcfsBackend {
+    fn name(&self) -> &'static str {
+        "procfs"
+    }
+
+    fn scan(&self) -> Result<Vec<Listener>, ScanError> {
+        crate::port::procfs::scan(&self.root)
+    }
+
+    fn connections(&self) -> Result<Vec<Connection>, ScanError> {
+        crate::port::procfs::connections(&self.root)
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub struct NetlinkBackend {
+    pub root: std::path::PathBuf,
+}
+
+#[cfg(target_os = "linux")]
+impl ScanBackend for NetlinkBackend {
+    fn name(&self) -> &'static str {
+        "netlink"
+    }
+
+    fn scan(&self) -> Result<Vec<Listener>, ScanError> {
+        crate::port::netlink::scan(&self.root)
+    }
+
+    fn connections(&self) -> Result<Vec<Connection>, ScanError> {
+        crate::port::netlink::connections()
+    }
+}
+
+/// Backends usable on this platform, in the order `auto` tries them.
+pub fn available_backends() -> Vec<Box<dyn ScanBackend>> {
+    if cfg!(target_os = "windows") {
+        return vec![Box::new(NetstatBackend)];
+    }
+
+    #[allow(unused_mut)]
+    let mut out: Vec<Box<dyn ScanBackend>> = vec![];
+    // Netlink and procfs need no subprocess; lsof is only a fallback for
+    // sandboxes where netlink is blocked and /proc is hidden or restricted.
+    #[cfg(target_os = "linux")]
+    {
+        out.push(Box::new(NetlinkBackend {
+            root: "/proc".into(),
+        }));
+        out.push(Box::new(ProcfsBackend {
+            root: "/proc".into(),
+        }));
+    }
+    out.push(Box::new(LsofBackend));
+    out
+}
+
+/// One socket as reported by a backend. Several rows can share a (protocol, port)
+/// when a port is bound on multiple addresses or by several processes.
+#[derive(Debug, Clone)]
+pub struct Listener {
+    pub protocol: String,
+    pub port: u16,
+    pub address: String,
+    pub pid: u32,
+    pub process_name: String,
+    pub user: String,
+    pub fd: String,
+}
+
+/// Scans with the named backend, or with the first one that works for `auto`.
+pub fn scan_with_backend(name: &str) -> Result<Vec<PortInfo>, ScanError> {
+    let backends = available_backends();
+    let (backend, rows) = run_backend(&backends, name, |b| b.scan())?;
+
+    let mut ports = group_listeners(rows);
+    // Counts are a nice-to-have; a failure here shouldn't hide the listeners.
+    if let Ok(conns) = backend.connections() {
+        attach_connection_counts(&mut ports, &conns);
+    }
+    attach_process_details(&mut ports);
+    Ok(ports)
+}
+
+/// Peers of a listening port: every non-listening TCP socket whose local end
+/// is `port`.
+pub fn list_connections(name: &str, port: u16) -> Result<Vec<Connection>, ScanError> {
+    let backends = available_backends();
+    let (_, conns) = run_backend(&backends, name, |b| b.connections())?;
+    let mut out: Vec<Connection> = conns.into_iter().filter(|c| c.local_port == port).collect();
+    out.sort_by(|a, b| (&a.state, &a.remote_ip, a.remote_port).cmp(&(&b.state, &b.remote_ip, b.remote_port)));
+    Ok(out)
+}
+
+/// Runs `f` on the named backend, or on each backend in order for `auto`
+/// until one succeeds. Returns the backend that produced the result.
+fn run_backend<'a, T>(
+    backends: &'a [Box<dyn ScanBackend>],
+    name: &str,
+    f: impl Fn(&dyn ScanBackend) -> Result<T, ScanError>,
+) -> Result<(&'a dyn ScanBackend, T), ScanError> {
+    if name != AUTO_BACKEND {
+        let b = backends
+            .iter()
+            .find(|b| b.name() == name)
+            .ok_or_else(|| ScanError::UnknownBackend(name.to_string()))?;
+        return f(b.as_ref()).map(|v| (b.as_ref(), v));
+    }
+
+    let mut last_err = ScanError::UnknownBackend(name.to_string());
+    for b in backends {
+        match f(b.as_ref()) {
+            Ok(v) => return Ok((b.as_ref(), v)),
+            Err(e) => last_err = e,
+        }
+    }
+    Err(last_err)
+}
+
+pub fn is_known_backend(name: &str) -> bool {
+    name == AUTO_BACKEND || available_backends().iter().any(|b| b.name() == name)
+}
+
+/// Fills `details` for each port's primary owner and upgrades `command` from the
+/// short process name to the full command line when we can read it. The owner's
+/// cwd is then used to detect which project the listener belongs to.
+fn attach_process_details(ports: &mut [PortInfo]) {
+    let pids: Vec<u32> = ports.iter().map(|p| p.pid).collect();
+    let details = details_for(&pids);
+    let mut projects = HashMap::new();
+    for p in ports.iter_mut() {
+        let Some(d) = details.get(&p.pid) else {
+            continue;
+        };
+        if d.command_line.is_some() {
+            p.command = d.command_line.clone();
+        }
+        if p.process_name == "unknown" {
+            p.process_name = d.name.clone();
+        }
+        if let Some(cwd) = &d.cwd {
+            p.project = projects
+                .entry(cwd.clone())
+                .or_insert_with(|| detect_project(Path::new(cwd)))
+                .clone();
+        }
+        p.details = Some(d.clone());
+    }
 }
 
-fn scan_unix() -> Result<Vec<PortInfo>, ScanError> {
-    // In packaged GUI apps, PATH can be minimal/unexpected. Prefer absolute paths.
-    let lsof_cmd = if cfg!(target_os = "macos") {
+// In packaged GUI apps, PATH can be minimal/unexpected. Prefer absolute paths.
+pub(crate) fn lsof_command() -> &'static str {
+    if cfg!(target_os = "macos") {
         if Path::new("/usr/sbin/lsof").exists() {
             "/usr/sbin/lsof"
         } else if Path::new("/usr/bin/lsof").exists() {
@@ -36,10 +236,13 @@ fn scan_unix() -> Result<Vec<PortInfo>, ScanError> {
         "/usr/sbin/lsof"
     } else {
         "lsof"
-    };
+    }
+}
 
-    let out = Command::new(lsof_cmd)
-        .args(["-iTCP", "-sTCP:LISTEN", "-P", "-n", "+c", "0"])
+fn scan_unix() -> Result<Vec<Listener>, ScanError> {
+    let out = Command::new(lsof_command())
+        // -sTCP:LISTEN only narrows the TCP selection; -iUDP still lists every UDP socket.
+        .args(["-iTCP", "-sTCP:LISTEN", "-iUDP", "-P", "-n", "+c", "0"])
         .output()
         .map_err(|e| ScanError::CommandFailed(e.to_string()))?;
 
@@ -50,7 +253,7 @@ fn scan_unix() -> Result<Vec<PortInfo>, ScanError> {
     }
 
     let s = String::from_utf8_lossy(&out.stdout);
-    let mut by_port: HashMap<u16, PortInfo> = HashMap::new();
+    let mut rows = vec![];
 
     for (idx, line) in s.lines().enumerate() {
         if idx == 0 {
@@ -69,32 +272,78 @@ fn scan_unix() -> Result<Vec<PortInfo>, ScanError> {
         };
         let user = cols[2].to_string();
         let fd = cols[3].to_string();
+        let protocol = cols[7].to_ascii_lowercase();
+        if protocol != "tcp" && protocol != "udp" {
+            continue;
+        }
         let address = cols[8].to_string();
         let name = cols[8];
+        // Connected UDP sockets are clients, not bound listeners.
+        if name.contains("->") {
+            continue;
+        }
         let port = parse_port_from_name(name);
         if let Some(port) = port {
-            by_port.entry(port).or_insert_with(|| PortInfo {
+            rows.push(Listener {
+                protocol,
                 port,
+                address,
                 pid,
-                protocol: "tcp".to_string(),
-                process_name: process_name.clone(),
-                address: address.clone(),
-                user: user.clone(),
-                fd: fd.clone(),
-                command: Some(process_name.clone()),
-                is_active: true,
+                process_name,
+                user,
+                fd,
             });
         }
     }
 
-    let mut out: Vec<PortInfo> = by_port.into_values().collect();
-    out.sort_by_key(|p| p.port);
-    Ok(out)
+    Ok(rows)
 }
 
-fn scan_windows() -> Result<Vec<PortInfo>, ScanError> {
+fn connections_unix() -> Result<Vec<Connection>, ScanError> {
+    let out = Command::new(lsof_command())
+        .args(["-iTCP", "-sTCP:^LISTEN", "-P", "-n", "+c", "0"])
+        .output()
+        .map_err(|e| ScanError::CommandFailed(e.to_string()))?;
+
+    // lsof exits 1 when nothing matched, which here just means no connections.
+    if !out.status.success() && !out.stderr.is_empty() {
+        return Err(ScanError::CommandFailed(
+            String::from_utf8_lossy(&out.stderr).to_string(),
+        ));
+    }
+
+    let s = String::from_utf8_lossy(&out.stdout);
+    let mut conns = vec![];
+    for line in s.lines().skip(1) {
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        if cols.len() < 10 {
+            continue;
+        }
+        // NAME is `local->remote`, followed by `(STATE)`.
+        let Some((local, remote)) = cols[8].split_once("->") else {
+            continue;
+        };
+        let (Some((_, local_port)), Some((remote_ip, remote_port))) =
+            (split_host_port(local), split_host_port(remote))
+        else {
+            continue;
+        };
+        let state = cols[9].trim_matches(|c| c == '(' || c == ')');
+        conns.push(Connection {
+            local_address: local.to_string(),
+            local_port,
+            remote_ip,
+            remote_port,
+            state: state.to_ascii_lowercase(),
+        });
+    }
+    Ok(conns)
+}
+
+fn scan_windows() -> Result<Vec<Listener>, ScanError> {
+    // `-p tcp`/`-p udp` only cover IPv4; without it netstat lists all four tables.
     let out = Command::new("netstat")
-        .args(["-ano", "-p", "tcp"])
+        .args(["-ano"])
         .output()
         .map_err(|e| ScanError::CommandFailed(e.to_string()))?;
 
@@ -105,46 +354,149 @@ fn scan_windows() -> Result<Vec<PortInfo>, ScanError> {
     }
 
     let s = String::from_utf8_lossy(&out.stdout);
-    let mut by_port: HashMap<u16, PortInfo> = HashMap::new();
+    let mut rows = vec![];
 
     for line in s.lines() {
         let line = line.trim();
-        if !line.starts_with("TCP") {
-            continue;
-        }
-
         let cols: Vec<&str> = line.split_whitespace().collect();
-        if cols.len() < 5 {
-            continue;
-        }
-        if cols[3] != "LISTENING" {
-            continue;
-        }
+
+        // TCP rows: proto local remote state pid; UDP rows have no state column.
+        let (protocol, pid_col) = match cols.first() {
+            Some(&"TCP") if cols.len() >= 5 && cols[3] == "LISTENING" => ("tcp", cols[4]),
+            Some(&"UDP") if cols.len() >= 4 => ("udp", cols[3]),
+            _ => continue,
+        };
 
         let local = cols[1];
-        let pid: u32 = match cols[4].parse() {
+        let pid: u32 = match pid_col.parse() {
             Ok(v) => v,
             Err(_) => continue,
         };
         let port = parse_port_from_local(local);
         if let Some(port) = port {
-            by_port.entry(port).or_insert_with(|| PortInfo {
+            rows.push(Listener {
+                protocol: protocol.to_string(),
                 port,
+                address: local.to_string(),
                 pid,
-                protocol: "tcp".to_string(),
                 process_name: "unknown".to_string(),
-                address: local.to_string(),
                 user: "-".to_string(),
                 fd: "-".to_string(),
-                command: None,
-                is_active: true,
+            });
+        }
+    }
+
+    Ok(rows)
+}
+
+fn connections_windows() -> Result<Vec<Connection>, ScanError> {
+    let out = Command::new("netstat")
+        .args(["-ano", "-p", "tcp"])
+        .output()
+        .map_err(|e| ScanError::CommandFailed(e.to_string()))?;
+    let out6 = Command::new("netstat")
+        .args(["-ano", "-p", "tcpv6"])
+        .output()
+        .map_err(|e| ScanError::CommandFailed(e.to_string()))?;
+
+    let mut conns = vec![];
+    for stdout in [&out.stdout, &out6.stdout] {
+        for line in String::from_utf8_lossy(stdout).lines() {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() < 5 || cols[0] != "TCP" || cols[3] == "LISTENING" {
+                continue;
+            }
+            let (Some((_, local_port)), Some((remote_ip, remote_port))) =
+                (split_host_port(cols[1]), split_host_port(cols[2]))
+            else {
+                continue;
+            };
+            conns.push(Connection {
+                local_address: cols[1].to_string(),
+                local_port,
+                remote_ip,
+                remote_port,
+                state: cols[3].to_ascii_lowercase(),
+            });
+        }
+    }
+    Ok(conns)
+}
+
+/// Folds backend rows into one `PortInfo` per (protocol, port), keeping every
+/// bind address and every owning process. The flat `pid`/`address`/... fields
+/// describe the first row that could be attributed to a process.
+pub fn group_listeners(rows: Vec<Listener>) -> Vec<PortInfo> {
+    let mut by_port: HashMap<(String, u16), PortInfo> = HashMap::new();
+
+    for row in rows {
+        let key = (row.protocol.clone(), row.port);
+        let info = by_port.entry(key).or_insert_with(|| PortInfo {
+            port: row.port,
+            pid: row.pid,
+            protocol: row.protocol.clone(),
+            process_name: row.process_name.clone(),
+            address: row.address.clone(),
+            user: row.user.clone(),
+            fd: row.fd.clone(),
+            command: (row.pid != 0 && row.process_name != "unknown")
+                .then(|| row.process_name.clone()),
+            is_active: true,
+            addresses: vec![],
+            owners: vec![],
+            loopback_only: true,
+            details: None,
+            project: None,
+            connection_count: 0,
+            service: None,
+        });
+
+        if info.pid == 0 && row.pid != 0 {
+            info.pid = row.pid;
+            info.process_name = row.process_name.clone();
+            info.address = row.address.clone();
+            info.user = row.user.clone();
+            info.fd = row.fd.clone();
+            info.command = (row.process_name != "unknown").then(|| row.process_name.clone());
+        }
+
+        if !info.addresses.contains(&row.address) {
+            info.loopback_only &= is_loopback_address(&row.address);
+            info.addresses.push(row.address);
+        }
+
+        if row.pid != 0 && !info.owners.iter().any(|o| o.pid == row.pid) {
+            info.owners.push(PortOwner {
+                pid: row.pid,
+                process_name: row.process_name,
+                user: row.user,
+                fd: row.fd,
             });
         }
     }
 
     let mut out: Vec<PortInfo> = by_port.into_values().collect();
-    out.sort_by_key(|p| p.port);
-    Ok(out)
+    for info in &mut out {
+        info.addresses.sort();
+        info.owners.sort_by_key(|o| o.pid);
+    }
+    out.sort_by(|a, b| (a.port, &a.protocol).cmp(&(b.port, &b.protocol)));
+    out
+}
+
+/// `127.0.0.1:80`, `[::1]:80` and `localhost:80` are loopback; `*:80` is not.
+pub fn is_loopback_address(address: &str) -> bool {
+    let host = match address.rfind(':') {
+        Some(idx) => &address[..idx],
+        None => address,
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    // Strip an IPv6 zone id such as `fe80::1%lo0`.
+    let host = host.split('%').next().unwrap_or(host);
+    if host.eq_ignore_ascii_case("localhost") {
+        return true;
+    }
+    host.parse::<IpAddr>().map(|ip| ip.is_loopback()).unwrap_or(false)
 }
 
 fn parse_port_from_local(local: &str) -> Option<u16> {
@@ -0,0 +1,314 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Condvar, Mutex,
+    },
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::{
+    clock::now_ms,
+    port::{
+        probe::{Prober, ServiceInfo},
+        scanner::{is_known_backend, scan_with_backend, ScanError},
+        PortInfo,
+    },
+};
+
+pub const PORTS_CHANGED_EVENT: &str = "ports://changed";
+pub const DEFAULT_SCAN_INTERVAL_MS: u64 = 2_500;
+pub const MIN_SCAN_INTERVAL_MS: u64 = 500;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PortChange {
+    Opened { info: PortInfo },
+    Closed { info: PortInfo },
+    OwnerChanged { info: PortInfo, previous: Box<PortInfo> },
+    /// Same owner, but liveness, service or connection count changed.
+    Updated { info: PortInfo },
+}
+
+/// Payload of `ports://changed`: only the deltas since the previous scan.
+#[derive(Debug, Clone, Serialize)]
+pub struct PortsChanged {
+    pub scanned_at_ms: u64,
+    pub changes: Vec<PortChange>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PortSnapshot {
+    pub scanned_at_ms: u64,
+    pub ports: Vec<PortInfo>,
+    pub interval_ms: u64,
+}
+
+/// Keeps the last scan so every consumer (UI, tray, automation) reads the same
+/// data and only deltas need to be pushed. The scanning thread itself lives in
+/// `app.rs`, since it needs an `AppHandle` to emit events.
+pub struct PortWatcher {
+    last: Mutex<Option<PortSnapshot>>,
+    interval_ms: AtomicU64,
+    backend: Mutex<String>,
+    prober: Prober,
+    // Orders scans, so their timestamps say which one is newer; see `apply`.
+    scan_lock: Mutex<()>,
+    wake: (Mutex<bool>, Condvar),
+}
+
+impl PortWatcher {
+    pub fn new(interval_ms: u64, backend: &str) -> Self {
+        Self {
+            last: Mutex::new(None),
+            interval_ms: AtomicU64::new(interval_ms.max(MIN_SCAN_INTERVAL_MS)),
+            backend: Mutex::new(backend.to_string()),
+            prober: Prober::new(),
+            scan_lock: Mutex::new(()),
+            wake: (Mutex::new(false), Condvar::new()),
+        }
+    }
+
+    pub fn interval_ms(&self) -> u64 {
+        self.interval_ms.load(Ordering::SeqCst)
+    }
+
+    /// Changes the scan interval and wakes the watcher so it applies immediately.
+    pub fn set_interval_ms(&self, interval_ms: u64) -> u64 {
+        let interval_ms = interval_ms.max(MIN_SCAN_INTERVAL_MS);
+        self.interval_ms.store(interval_ms, Ordering::SeqCst);
+        self.wake();
+        interval_ms
+    }
+
+    pub fn backend(&self) -> String {
+        self.backend
+            .lock()
+            .map(|b| b.clone())
+            .unwrap_or_else(|e| e.into_inner().clone())
+    }
+
+    /// Switches the scan backend (`auto`, `netlink`, `procfs`, `lsof`, `netstat`).
+    pub fn set_backend(&self, name: &str) -> Result<(), ScanError> {
+        if !is_known_backend(name) {
+            return Err(ScanError::UnknownBackend(name.to_string()));
+        }
+        *self.backend.lock().unwrap_or_else(|e| e.into_inner()) = name.to_string();
+        self.wake();
+        Ok(())
+    }
+
+    pub fn snapshot(&self) -> Option<PortSnapshot> {
+        let mut snap = self.last.lock().ok()?.clone()?;
+        snap.interval_ms = self.interval_ms();
+        Some(snap)
+    }
+
+    /// Scans now, replaces the stored snapshot and returns the deltas.
+    pub fn rescan(&self) -> Result<(PortSnapshot, Vec<PortChange>), ScanError> {
+        let (mut ports, scanned_at_ms) = {
+            let _scan = self.scan_lock.lock().unwrap_or_else(|e| e.into_inner());
+            (scan_with_backend(&self.backend())?, now_ms())
+        };
+        // New listeners can take seconds to probe; a refresh meanwhile
+        // shouldn't wait for that.
+        self.prober.apply(&mut ports);
+        Ok(self.apply(ports, scanned_at_ms))
+    }
+
+    fn apply(&self, ports: Vec<PortInfo>, scanned_at_ms: u64) -> (PortSnapshot, Vec<PortChange>) {
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        // A scan that started later already finished probing; it is newer.
+        if let Some(newer) = last.as_ref().filter(|s| s.scanned_at_ms > scanned_at_ms) {
+            return (newer.clone(), vec![]);
+        }
+        let snap = PortSnapshot {
+            scanned_at_ms,
+            ports,
+            interval_ms: self.interval_ms(),
+        };
+        let previous = last.as_ref().map(|s| s.ports.as_slice()).unwrap_or(&[]);
+        let changes = diff_ports(previous, &snap.ports);
+        *last = Some(snap.clone());
+        (snap, changes)
+    }
+
+    /// Blocks until the interval elapses or `wake` is called.
+    pub fn wait(&self) {
+        let (lock, cvar) = &self.wake;
+        let mut woken = lock.lock().unwrap_or_else(|e| e.into_inner());
+        if !*woken {
+            let timeout = Duration::from_millis(self.interval_ms());
+            woken = cvar
+                .wait_timeout(woken, timeout)
+                .map(|(g, _)| g)
+                .unwrap_or_else(|e| e.into_inner().0);
+        }
+        *woken = false;
+    }
+
+    pub fn wake(&self) {
+        let (lock, cvar) = &self.wake;
+        if let Ok(mut woken) = lock.lock() {
+            *woken = true;
+            cvar.notify_all();
+        }
+    }
+}
+
+fn key(p: &PortInfo) -> (&str, u16) {
+    (p.protocol.as_str(), p.port)
+}
+
+fn owner_pids(p: &PortInfo) -> HashSet<u32> {
+    let mut pids: HashSet<u32> = p.owners.iter().map(|o| o.pid).collect();
+    pids.insert(p.pid);
+    pids
+}
+
+fn observed(p: &PortInfo) -> (bool, Option<&ServiceInfo>, u32) {
+    (p.is_active, p.service.as_ref(), p.connection_count)
+}
+
+/// Compares two scans keyed by (protocol, port). Output is ordered like the scans.
+pub fn diff_ports(old: &[PortInfo], new: &[PortInfo]) -> Vec<PortChange> {
+    let old_by_key: HashMap<(&str, u16), &PortInfo> = old.iter().map(|p| (key(p), p)).collect();
+    let new_keys: HashSet<(&str, u16)> = new.iter().map(key).collect();
+
+    let mut changes = vec![];
+    for p in new {
+        match old_by_key.get(&key(p)) {
+            None => changes.push(PortChange::Opened { info: p.clone() }),
+            Some(prev) if owner_pids(prev) != owner_pids(p) => {
+                changes.push(PortChange::OwnerChanged {
+                    info: p.clone(),
+                    previous: Box::new((*prev).clone()),
+                })
+            }
+            Some(prev) if observed(prev) != observed(p) => {
+                changes.push(PortChange::Updated { info: p.clone() })
+            }
+            Some(_) => {}
+        }
+    }
+    for p in old {
+        if !new_keys.contains(&key(p)) {
+            changes.push(PortChange::Closed { info: p.clone() });
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::port::PortOwner;
+
+    fn port(protocol: &str, port: u16, pid: u32) -> PortInfo {
+        PortInfo {
+            port,
+            pid,
+            protocol: protocol.to_string(),
+            process_name: "node".to_string(),
+            address: format!("*:{port}"),
+            user: "dev".to_string(),
+            fd: "3".to_string(),
+            command: None,
+            is_active: true,
+            addresses: vec![format!("*:{port}")],
+            owners: vec![PortOwner {
+                pid,
+                process_name: "node".to_string(),
+                user: "dev".to_string(),
+                fd: "3".to_string(),
+            }],
+            loopback_only: false,
+            details: None,
+            project: None,
+            connection_count: 0,
+            service: None,
+        }
+    }
+
+    fn kinds(changes: &[PortChange]) -> Vec<(&'static str, &str, u16)> {
+        changes
+            .iter()
+            .map(|c| match c {
+                PortChange::Opened { info } => ("opened", info.protocol.as_str(), info.port),
+                PortChange::Closed { info } => ("closed", info.protocol.as_str(), info.port),
+                PortChange::OwnerChanged { info, .. } => ("owner_changed", info.protocol.as_str(), info.port),
+                PortChange::Updated { info } => ("updated", info.protocol.as_str(), info.port),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reports_opened_closed_and_owner_changes() {
+        let old = vec![port("tcp", 3000, 10), port("tcp", 5432, 20), port("udp", 5353, 30)];
+        let new = vec![port("tcp", 3000, 11), port("tcp", 5432, 20), port("tcp", 8080, 40)];
+
+        assert_eq!(
+            kinds(&diff_ports(&old, &new)),
+            vec![
+                ("owner_changed", "tcp", 3000),
+                ("opened", "tcp", 8080),
+                ("closed", "udp", 5353),
+            ]
+        );
+    }
+
+    #[test]
+    fn tcp_and_udp_on_same_port_are_distinct() {
+        let old = vec![port("tcp", 53, 1)];
+        let new = vec![port("tcp", 53, 1), port("udp", 53, 1)];
+        assert_eq!(kinds(&diff_ports(&old, &new)), vec![("opened", "udp", 53)]);
+    }
+
+    #[test]
+    fn reports_probe_and_connection_updates() {
+        let old = vec![port("tcp", 3000, 10), port("tcp", 5432, 20)];
+        let mut new = old.clone();
+        new[0].connection_count = 2;
+        new[1].service = Some(ServiceInfo::Postgres);
+        assert_eq!(
+            kinds(&diff_ports(&old, &new)),
+            vec![("updated", "tcp", 3000), ("updated", "tcp", 5432)]
+        );
+    }
+
+    #[test]
+    fn first_scan_opens_everything() {
+        let watcher = PortWatcher::new(DEFAULT_SCAN_INTERVAL_MS, "auto");
+        let (_, changes) = watcher.apply(vec![port("tcp", 3000, 10)], 1);
+        assert_eq!(kinds(&changes), vec![("opened", "tcp", 3000)]);
+        let (_, changes) = watcher.apply(vec![port("tcp", 3000, 10)], 2);
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn keeps_a_newer_scan_that_finished_first() {
+        let watcher = PortWatcher::new(DEFAULT_SCAN_INTERVAL_MS, "auto");
+        watcher.apply(vec![port("tcp", 3000, 10), port("tcp", 5432, 20)], 5);
+        let (snap, changes) = watcher.apply(vec![port("tcp", 3000, 10)], 4);
+        assert!(changes.is_empty());
+        assert_eq!(snap.scanned_at_ms, 5);
+        assert_eq!(watcher.snapshot().unwrap().ports.len(), 2);
+    }
+
+    #[test]
+    fn clamps_interval() {
+        let watcher = PortWatcher::new(10, "auto");
+        assert_eq!(watcher.interval_ms(), MIN_SCAN_INTERVAL_MS);
+        assert_eq!(watcher.set_interval_ms(5_000), 5_000);
+    }
+
+    #[test]
+    fn rejects_unknown_backend() {
+        let watcher = PortWatcher::new(DEFAULT_SCAN_INTERVAL_MS, "auto");
+        assert!(watcher.set_backend("carrier-pigeon").is_err());
+        assert!(watcher.set_backend("lsof").is_ok());
+        assert_eq!(watcher.backend(), "lsof");
+    }
+}
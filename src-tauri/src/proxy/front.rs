@@ -0,0 +1,94 @@
+use std::{collections::HashMap, io, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    guard::Guard,
+    inspector::{CapturedRequest, Inspector},
+    server::{ProxyConfig, ProxyServer},
+};
+
+/// Per-tunnel extras that need a proxy between the tunnel and the shared
+/// port. Saved in `DesiredTunnel.options`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TunnelOptions {
+    /// Record requests for the inspector.
+    pub inspect: bool,
+    /// Access protection for the shared URL.
+    pub guard: Option<Guard>,
+}
+
+impl TunnelOptions {
+    pub fn needs_proxy(&self) -> bool {
+        self.inspect || self.guard.as_ref().is_some_and(|g| !g.is_empty())
+    }
+}
+
+struct Front {
+    options: TunnelOptions,
+    server: ProxyServer,
+}
+
+/// Proxies in front of tunneled ports, by shared port.
+#[derive(Default)]
+pub struct Fronts {
+    fronts: HashMap<u16, Front>,
+}
+
+impl Fronts {
+    /// Puts a proxy in front of `port` for `options` and returns its port, or
+    /// `None` when the options need none. A proxy already running with the
+    /// same options is kept, along with the requests it recorded.
+    pub fn apply(
+        &mut self,
+        port: u16,
+        options: &TunnelOptions,
+        notify: impl Fn(&CapturedRequest) + Send + Sync + 'static,
+    ) -> io::Result<Option<u16>> {
+        if !options.needs_proxy() {
+            self.fronts.remove(&port);
+            return Ok(None);
+        }
+        if let Some(f) = self.fronts.get(&port).filter(|f| &f.options == options) {
+            return Ok(Some(f.server.port()));
+        }
+        let config = ProxyConfig {
+            inspector: options
+                .inspect
+                .then(|| Arc::new(Inspector::new(port, notify))),
+            guard: options.guard.clone().filter(|g| !g.is_empty()).map(Arc::new),
+            ..ProxyConfig::single(port)
+        };
+        let server = ProxyServer::bind(0, config)?;
+        let proxy = server.port();
+        self.fronts.insert(
+            port,
+            Front {
+                options: options.clone(),
+                server,
+            },
+        );
+        Ok(Some(proxy))
+    }
+
+    pub fn remove(&mut self, port: u16) {
+        self.fronts.remove(&port);
+    }
+
+    pub fn inspector(&self, port: u16) -> Option<Arc<Inspector>> {
+        self.fronts.get(&port)?.server.inspector()
+    }
+
+    pub fn find_request(&self, id: u64) -> Option<CapturedRequest> {
+        self.inspector_of(id)?.get(id)
+    }
+
+    /// The inspector holding request `id`.
+    pub fn inspector_of(&self, id: u64) -> Option<Arc<Inspector>> {
+        self.fronts
+            .values()
+            .filter_map(|f| f.server.inspector())
+            .find(|i| i.get(id).is_some())
+    }
+}
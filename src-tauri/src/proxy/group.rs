@@ -0,0 +1,56 @@
+use std::{collections::HashMap, io};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    router::Route,
+    server::{ProxyConfig, ProxyServer},
+};
+
+/// Several local ports published behind one tunnel, e.g. a frontend on `/`
+/// and its API on `/api`, so they share an origin. Saved in the store.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShareGroup {
+    pub name: String,
+    pub routes: Vec<Route>,
+    /// Loopback port of the group's proxy, which is what gets tunneled. Kept
+    /// across restarts so the tunnel (and a named-tunnel hostname) stays put.
+    #[serde(default)]
+    pub listen_port: u16,
+}
+
+/// Running proxies by group name.
+#[derive(Default)]
+pub struct ShareGroups {
+    servers: HashMap<String, ProxyServer>,
+}
+
+impl ShareGroups {
+    /// Starts the group's proxy, or swaps the routes of the running one.
+    /// Fills in `listen_port` when the group is new.
+    pub fn apply(&mut self, group: &mut ShareGroup) -> io::Result<()> {
+        if let Some(server) = self.servers.get(&group.name) {
+            server.set_routes(group.routes.clone());
+            group.listen_port = server.port();
+            return Ok(());
+        }
+        let config = ProxyConfig {
+            routes: group.routes.clone(),
+            inspector: None,
+            guard: None,
+        };
+        let server = ProxyServer::bind(group.listen_port, config)?;
+        group.listen_port = server.port();
+        self.servers.insert(group.name.clone(), server);
+        Ok(())
+    }
+
+    /// Stops the group's proxy; returns the port it listened on.
+    pub fn remove(&mut self, name: &str) -> Option<u16> {
+        self.servers.remove(name).map(|s| s.port())
+    }
+
+    pub fn is_proxy_port(&self, port: u16) -> bool {
+        self.servers.values().any(|s| s.port() == port)
+    }
+}
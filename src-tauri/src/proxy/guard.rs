@@ -0,0 +1,311 @@
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+
+use super::http::RequestHead;
+
+/// Query parameter carrying the guard token, for callers (webhooks) that
+/// cannot set headers. Stripped before the request is forwarded.
+const TOKEN_PARAM: &str = "access_token";
+/// Set by Cloudflare's edge to the visitor's address.
+const CLIENT_IP_HEADER: &str = "cf-connecting-ip";
+
+/// Who may use a shared URL. Any configured credential lets a request in;
+/// the allowlist, when set, applies on top of that.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Guard {
+    /// `user:password` for HTTP basic auth.
+    pub basic_auth: Option<String>,
+    /// Accepted as `Authorization: Bearer <token>` or `?access_token=<token>`.
+    pub token: Option<String>,
+    /// IPs or CIDRs (`203.0.113.7`, `10.0.0.0/8`, `2001:db8::/32`) matched
+    /// against `CF-Connecting-IP`. Empty allows any address.
+    pub allow_ips: Vec<String>,
+}
+
+/// Why a request was turned away.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    /// No or wrong credentials; `basic` asks the browser for a password.
+    Unauthorized { basic: bool },
+    /// The client address is missing or not on the allowlist.
+    Forbidden(Option<IpAddr>),
+}
+
+impl Guard {
+    pub fn is_empty(&self) -> bool {
+        self.basic_auth.is_none() && self.token.is_none() && self.allow_ips.is_empty()
+    }
+
+    /// Rejects malformed settings before anything is shared.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(b) = &self.basic_auth {
+            if !b.contains(':') || b.starts_with(':') {
+                return Err("basic auth must be `user:password`".to_string());
+            }
+        }
+        if self.token.as_deref().is_some_and(|t| t.trim().is_empty()) {
+            return Err("token must not be empty".to_string());
+        }
+        for entry in &self.allow_ips {
+            parse_cidr(entry).ok_or_else(|| format!("invalid ip or cidr: {entry}"))?;
+        }
+        Ok(())
+    }
+
+    /// Checks `req` and strips the credentials it used, so the guard's
+    /// secrets never reach the local service.
+    pub fn check(&self, req: &mut RequestHead) -> Result<(), Rejection> {
+        if !self.allow_ips.is_empty() {
+            let ip = req
+                .header(CLIENT_IP_HEADER)
+                .and_then(|v| v.trim().parse::<IpAddr>().ok());
+            let allowed = ip.is_some_and(|ip| {
+                self.allow_ips
+                    .iter()
+                    .filter_map(|e| parse_cidr(e))
+                    .any(|(net, bits)| in_network(ip, net, bits))
+            });
+            if !allowed {
+                return Err(Rejection::Forbidden(ip));
+            }
+        }
+        if self.basic_auth.is_none() && self.token.is_none() {
+            return Ok(());
+        }
+
+        let auth = req.header("authorization").map(str::to_string);
+        let scheme = |s: &str| {
+            auth.as_deref()
+                .and_then(|a| a.split_once(' '))
+                .filter(|(sc, _)| sc.eq_ignore_ascii_case(s))
+                .map(|(_, v)| v.trim())
+        };
+        let basic_ok = self
+            .basic_auth
+            .as_deref()
+            .zip(scheme("basic"))
+            .is_some_and(|(want, got)| secret_eq(&base64(want.as_bytes()), got));
+        let bearer_ok = self
+            .token
+            .as_deref()
+            .zip(scheme("bearer"))
+            .is_some_and(|(want, got)| secret_eq(want, got));
+        if basic_ok || bearer_ok {
+            req.remove_header("Authorization");
+            return Ok(());
+        }
+        if let Some(want) = &self.token {
+            if take_query_param(&mut req.target, TOKEN_PARAM)
+                .and_then(|got| percent_decode(&got))
+                .is_some_and(|got| secret_eq(want, &got))
+            {
+                return Ok(());
+            }
+        }
+        Err(Rejection::Unauthorized {
+            basic: self.basic_auth.is_some(),
+        })
+    }
+}
+
+/// Removes `name=value` from the query of `target` and returns the value.
+fn take_query_param(target: &mut String, name: &str) -> Option<String> {
+    let (path, query) = target.split_once('?')?;
+    let mut value = None;
+    let rest: Vec<&str> = query
+        .split('&')
+        .filter(|pair| match pair.split_once('=') {
+            Some((k, v)) if k == name && value.is_none() => {
+                value = Some(v.to_string());
+                false
+            }
+            _ => true,
+        })
+        .collect();
+    let value = value?;
+    *target = if rest.is_empty() {
+        path.to_string()
+    } else {
+        format!("{path}?{}", rest.join("&"))
+    };
+    Some(value)
+}
+
+/// Undoes `%XX` escapes. `+` stays as is: clients escape a `+` in the token,
+/// and one pasted raw is meant literally. `None` for invalid escapes or UTF-8.
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Compares without returning early, so timing does not leak the secret.
+fn secret_eq(want: &str, got: &str) -> bool {
+    let (a, b) = (want.as_bytes(), got.as_bytes());
+    let mut diff = a.len() ^ b.len();
+    for (i, x) in a.iter().enumerate() {
+        diff |= (x ^ b.get(i).copied().unwrap_or(0)) as usize;
+    }
+    diff == 0
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | ((*b as u32) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[((n >> (18 - 6 * i)) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// `addr` or `addr/bits`, as the network address and prefix length.
+fn parse_cidr(entry: &str) -> Option<(IpAddr, u32)> {
+    let entry = entry.trim();
+    let (addr, bits) = match entry.split_once('/') {
+        Some((a, b)) => (a.parse::<IpAddr>().ok()?, Some(b.parse::<u32>().ok()?)),
+        None => (entry.parse::<IpAddr>().ok()?, None),
+    };
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let bits = bits.unwrap_or(max);
+    (bits <= max).then_some((addr, bits))
+}
+
+fn in_network(ip: IpAddr, net: IpAddr, bits: u32) -> bool {
+    // Compare IPv4-mapped IPv6 clients against IPv4 rules.
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        v4 => v4,
+    };
+    let (ip, net, width) = match (ip, net) {
+        (IpAddr::V4(a), IpAddr::V4(b)) => (u32::from(a) as u128, u32::from(b) as u128, 32),
+        (IpAddr::V6(a), IpAddr::V6(b)) => (u128::from(a), u128::from(b), 128),
+        _ => return false,
+    };
+    if bits == 0 {
+        return true;
+    }
+    let shift = width - bits;
+    ip >> shift == net >> shift
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(target: &str, headers: &[(&str, &str)]) -> RequestHead {
+        RequestHead {
+            method: "GET".to_string(),
+            target: target.to_string(),
+            version: "HTTP/1.1".to_string(),
+            headers: headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn accepts_basic_bearer_or_query_token_and_strips_them() {
+        let guard = Guard {
+            basic_auth: Some("dev:secret".to_string()),
+            token: Some("t0k".to_string()),
+            allow_ips: vec![],
+        };
+        assert_eq!(base64(b"dev:secret"), "ZGV2OnNlY3JldA==");
+
+        let mut basic = request("/", &[("Authorization", "Basic ZGV2OnNlY3JldA==")]);
+        assert_eq!(guard.check(&mut basic), Ok(()));
+        assert_eq!(basic.header("authorization"), None);
+
+        let mut bearer = request("/", &[("authorization", "bearer t0k")]);
+        assert_eq!(guard.check(&mut bearer), Ok(()));
+
+        let mut query = request("/hook?a=1&access_token=t0k&b=2", &[]);
+        assert_eq!(guard.check(&mut query), Ok(()));
+        assert_eq!(query.target, "/hook?a=1&b=2");
+
+        let mut wrong = request(
+            "/?access_token=nope",
+            &[("Authorization", "Basic ZGV2Om5vcGU=")],
+        );
+        assert_eq!(
+            guard.check(&mut wrong),
+            Err(Rejection::Unauthorized { basic: true })
+        );
+    }
+
+    #[test]
+    fn decodes_the_query_token() {
+        let guard = Guard {
+            token: Some("a+b/c=d%e".to_string()),
+            ..Guard::default()
+        };
+        let mut escaped = request("/?access_token=a%2Bb%2Fc%3Dd%25e", &[]);
+        assert_eq!(guard.check(&mut escaped), Ok(()));
+        assert_eq!(escaped.target, "/");
+
+        let mut raw = request("/?access_token=a+b/c=d%25e", &[]);
+        assert_eq!(guard.check(&mut raw), Ok(()));
+
+        let mut broken = request("/?access_token=a%2", &[]);
+        assert_eq!(
+            guard.check(&mut broken),
+            Err(Rejection::Unauthorized { basic: false })
+        );
+    }
+
+    #[test]
+    fn allowlist_matches_cf_connecting_ip() {
+        let guard = Guard {
+            allow_ips: vec![
+                "203.0.113.7".to_string(),
+                "10.0.0.0/8".to_string(),
+                "2001:db8::/32".to_string(),
+            ],
+            ..Guard::default()
+        };
+        assert_eq!(guard.validate(), Ok(()));
+        for ip in ["203.0.113.7", "10.1.2.3", "2001:db8::1", "::ffff:10.0.0.1"] {
+            let mut req = request("/", &[("CF-Connecting-IP", ip)]);
+            assert_eq!(guard.check(&mut req), Ok(()), "{ip}");
+        }
+        let mut outside = request("/", &[("CF-Connecting-IP", "203.0.113.8")]);
+        assert_eq!(
+            guard.check(&mut outside),
+            Err(Rejection::Forbidden(Some("203.0.113.8".parse().unwrap())))
+        );
+        assert_eq!(
+            guard.check(&mut request("/", &[])),
+            Err(Rejection::Forbidden(None))
+        );
+
+        let bad = Guard {
+            allow_ips: vec!["10.0.0.0/33".to_string()],
+            ..Guard::default()
+        };
+        assert!(bad.validate().is_err());
+    }
+}
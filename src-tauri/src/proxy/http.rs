@@ -0,0 +1,360 @@
+use std::io::{self, BufRead, Read, Write};
+
+const MAX_LINE: usize = 16 * 1024;
+const MAX_HEADERS: usize = 128;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestHead {
+    pub method: String,
+    /// Path and query as sent, e.g. `/api/users?page=2`.
+    pub target: String,
+    pub version: String,
+    pub headers: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseHead {
+    pub version: String,
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+}
+
+/// How the body following a head is delimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Body {
+    Empty,
+    Length(u64),
+    Chunked,
+    /// HTTP/1.0-style: everything until the connection closes.
+    UntilClose,
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+fn set_header(headers: &mut Vec<(String, String)>, name: &str, value: &str) {
+    remove_header(headers, name);
+    headers.push((name.to_string(), value.to_string()));
+}
+
+fn remove_header(headers: &mut Vec<(String, String)>, name: &str) {
+    headers.retain(|(k, _)| !k.eq_ignore_ascii_case(name));
+}
+
+fn has_token(headers: &[(String, String)], name: &str, token: &str) -> bool {
+    headers
+        .iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case(name))
+        .flat_map(|(_, v)| v.split(','))
+        .any(|t| t.trim().eq_ignore_ascii_case(token))
+}
+
+fn write_headers<W: Write>(w: &mut W, headers: &[(String, String)]) -> io::Result<()> {
+    for (k, v) in headers {
+        write!(w, "{k}: {v}\r\n")?;
+    }
+    w.write_all(b"\r\n")
+}
+
+impl RequestHead {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header(&self.headers, name)
+    }
+
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        set_header(&mut self.headers, name, value)
+    }
+
+    pub fn remove_header(&mut self, name: &str) {
+        remove_header(&mut self.headers, name)
+    }
+
+    pub fn path(&self) -> &str {
+        self.target.split(['?', '#']).next().unwrap_or("/")
+    }
+
+    pub fn body(&self) -> Body {
+        if has_token(&self.headers, "transfer-encoding", "chunked") {
+            return Body::Chunked;
+        }
+        match self.header("content-length").and_then(|v| v.trim().parse().ok()) {
+            Some(0) | None => Body::Empty,
+            Some(n) => Body::Length(n),
+        }
+    }
+
+    /// Whether the client wants the connection closed after this exchange.
+    pub fn wants_close(&self) -> bool {
+        if self.version == "HTTP/1.0" {
+            return !has_token(&self.headers, "connection", "keep-alive");
+        }
+        has_token(&self.headers, "connection", "close")
+    }
+
+    pub fn is_upgrade(&self) -> bool {
+        self.header("upgrade").is_some() && has_token(&self.headers, "connection", "upgrade")
+    }
+
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write!(w, "{} {} {}\r\n", self.method, self.target, self.version)?;
+        write_headers(w, &self.headers)
+    }
+}
+
+impl ResponseHead {
+    pub fn new(status: u16, reason: &str) -> Self {
+        Self {
+            version: "HTTP/1.1".to_string(),
+            status,
+            reason: reason.to_string(),
+            headers: vec![],
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header(&self.headers, name)
+    }
+
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        set_header(&mut self.headers, name, value)
+    }
+
+    pub fn remove_header(&mut self, name: &str) {
+        remove_header(&mut self.headers, name)
+    }
+
+    /// Body framing of this response to a `method` request (RFC 9112 §6.3).
+    pub fn body(&self, method: &str) -> Body {
+        if method.eq_ignore_ascii_case("HEAD")
+            || (100..200).contains(&self.status)
+            || self.status == 204
+            || self.status == 304
+        {
+            return Body::Empty;
+        }
+        if has_token(&self.headers, "transfer-encoding", "chunked") {
+            return Body::Chunked;
+        }
+        match self.header("content-length").and_then(|v| v.trim().parse().ok()) {
+            Some(0) => Body::Empty,
+            Some(n) => Body::Length(n),
+            None => Body::UntilClose,
+        }
+    }
+
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write!(w, "{} {} {}\r\n", self.version, self.status, self.reason)?;
+        write_headers(w, &self.headers)
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Reads one CRLF- (or LF-) terminated line without the terminator. `None`
+/// on EOF before any byte.
+fn read_line<R: BufRead>(r: &mut R) -> io::Result<Option<String>> {
+    let mut buf = Vec::new();
+    let n = r.by_ref().take(MAX_LINE as u64).read_until(b'\n', &mut buf)?;
+    if n == 0 {
+        return Ok(None);
+    }
+    if buf.last() != Some(&b'\n') {
+        return Err(invalid(if n >= MAX_LINE { "line too long" } else { "truncated line" }));
+    }
+    buf.pop();
+    if buf.last() == Some(&b'\r') {
+        buf.pop();
+    }
+    String::from_utf8(buf).map(Some).map_err(|_| invalid("line is not utf-8"))
+}
+
+fn read_headers<R: BufRead>(r: &mut R) -> io::Result<Vec<(String, String)>> {
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line(r)?.ok_or_else(|| invalid("eof in headers"))?;
+        if line.is_empty() {
+            return Ok(headers);
+        }
+        if headers.len() >= MAX_HEADERS {
+            return Err(invalid("too many headers"));
+        }
+        let (k, v) = line.split_once(':').ok_or_else(|| invalid("malformed header"))?;
+        headers.push((k.trim().to_string(), v.trim().to_string()));
+    }
+}
+
+/// Reads the next request head on a connection. `None` when the client closed
+/// it between requests.
+pub fn read_request_head<R: BufRead>(r: &mut R) -> io::Result<Option<RequestHead>> {
+    // Tolerate stray blank lines between keep-alive requests.
+    let line = loop {
+        match read_line(r)? {
+            None => return Ok(None),
+            Some(l) if l.is_empty() => continue,
+            Some(l) => break l,
+        }
+    };
+    let mut parts = line.split(' ');
+    let (Some(method), Some(target), Some(version), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid("malformed request line"));
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(invalid("unsupported http version"));
+    }
+    Ok(Some(RequestHead {
+        method: method.to_string(),
+        target: target.to_string(),
+        version: version.to_string(),
+        headers: read_headers(r)?,
+    }))
+}
+
+pub fn read_response_head<R: BufRead>(r: &mut R) -> io::Result<ResponseHead> {
+    let line = read_line(r)?.ok_or_else(|| invalid("upstream closed without a response"))?;
+    let mut parts = line.splitn(3, ' ');
+    let version = parts.next().unwrap_or_default();
+    let status = parts
+        .next()
+        .and_then(|s| s.parse().ok())
+        .filter(|_| version.starts_with("HTTP/1."))
+        .ok_or_else(|| invalid("malformed status line"))?;
+    Ok(ResponseHead {
+        version: version.to_string(),
+        status,
+        reason: parts.next().unwrap_or_default().to_string(),
+        headers: read_headers(r)?,
+    })
+}
+
+/// Forwards a body from `r` to `w` byte for byte, stopping exactly at its end
+/// so the next message on the connection is left unread. Every piece of
+/// (de-chunked) content is also handed to `tap`.
+pub fn copy_body<R: BufRead, W: Write>(
+    r: &mut R,
+    w: &mut W,
+    body: Body,
+    tap: &mut dyn FnMut(&[u8]),
+) -> io::Result<()> {
+    match body {
+        Body::Empty => Ok(()),
+        Body::Length(n) => {
+            let mut left = n;
+            while left > 0 {
+                let buf = r.fill_buf()?;
+                if buf.is_empty() {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "body cut short"));
+                }
+                let take = buf.len().min(left.min(usize::MAX as u64) as usize);
+                w.write_all(&buf[..take])?;
+                tap(&buf[..take]);
+                r.consume(take);
+                left -= take as u64;
+            }
+            Ok(())
+        }
+        Body::UntilClose => loop {
+            let buf = r.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(());
+            }
+            let len = buf.len();
+            w.write_all(buf)?;
+            tap(buf);
+            r.consume(len);
+        },
+        Body::Chunked => loop {
+            let line = read_line(r)?.ok_or_else(|| invalid("eof in chunked body"))?;
+            let size_hex = line.split(';').next().unwrap_or_default().trim();
+            let size = u64::from_str_radix(size_hex, 16).map_err(|_| invalid("bad chunk size"))?;
+            write!(w, "{line}\r\n")?;
+            if size == 0 {
+                // Trailers, then the blank line ending the message.
+                loop {
+                    let trailer = read_line(r)?.ok_or_else(|| invalid("eof in trailers"))?;
+                    write!(w, "{trailer}\r\n")?;
+                    if trailer.is_empty() {
+                        return Ok(());
+                    }
+                }
+            }
+            copy_body(r, w, Body::Length(size), tap)?;
+            if read_line(r)?.as_deref() != Some("") {
+                return Err(invalid("missing chunk terminator"));
+            }
+            w.write_all(b"\r\n")?;
+        },
+    }
+}
+
+/// A short plain-text response from the proxy itself (404, 502, ...). `head`
+/// may carry extra headers; framing and content type are filled in.
+pub fn write_simple_response<W: Write>(
+    w: &mut W,
+    mut head: ResponseHead,
+    message: &str,
+) -> io::Result<()> {
+    head.set_header("Content-Type", "text/plain; charset=utf-8");
+    head.set_header("Content-Length", &message.len().to_string());
+    head.set_header("Connection", "close");
+    head.write_to(w)?;
+    w.write_all(message.as_bytes())?;
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use super::*;
+
+    #[test]
+    fn reads_request_and_leaves_next_one_buffered() {
+        let raw = b"POST /api/hook?x=1 HTTP/1.1\r\nHost: a.example.com\r\nContent-Length: 5\r\n\r\nhelloGET / HTTP/1.1\r\nHost: b\r\n\r\n";
+        let mut r = BufReader::new(&raw[..]);
+
+        let req = read_request_head(&mut r).unwrap().unwrap();
+        assert_eq!(req.method, "POST");
+        assert_eq!(req.path(), "/api/hook");
+        assert_eq!(req.header("HOST"), Some("a.example.com"));
+        assert_eq!(req.body(), Body::Length(5));
+        let mut body = Vec::new();
+        copy_body(&mut r, &mut body, req.body(), &mut |_| {}).unwrap();
+        assert_eq!(body, b"hello");
+
+        let next = read_request_head(&mut r).unwrap().unwrap();
+        assert_eq!((next.method.as_str(), next.body()), ("GET", Body::Empty));
+        assert!(read_request_head(&mut r).unwrap().is_none());
+    }
+
+    #[test]
+    fn copies_chunked_body_verbatim() {
+        let raw = b"4\r\nWiki\r\n6;ext=1\r\npedia \r\n0\r\nX-Trailer: t\r\n\r\nrest";
+        let mut r = BufReader::new(&raw[..]);
+        let (mut out, mut content) = (Vec::new(), Vec::new());
+        copy_body(&mut r, &mut out, Body::Chunked, &mut |d| content.extend_from_slice(d)).unwrap();
+        assert_eq!(out, &raw[..raw.len() - 4]);
+        assert_eq!(content, b"Wikipedia ");
+        let mut rest = String::new();
+        r.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "rest");
+    }
+
+    #[test]
+    fn response_framing() {
+        let raw = b"HTTP/1.1 200 OK\r\nServer: x\r\n\r\n";
+        let resp = read_response_head(&mut BufReader::new(&raw[..])).unwrap();
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.body("GET"), Body::UntilClose);
+        assert_eq!(resp.body("HEAD"), Body::Empty);
+        assert_eq!(ResponseHead::new(304, "Not Modified").body("GET"), Body::Empty);
+    }
+}
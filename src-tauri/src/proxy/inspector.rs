@@ -0,0 +1,214 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use serde::Serialize;
+
+use super::http::{RequestHead, ResponseHead};
+use crate::clock::now_ms;
+
+pub const REQUEST_CAPTURED_EVENT: &str = "tunnels://request";
+/// Requests kept per tunnel; the oldest are dropped first.
+pub const MAX_REQUESTS: usize = 200;
+/// Bytes of each body that are kept. The full size is still reported.
+pub const MAX_BODY_BYTES: usize = 256 * 1024;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A body as seen on the wire, capped at `MAX_BODY_BYTES`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CapturedBody {
+    /// Bytes actually transferred, which may exceed what was kept.
+    pub size: u64,
+    pub truncated: bool,
+    /// The kept bytes when they are valid UTF-8; binary bodies are not sent
+    /// to the UI.
+    pub text: Option<String>,
+    #[serde(skip)]
+    pub bytes: Vec<u8>,
+}
+
+impl CapturedBody {
+    pub fn push(&mut self, data: &[u8]) {
+        self.size += data.len() as u64;
+        let room = MAX_BODY_BYTES.saturating_sub(self.bytes.len());
+        self.bytes.extend_from_slice(&data[..data.len().min(room)]);
+        self.truncated |= data.len() > room;
+    }
+
+    fn finish(&mut self) {
+        self.text = String::from_utf8(self.bytes.clone()).ok();
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CapturedRequest {
+    pub id: u64,
+    /// The shared port the request was sent to.
+    pub port: u16,
+    pub started_at_ms: u64,
+    pub duration_ms: u64,
+    pub method: String,
+    pub target: String,
+    pub request_headers: Vec<(String, String)>,
+    pub request_body: CapturedBody,
+    pub status: Option<u16>,
+    pub response_headers: Vec<(String, String)>,
+    pub response_body: CapturedBody,
+    /// Why no (complete) response was relayed, e.g. the upstream was down.
+    pub error: Option<String>,
+    /// The recorded request this one re-sent, for replays.
+    pub replay_of: Option<u64>,
+}
+
+impl CapturedRequest {
+    /// Starts a record from the request as the client sent it.
+    pub fn new(port: u16, req: &RequestHead) -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            port,
+            started_at_ms: now_ms(),
+            duration_ms: 0,
+            method: req.method.clone(),
+            target: req.target.clone(),
+            request_headers: req.headers.clone(),
+            request_body: CapturedBody::default(),
+            status: None,
+            response_headers: vec![],
+            response_body: CapturedBody::default(),
+            error: None,
+            replay_of: None,
+        }
+    }
+
+    pub fn set_response(&mut self, resp: &ResponseHead) {
+        self.status = Some(resp.status);
+        self.response_headers = resp.headers.clone();
+    }
+
+    pub fn summary(&self) -> RequestSummary {
+        RequestSummary {
+            id: self.id,
+            port: self.port,
+            started_at_ms: self.started_at_ms,
+            duration_ms: self.duration_ms,
+            method: self.method.clone(),
+            target: self.target.clone(),
+            status: self.status,
+            request_size: self.request_body.size,
+            response_size: self.response_body.size,
+            error: self.error.clone(),
+            replay_of: self.replay_of,
+        }
+    }
+}
+
+/// What `list_requests` and the live event carry: no headers or bodies.
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestSummary {
+    pub id: u64,
+    pub port: u16,
+    pub started_at_ms: u64,
+    pub duration_ms: u64,
+    pub method: String,
+    pub target: String,
+    pub status: Option<u16>,
+    pub request_size: u64,
+    pub response_size: u64,
+    pub error: Option<String>,
+    pub replay_of: Option<u64>,
+}
+
+type Notify = Box<dyn Fn(&CapturedRequest) + Send + Sync>;
+
+/// Ring buffer of the requests that went through one tunnel's proxy.
+pub struct Inspector {
+    port: u16,
+    requests: Mutex<VecDeque<CapturedRequest>>,
+    notify: Notify,
+}
+
+impl Inspector {
+    /// `notify` runs for every finished request, e.g. to emit a Tauri event.
+    pub fn new(port: u16, notify: impl Fn(&CapturedRequest) + Send + Sync + 'static) -> Self {
+        Self {
+            port,
+            requests: Mutex::new(VecDeque::new()),
+            notify: Box::new(notify),
+        }
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Finishes `req` (timing, body text), stores it and returns it.
+    pub fn record(&self, mut req: CapturedRequest, started: std::time::Instant) -> CapturedRequest {
+        req.duration_ms = started.elapsed().as_millis() as u64;
+        req.request_body.finish();
+        req.response_body.finish();
+        // Stored before notifying, so listeners can fetch it right away.
+        if let Ok(mut buf) = self.requests.lock() {
+            if buf.len() >= MAX_REQUESTS {
+                buf.pop_front();
+            }
+            buf.push_back(req.clone());
+        }
+        (self.notify)(&req);
+        req
+    }
+
+    /// Newest first.
+    pub fn list(&self) -> Vec<RequestSummary> {
+        match self.requests.lock() {
+            Ok(buf) => buf.iter().rev().map(|r| r.summary()).collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    pub fn get(&self, id: u64) -> Option<CapturedRequest> {
+        self.requests.lock().ok()?.iter().find(|r| r.id == id).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    fn request(target: &str) -> RequestHead {
+        RequestHead {
+            method: "POST".to_string(),
+            target: target.to_string(),
+            version: "HTTP/1.1".to_string(),
+            headers: vec![],
+        }
+    }
+
+    #[test]
+    fn caps_bodies_and_keeps_newest_requests() {
+        let inspector = Inspector::new(3000, |_| {});
+        let mut first = CapturedRequest::new(3000, &request("/first"));
+        first.request_body.push(&vec![b'a'; MAX_BODY_BYTES - 1]);
+        first.request_body.push(b"bc");
+        inspector.record(first, Instant::now());
+
+        let kept = inspector.list()[0].id;
+        let body = inspector.get(kept).unwrap().request_body;
+        assert_eq!((body.size, body.bytes.len(), body.truncated), (MAX_BODY_BYTES as u64 + 1, MAX_BODY_BYTES, true));
+        assert!(body.text.unwrap().ends_with("ab"));
+
+        for i in 0..MAX_REQUESTS {
+            inspector.record(CapturedRequest::new(3000, &request(&format!("/{i}"))), Instant::now());
+        }
+        let list = inspector.list();
+        assert_eq!(list.len(), MAX_REQUESTS);
+        assert_eq!(list[0].target, format!("/{}", MAX_REQUESTS - 1));
+        assert!(inspector.get(kept).is_none());
+    }
+}
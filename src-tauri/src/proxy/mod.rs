@@ -0,0 +1,8 @@
+pub mod front;
+pub mod group;
+pub mod guard;
+pub mod http;
+pub mod inspector;
+pub mod replay;
+pub mod router;
+pub mod server;
@@ -0,0 +1,184 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, BufReader, Write},
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::{
+    http::{self, RequestHead},
+    inspector::{CapturedRequest, Inspector},
+    server::connect_upstream,
+};
+
+// A replayed webhook handler may be slow, but the command must not hang.
+const REPLAY_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("request {0} is no longer recorded")]
+    NotFound(u64),
+    #[error("request {0} has a truncated body; pass the body to replay it")]
+    Truncated(u64),
+}
+
+/// Edits applied to a recorded request before it is sent again.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ReplayOverrides {
+    /// Headers to set; `null` removes the header.
+    pub headers: BTreeMap<String, Option<String>>,
+    /// Replaces the recorded body.
+    pub body: Option<String>,
+}
+
+/// Sends recorded request `id` to the inspector's local port again, with
+/// `overrides` applied, and records the exchange as a new request pointing
+/// back at the original. Upstream failures end up in the record's `error`.
+pub fn replay(
+    inspector: &Inspector,
+    id: u64,
+    overrides: &ReplayOverrides,
+) -> Result<CapturedRequest, ReplayError> {
+    let original = inspector.get(id).ok_or(ReplayError::NotFound(id))?;
+    let body = match &overrides.body {
+        Some(b) => b.clone().into_bytes(),
+        None if original.request_body.truncated => return Err(ReplayError::Truncated(id)),
+        None => original.request_body.bytes.clone(),
+    };
+
+    let mut req = RequestHead {
+        method: original.method.clone(),
+        target: original.target.clone(),
+        version: "HTTP/1.1".to_string(),
+        headers: original.request_headers.clone(),
+    };
+    for (name, value) in &overrides.headers {
+        match value {
+            Some(v) => req.set_header(name, v),
+            None => req.remove_header(name),
+        }
+    }
+    // The recorded body is de-chunked; resend it with an exact length.
+    req.remove_header("Transfer-Encoding");
+    req.remove_header("Expect");
+    req.remove_header("Keep-Alive");
+    if body.is_empty() {
+        req.remove_header("Content-Length");
+    } else {
+        req.set_header("Content-Length", &body.len().to_string());
+    }
+    req.set_header("Connection", "close");
+
+    let started = Instant::now();
+    let mut record = CapturedRequest::new(inspector.port(), &req);
+    record.replay_of = Some(id);
+    record.request_body.push(&body);
+    if let Err(e) = send(&req, &body, inspector.port(), &mut record) {
+        record.error.get_or_insert_with(|| e.to_string());
+    }
+    Ok(inspector.record(record, started))
+}
+
+fn send(req: &RequestHead, body: &[u8], port: u16, record: &mut CapturedRequest) -> io::Result<()> {
+    let upstream = connect_upstream(port).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("nothing is listening on localhost:{port}"),
+        )
+    })?;
+    upstream.set_read_timeout(Some(REPLAY_TIMEOUT))?;
+    let mut writer = upstream.try_clone()?;
+    req.write_to(&mut writer)?;
+    writer.write_all(body)?;
+    writer.flush()?;
+
+    let mut reader = BufReader::new(upstream);
+    let resp = loop {
+        let resp = http::read_response_head(&mut reader)?;
+        if !(100..200).contains(&resp.status) {
+            break resp;
+        }
+    };
+    record.set_response(&resp);
+    http::copy_body(
+        &mut reader,
+        &mut io::sink(),
+        resp.body(&req.method),
+        &mut |d| record.response_body.push(d),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Read, net::TcpListener, thread};
+
+    use super::*;
+
+    /// Answers one request with its `X-Sig` header and body, then closes.
+    fn echo_once() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (conn, _) = listener.accept().unwrap();
+            let mut r = BufReader::new(conn.try_clone().unwrap());
+            let req = http::read_request_head(&mut r).unwrap().unwrap();
+            let mut body = Vec::new();
+            http::copy_body(&mut r, &mut body, req.body(), &mut |_| {}).unwrap();
+            let text = format!(
+                "{} {}",
+                req.header("x-sig").unwrap_or("-"),
+                String::from_utf8_lossy(&body)
+            );
+            let mut w = conn;
+            write!(
+                w,
+                "HTTP/1.1 201 Created\r\nContent-Length: {}\r\n\r\n{text}",
+                text.len()
+            )
+            .unwrap();
+            let _ = r.read_to_end(&mut Vec::new());
+        });
+        port
+    }
+
+    #[test]
+    fn resends_with_overrides_and_records_the_result() {
+        let port = echo_once();
+        let inspector = Inspector::new(port, |_| {});
+        let mut original = CapturedRequest::new(
+            port,
+            &RequestHead {
+                method: "POST".to_string(),
+                target: "/hook".to_string(),
+                version: "HTTP/1.1".to_string(),
+                headers: vec![
+                    ("Host".to_string(), "x".to_string()),
+                    ("X-Sig".to_string(), "old".to_string()),
+                    ("Transfer-Encoding".to_string(), "chunked".to_string()),
+                ],
+            },
+        );
+        original.request_body.push(b"{\"a\":1}");
+        let id = original.id;
+        inspector.record(original, Instant::now());
+
+        let overrides = ReplayOverrides {
+            headers: BTreeMap::from([("x-sig".to_string(), Some("new".to_string()))]),
+            body: None,
+        };
+        let replayed = replay(&inspector, id, &overrides).unwrap();
+        assert_eq!(replayed.replay_of, Some(id));
+        assert_eq!(replayed.status, Some(201));
+        assert_eq!(
+            replayed.response_body.text.as_deref(),
+            Some("new {\"a\":1}")
+        );
+        assert_eq!(inspector.list().len(), 2);
+
+        let gone = replay(&inspector, u64::MAX, &ReplayOverrides::default());
+        assert!(matches!(gone, Err(ReplayError::NotFound(_))));
+    }
+}
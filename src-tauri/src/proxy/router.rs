@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+/// Sends matching requests to a local port.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Route {
+    /// Path prefix such as `/api`; matches `/api` and `/api/...` but not
+    /// `/apix`. Empty or `/` matches every path.
+    #[serde(default)]
+    pub path: String,
+    /// First label of the `Host` header, e.g. `api` for `api.dev.example.com`.
+    /// Only useful with a wildcard hostname on a named tunnel.
+    #[serde(default)]
+    pub subdomain: Option<String>,
+    pub port: u16,
+}
+
+impl Route {
+    fn prefix(&self) -> &str {
+        self.path.trim_end_matches('/')
+    }
+
+    fn matches_path(&self, path: &str) -> bool {
+        let prefix = self.prefix();
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+
+    fn matches_host(&self, host: Option<&str>) -> bool {
+        let Some(sub) = self.subdomain.as_deref().filter(|s| !s.is_empty()) else {
+            return true;
+        };
+        host.and_then(|h| h.split('.').next())
+            .is_some_and(|label| label.eq_ignore_ascii_case(sub))
+    }
+}
+
+/// Picks the route for a request: subdomain routes beat host-agnostic ones,
+/// then the longest matching path prefix wins.
+pub fn pick(routes: &[Route], host: Option<&str>, path: &str) -> Option<u16> {
+    routes
+        .iter()
+        .filter(|r| r.matches_host(host) && r.matches_path(path))
+        .max_by_key(|r| (r.subdomain.as_deref().is_some_and(|s| !s.is_empty()), r.prefix().len()))
+        .map(|r| r.port)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(path: &str, subdomain: Option<&str>, port: u16) -> Route {
+        Route {
+            path: path.to_string(),
+            subdomain: subdomain.map(|s| s.to_string()),
+            port,
+        }
+    }
+
+    #[test]
+    fn longest_prefix_on_segment_boundary() {
+        let routes = [route("/", None, 5173), route("/api/", None, 8080)];
+        assert_eq!(pick(&routes, None, "/api"), Some(8080));
+        assert_eq!(pick(&routes, None, "/api/users"), Some(8080));
+        assert_eq!(pick(&routes, None, "/apix"), Some(5173));
+        assert_eq!(pick(&routes, None, "/"), Some(5173));
+        assert_eq!(pick(&routes[1..], None, "/assets/app.js"), None);
+    }
+
+    #[test]
+    fn subdomain_routes_win() {
+        let routes = [
+            route("", None, 5173),
+            route("", Some("api"), 8080),
+            route("/admin", Some("api"), 9000),
+        ];
+        assert_eq!(pick(&routes, Some("api.dev.example.com"), "/users"), Some(8080));
+        assert_eq!(pick(&routes, Some("API.dev.example.com:443"), "/admin/x"), Some(9000));
+        assert_eq!(pick(&routes, Some("app.dev.example.com"), "/admin"), Some(5173));
+        assert_eq!(pick(&routes, None, "/users"), Some(5173));
+    }
+}
@@ -0,0 +1,450 @@
+use std::{
+    io::{self, BufReader, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{
+    guard::{Guard, Rejection},
+    http::{self, Body, RequestHead, ResponseHead},
+    inspector::{CapturedRequest, Inspector},
+    router::{pick, Route},
+};
+
+// Idle keep-alive connections from the tunnel client are dropped after this.
+const CLIENT_IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// What a proxy does with each request.
+#[derive(Clone, Default)]
+pub struct ProxyConfig {
+    pub routes: Vec<Route>,
+    /// Records every exchange when set.
+    pub inspector: Option<Arc<Inspector>>,
+    /// Turns away requests without the right credentials or address.
+    pub guard: Option<Arc<Guard>>,
+}
+
+impl ProxyConfig {
+    /// Everything to `port`, e.g. for a proxy in front of a single tunnel.
+    pub fn single(port: u16) -> Self {
+        Self {
+            routes: vec![Route {
+                path: String::new(),
+                subdomain: None,
+                port,
+            }],
+            inspector: None,
+            guard: None,
+        }
+    }
+}
+
+/// A reverse proxy on a loopback port, forwarding each request to the local
+/// port its route picks. Stops when dropped.
+pub struct ProxyServer {
+    port: u16,
+    config: Arc<RwLock<ProxyConfig>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl ProxyServer {
+    /// Listens on `127.0.0.1:port`, or any free port when `port` is 0.
+    pub fn bind(port: u16, config: ProxyConfig) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let port = listener.local_addr()?.port();
+        let config = Arc::new(RwLock::new(config));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let (c, s) = (config.clone(), stopped.clone());
+        thread::spawn(move || {
+            for conn in listener.incoming() {
+                if s.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(conn) = conn else { continue };
+                let config = c.clone();
+                thread::spawn(move || {
+                    let _ = serve(conn, &config);
+                });
+            }
+        });
+
+        Ok(Self {
+            port,
+            config,
+            stopped,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn inspector(&self) -> Option<Arc<Inspector>> {
+        self.config.read().ok()?.inspector.clone()
+    }
+
+    /// Swaps the routing table; requests already in flight finish on the old one.
+    pub fn set_routes(&self, routes: Vec<Route>) {
+        if let Ok(mut c) = self.config.write() {
+            c.routes = routes;
+        }
+    }
+}
+
+impl Drop for ProxyServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wake the blocking accept so the thread sees the flag.
+        let _ = TcpStream::connect(("127.0.0.1", self.port));
+    }
+}
+
+pub(super) fn connect_upstream(port: u16) -> io::Result<TcpStream> {
+    let v4 = ([127, 0, 0, 1], port).into();
+    TcpStream::connect_timeout(&v4, CONNECT_TIMEOUT).or_else(|_| {
+        let v6 = (std::net::Ipv6Addr::LOCALHOST, port).into();
+        TcpStream::connect_timeout(&v6, CONNECT_TIMEOUT)
+    })
+}
+
+enum Next {
+    KeepAlive,
+    Close,
+    /// The upstream switched protocols; relay raw bytes from here on.
+    Upgraded(BufReader<TcpStream>, TcpStream),
+}
+
+/// Handles one client connection, one request at a time. Every request gets
+/// a fresh upstream connection (sent with `Connection: close`), which keeps
+/// routing per request and body framing simple.
+fn serve(client: TcpStream, config: &RwLock<ProxyConfig>) -> io::Result<()> {
+    client.set_read_timeout(Some(CLIENT_IDLE_TIMEOUT))?;
+    let mut reader = BufReader::new(client.try_clone()?);
+    let mut writer = client;
+
+    while let Some(mut req) = http::read_request_head(&mut reader)? {
+        let (target, inspector, guard) = match config.read() {
+            Ok(c) => (
+                pick(&c.routes, req.header("host"), req.path()),
+                c.inspector.clone(),
+                c.guard.clone(),
+            ),
+            Err(_) => (None, None, None),
+        };
+        let started = Instant::now();
+        // Checked first: it strips the guard's credentials from what is
+        // recorded and forwarded.
+        let rejection = guard.and_then(|g| g.check(&mut req).err());
+        let mut record = inspector.as_ref().map(|i| CapturedRequest::new(i.port(), &req));
+
+        let next = match rejection {
+            Some(r) => reject(&mut writer, r, &mut record),
+            None => forward(&mut reader, &mut writer, req, target, &mut record),
+        };
+        if let (Some(i), Some(mut r)) = (inspector, record) {
+            if let Err(e) = &next {
+                r.error.get_or_insert_with(|| e.to_string());
+            }
+            i.record(r, started);
+        }
+        match next? {
+            Next::KeepAlive => continue,
+            Next::Close => break,
+            Next::Upgraded(up_reader, up_writer) => {
+                return tunnel(reader, writer, up_reader, up_writer)
+            }
+        }
+    }
+    let _ = writer.shutdown(Shutdown::Both);
+    Ok(())
+}
+
+/// Answers a request the guard turned away. The body is left unread, so the
+/// connection is closed.
+fn reject(
+    writer: &mut TcpStream,
+    rejection: Rejection,
+    record: &mut Option<CapturedRequest>,
+) -> io::Result<Next> {
+    let (mut head, msg) = match rejection {
+        Rejection::Unauthorized { basic } => {
+            let mut head = ResponseHead::new(401, "Unauthorized");
+            if basic {
+                head.set_header("WWW-Authenticate", "Basic realm=\"shared\", charset=\"UTF-8\"");
+            }
+            (head, "authentication required\n".to_string())
+        }
+        Rejection::Forbidden(ip) => {
+            let who = ip.map_or("unknown address".to_string(), |ip| ip.to_string());
+            (ResponseHead::new(403, "Forbidden"), format!("{who} is not allowed\n"))
+        }
+    };
+    if let Some(r) = record.as_mut() {
+        r.status = Some(head.status);
+        r.error = Some(format!("rejected: {}", msg.trim_end()));
+    }
+    head.set_header("Cache-Control", "no-store");
+    http::write_simple_response(writer, head, &msg).map(|_| Next::Close)
+}
+
+/// Relays one request to `target` and its response back, filling in `record`.
+fn forward(
+    reader: &mut BufReader<TcpStream>,
+    writer: &mut TcpStream,
+    mut req: RequestHead,
+    target: Option<u16>,
+    record: &mut Option<CapturedRequest>,
+) -> io::Result<Next> {
+    let mut fail = |writer: &mut TcpStream, status: u16, reason: &str, msg: String| {
+        if let Some(r) = record.as_mut() {
+            r.status = Some(status);
+            r.error = Some(msg.trim_end().to_string());
+        }
+        http::write_simple_response(writer, ResponseHead::new(status, reason), &msg)
+            .map(|_| Next::Close)
+    };
+    let Some(port) = target else {
+        let msg = format!("no route for {}\n", req.path());
+        return fail(writer, 404, "Not Found", msg);
+    };
+    let Ok(upstream) = connect_upstream(port) else {
+        let msg = format!("nothing is listening on localhost:{port}\n");
+        return fail(writer, 502, "Bad Gateway", msg);
+    };
+
+    let upgrade = req.is_upgrade();
+    let client_close = req.wants_close();
+    if !upgrade {
+        req.set_header("Connection", "close");
+    }
+    req.remove_header("Keep-Alive");
+    req.remove_header("Proxy-Connection");
+    // Answer `Expect: 100-continue` ourselves instead of waiting on the
+    // upstream's interim response before forwarding the body.
+    if req.header("expect").is_some_and(|v| v.eq_ignore_ascii_case("100-continue")) {
+        req.remove_header("Expect");
+        writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+    }
+
+    let mut up_writer = upstream.try_clone()?;
+    let mut up_reader = BufReader::new(upstream);
+    req.write_to(&mut up_writer)?;
+    http::copy_body(reader, &mut up_writer, req.body(), &mut |d| {
+        if let Some(r) = record.as_mut() {
+            r.request_body.push(d);
+        }
+    })?;
+    up_writer.flush()?;
+
+    let mut resp = loop {
+        let resp = http::read_response_head(&mut up_reader)?;
+        if (100..200).contains(&resp.status) && resp.status != 101 {
+            resp.write_to(writer)?;
+            continue;
+        }
+        break resp;
+    };
+    if let Some(r) = record.as_mut() {
+        r.set_response(&resp);
+    }
+
+    if resp.status == 101 {
+        resp.write_to(writer)?;
+        writer.flush()?;
+        return Ok(Next::Upgraded(up_reader, up_writer));
+    }
+
+    let body = resp.body(&req.method);
+    let close = client_close || body == Body::UntilClose;
+    resp.remove_header("Connection");
+    resp.remove_header("Keep-Alive");
+    if close {
+        resp.set_header("Connection", "close");
+    }
+    resp.write_to(writer)?;
+    http::copy_body(&mut up_reader, writer, body, &mut |d| {
+        if let Some(r) = record.as_mut() {
+            r.response_body.push(d);
+        }
+    })?;
+    writer.flush()?;
+    Ok(if close { Next::Close } else { Next::KeepAlive })
+}
+
+/// After a `101 Switching Protocols` (WebSocket, e.g. dev server HMR) both
+/// sides talk freely; copy bytes each way until either closes.
+fn tunnel(
+    mut client_reader: BufReader<TcpStream>,
+    mut client_writer: TcpStream,
+    mut up_reader: BufReader<TcpStream>,
+    mut up_writer: TcpStream,
+) -> io::Result<()> {
+    client_writer.set_read_timeout(None)?;
+    let upstream = up_writer.try_clone()?;
+    let client = client_writer.try_clone()?;
+    let to_upstream = thread::spawn(move || {
+        let _ = io::copy(&mut client_reader, &mut up_writer);
+        let _ = up_writer.shutdown(Shutdown::Write);
+    });
+    let _ = io::copy(&mut up_reader, &mut client_writer);
+    let _ = client.shutdown(Shutdown::Both);
+    let _ = upstream.shutdown(Shutdown::Both);
+    let _ = to_upstream.join();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, Read};
+
+    use super::*;
+
+    /// Answers every request with `<name> <method> <target>`, keep-alive.
+    fn upstream(name: &'static str) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for conn in listener.incoming().map_while(Result::ok) {
+                thread::spawn(move || {
+                    let mut r = BufReader::new(conn.try_clone().unwrap());
+                    let mut w = conn;
+                    while let Ok(Some(req)) = http::read_request_head(&mut r) {
+                        let mut body = Vec::new();
+                        http::copy_body(&mut r, &mut body, req.body(), &mut |_| {}).unwrap();
+                        let text = format!(
+                            "{name} {} {} {}",
+                            req.method,
+                            req.target,
+                            String::from_utf8_lossy(&body)
+                        );
+                        // Chunked, to exercise framing on the way back.
+                        let _ = write!(
+                            w,
+                            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{text}\r\n0\r\n\r\n",
+                            text.len()
+                        );
+                    }
+                });
+            }
+        });
+        port
+    }
+
+    fn body_of(r: &mut BufReader<TcpStream>) -> (u16, String) {
+        let resp = http::read_response_head(r).unwrap();
+        let mut raw = Vec::new();
+        http::copy_body(r, &mut raw, resp.body("GET"), &mut |_| {}).unwrap();
+        // Decode the single chunk the fake upstream sends.
+        let raw = String::from_utf8(raw).unwrap();
+        let text = raw.lines().nth(1).unwrap_or_default().to_string();
+        (resp.status, text)
+    }
+
+    #[test]
+    fn routes_by_path_over_one_keep_alive_connection() {
+        let web = upstream("web");
+        let api = upstream("api");
+        let config = ProxyConfig {
+            routes: vec![
+                Route { path: "/".into(), subdomain: None, port: web },
+                Route { path: "/api".into(), subdomain: None, port: api },
+            ],
+            inspector: None,
+            guard: None,
+        };
+        let proxy = ProxyServer::bind(0, config).unwrap();
+
+        let conn = TcpStream::connect(("127.0.0.1", proxy.port())).unwrap();
+        let mut r = BufReader::new(conn.try_clone().unwrap());
+        let mut w = conn;
+
+        write!(w, "POST /api/hook HTTP/1.1\r\nHost: x\r\nContent-Length: 2\r\n\r\nhi").unwrap();
+        assert_eq!(body_of(&mut r), (200, "api POST /api/hook hi".to_string()));
+
+        write!(w, "GET /index.html HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+        assert_eq!(body_of(&mut r), (200, "web GET /index.html ".to_string()));
+
+        proxy.set_routes(vec![Route { path: "/api".into(), subdomain: None, port: api }]);
+        write!(w, "GET /index.html HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+        let resp = http::read_response_head(&mut r).unwrap();
+        assert_eq!(resp.status, 404);
+        assert_eq!(resp.header("connection"), Some("close"));
+        let mut rest = String::new();
+        r.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "no route for /index.html\n");
+    }
+
+    #[test]
+    fn reports_unreachable_upstream() {
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let proxy = ProxyServer::bind(0, ProxyConfig::single(closed)).unwrap();
+        let mut conn = TcpStream::connect(("127.0.0.1", proxy.port())).unwrap();
+        write!(conn, "GET / HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+        let mut first = String::new();
+        BufReader::new(conn).read_line(&mut first).unwrap();
+        assert_eq!(first, "HTTP/1.1 502 Bad Gateway\r\n");
+    }
+
+    #[test]
+    fn records_exchanges_for_the_inspector() {
+        let web = upstream("web");
+        let (tx, rx) = std::sync::mpsc::channel();
+        let tx = std::sync::Mutex::new(tx);
+        let inspector = Arc::new(Inspector::new(3000, move |r| {
+            let _ = tx.lock().unwrap().send(r.id);
+        }));
+        let config = ProxyConfig {
+            inspector: Some(inspector.clone()),
+            ..ProxyConfig::single(web)
+        };
+        let proxy = ProxyServer::bind(0, config).unwrap();
+
+        let conn = TcpStream::connect(("127.0.0.1", proxy.port())).unwrap();
+        let mut r = BufReader::new(conn.try_clone().unwrap());
+        let mut w = conn;
+        write!(w, "POST /hook?a=1 HTTP/1.1\r\nHost: x\r\nX-Sig: abc\r\nContent-Length: 7\r\n\r\n{{\"a\":1}}").unwrap();
+        body_of(&mut r);
+
+        // Recorded once the response is relayed, so possibly after we read it.
+        let id = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(inspector.list().len(), 1);
+        let req = inspector.get(id).unwrap();
+        assert_eq!((req.port, req.method.as_str(), req.target.as_str()), (3000, "POST", "/hook?a=1"));
+        assert!(req.request_headers.contains(&("X-Sig".to_string(), "abc".to_string())));
+        assert_eq!(req.request_body.text.as_deref(), Some("{\"a\":1}"));
+        assert_eq!(req.status, Some(200));
+        assert_eq!(req.response_body.text.as_deref(), Some("web POST /hook?a=1 {\"a\":1}"));
+    }
+
+    #[test]
+    fn guard_challenges_before_forwarding() {
+        let web = upstream("web");
+        let config = ProxyConfig {
+            guard: Some(Arc::new(Guard {
+                basic_auth: Some("dev:secret".to_string()),
+                ..Guard::default()
+            })),
+            ..ProxyConfig::single(web)
+        };
+        let proxy = ProxyServer::bind(0, config).unwrap();
+
+        let mut conn = TcpStream::connect(("127.0.0.1", proxy.port())).unwrap();
+        write!(conn, "GET / HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+        let resp = http::read_response_head(&mut BufReader::new(conn)).unwrap();
+        assert_eq!(resp.status, 401);
+        assert!(resp.header("www-authenticate").unwrap().starts_with("Basic "));
+
+        let conn = TcpStream::connect(("127.0.0.1", proxy.port())).unwrap();
+        let mut r = BufReader::new(conn.try_clone().unwrap());
+        let mut w = conn;
+        write!(w, "GET /ok HTTP/1.1\r\nHost: x\r\nAuthorization: Basic ZGV2OnNlY3JldA==\r\n\r\n").unwrap();
+        assert_eq!(body_of(&mut r), (200, "web GET /ok ".to_string()));
+    }
+}
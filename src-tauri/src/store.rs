@@ -1,14 +1,21 @@
 use std::{
+    collections::BTreeMap,
     fs,
     path::PathBuf,
     sync::Mutex,
-    time::{SystemTime, UNIX_EPOCH},
 };
 
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Manager};
 use thiserror::Error;
 
+use crate::{
+    clock::now_ms,
+    port::{scanner::AUTO_BACKEND, watcher::DEFAULT_SCAN_INTERVAL_MS},
+    proxy::{front::TunnelOptions, group::ShareGroup},
+    tunnel::{bore::BoreConfig, cloudflare::NamedTunnelConfig, ssh::SshConfig},
+};
+
 #[derive(Debug, Error)]
 pub enum StoreError {
     #[error("io: {0}")]
@@ -23,12 +30,48 @@ pub enum StoreError {
 pub struct DesiredTunnel {
     pub port: u16,
     pub provider: String,
+    #[serde(default)]
+    pub options: TunnelOptions,
+    /// Closed and dropped from the list at this time, across restarts too.
+    #[serde(default)]
+    pub expires_at_ms: Option<u64>,
     pub updated_at_ms: u64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub scan_interval_ms: u64,
+    pub scan_backend: String,
+    pub ngrok_authtoken: Option<String>,
+    pub ssh: SshConfig,
+    pub bore: BoreConfig,
+    pub cloudflare_named: NamedTunnelConfig,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            scan_interval_ms: DEFAULT_SCAN_INTERVAL_MS,
+            scan_backend: AUTO_BACKEND.to_string(),
+            ngrok_authtoken: None,
+            ssh: SshConfig::default(),
+            bore: BoreConfig::default(),
+            cloudflare_named: NamedTunnelConfig::default(),
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct StoreData {
     pub desired_tunnels: Vec<DesiredTunnel>,
+    #[serde(default)]
+    pub settings: Settings,
+    /// Hostname each port is published under on the named Cloudflare tunnel.
+    #[serde(default)]
+    pub tunnel_hostnames: BTreeMap<u16, String>,
+    #[serde(default)]
+    pub share_groups: Vec<ShareGroup>,
 }
 
 pub struct Store {
@@ -41,7 +84,7 @@ impl Store {
         let dir = app
             .path()
             .app_data_dir()
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
         fs::create_dir_all(&dir)?;
         let path = dir.join("state.json");
 
@@ -93,6 +136,110 @@ impl Store {
         self.persist()
     }
 
+    pub fn set_desired_expiry(&self, port: u16, expires_at_ms: Option<u64>) -> Result<(), StoreError> {
+        let mut guard = self.data.lock().map_err(|_| StoreError::LockFailed)?;
+        for x in &mut guard.desired_tunnels {
+            if x.port == port {
+                x.expires_at_ms = expires_at_ms;
+            }
+        }
+        drop(guard);
+        self.persist()
+    }
+
+    /// Directory holding `state.json`, also used for generated provider config.
+    pub fn data_dir(&self) -> PathBuf {
+        self.path.parent().map(PathBuf::from).unwrap_or_default()
+    }
+
+    pub fn tunnel_hostnames(&self) -> BTreeMap<u16, String> {
+        match self.data.lock() {
+            Ok(g) => g.tunnel_hostnames.clone(),
+            Err(_) => BTreeMap::new(),
+        }
+    }
+
+    pub fn set_tunnel_hostname(&self, port: u16, hostname: Option<String>) -> Result<(), StoreError> {
+        let mut guard = self.data.lock().map_err(|_| StoreError::LockFailed)?;
+        match hostname {
+            Some(h) => guard.tunnel_hostnames.insert(port, h),
+            None => guard.tunnel_hostnames.remove(&port),
+        };
+        drop(guard);
+        self.persist()
+    }
+
+    pub fn share_groups(&self) -> Vec<ShareGroup> {
+        match self.data.lock() {
+            Ok(g) => g.share_groups.clone(),
+            Err(_) => vec![],
+        }
+    }
+
+    pub fn save_share_group(&self, group: ShareGroup) -> Result<(), StoreError> {
+        let mut guard = self.data.lock().map_err(|_| StoreError::LockFailed)?;
+        guard.share_groups.retain(|g| g.name != group.name);
+        guard.share_groups.push(group);
+        drop(guard);
+        self.persist()
+    }
+
+    pub fn remove_share_group(&self, name: &str) -> Result<(), StoreError> {
+        let mut guard = self.data.lock().map_err(|_| StoreError::LockFailed)?;
+        guard.share_groups.retain(|g| g.name != name);
+        drop(guard);
+        self.persist()
+    }
+
+    pub fn settings(&self) -> Settings {
+        match self.data.lock() {
+            Ok(g) => g.settings.clone(),
+            Err(_) => Settings::default(),
+        }
+    }
+
+    pub fn set_scan_interval_ms(&self, interval_ms: u64) -> Result<(), StoreError> {
+        let mut guard = self.data.lock().map_err(|_| StoreError::LockFailed)?;
+        guard.settings.scan_interval_ms = interval_ms;
+        drop(guard);
+        self.persist()
+    }
+
+    pub fn set_scan_backend(&self, backend: &str) -> Result<(), StoreError> {
+        let mut guard = self.data.lock().map_err(|_| StoreError::LockFailed)?;
+        guard.settings.scan_backend = backend.to_string();
+        drop(guard);
+        self.persist()
+    }
+
+    pub fn set_ngrok_authtoken(&self, token: Option<String>) -> Result<(), StoreError> {
+        let mut guard = self.data.lock().map_err(|_| StoreError::LockFailed)?;
+        guard.settings.ngrok_authtoken = token;
+        drop(guard);
+        self.persist()
+    }
+
+    pub fn set_ssh_config(&self, config: SshConfig) -> Result<(), StoreError> {
+        let mut guard = self.data.lock().map_err(|_| StoreError::LockFailed)?;
+        guard.settings.ssh = config;
+        drop(guard);
+        self.persist()
+    }
+
+    pub fn set_bore_config(&self, config: BoreConfig) -> Result<(), StoreError> {
+        let mut guard = self.data.lock().map_err(|_| StoreError::LockFailed)?;
+        guard.settings.bore = config;
+        drop(guard);
+        self.persist()
+    }
+
+    pub fn set_cloudflare_named(&self, config: NamedTunnelConfig) -> Result<(), StoreError> {
+        let mut guard = self.data.lock().map_err(|_| StoreError::LockFailed)?;
+        guard.settings.cloudflare_named = config;
+        drop(guard);
+        self.persist()
+    }
+
     fn persist(&self) -> Result<(), StoreError> {
         let guard = self.data.lock().map_err(|_| StoreError::LockFailed)?;
         let tmp = self.path.with_extension("json.tmp");
@@ -102,9 +249,3 @@ impl Store {
     }
 }
 
-fn now_ms() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64
-}
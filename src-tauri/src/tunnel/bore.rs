@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    process::{Child, Command},
+    sync::mpsc::UnboundedReceiver,
+};
+
+use super::{
+    process::{spawn_with_events, wait_until_ready, BoxFuture, TunnelLog},
+    provider::{Forward, TunnelError, TunnelProvider},
+    registry::{resolve_binary, spawn_error, status_for, ProviderStatus},
+    TunnelKind,
+};
+
+const ADDRESS_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Which bore server to use. Saved in `settings.bore`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoreConfig {
+    pub server: String,
+    pub secret: Option<String>,
+}
+
+impl Default for BoreConfig {
+    fn default() -> Self {
+        Self {
+            server: "bore.pub".to_string(),
+            secret: None,
+        }
+    }
+}
+
+/// Raw TCP forwarding through a bore server, for databases, caches and
+/// anything else that doesn't speak HTTP. URLs look like `tcp://bore.pub:41234`.
+pub struct BoreProvider {
+    pub binary: String,
+    pub config: BoreConfig,
+}
+
+impl Default for BoreProvider {
+    fn default() -> Self {
+        Self::new(BoreConfig::default())
+    }
+}
+
+impl BoreProvider {
+    pub fn new(config: BoreConfig) -> Self {
+        Self {
+            binary: "bore".to_string(),
+            config,
+        }
+    }
+}
+
+enum BoreEvent {
+    Listening(String),
+    Line(String),
+}
+
+impl TunnelProvider for BoreProvider {
+    fn name(&self) -> &'static str {
+        "bore"
+    }
+
+    fn kind(&self) -> TunnelKind {
+        TunnelKind::Tcp
+    }
+
+    fn start(&self, forward: Forward, log: TunnelLog) -> BoxFuture<'_, Result<(Child, String), TunnelError>> {
+        Box::pin(async move {
+            let bin = resolve_binary(&self.binary, "BORE_PATH").unwrap_or_else(|| self.binary.clone());
+
+            let mut cmd = Command::new(&bin);
+            cmd.args(["local", &forward.target.to_string(), "--to", self.config.server.trim()]);
+            if let Some(secret) = self.config.secret.as_deref().filter(|s| !s.is_empty()) {
+                cmd.env("BORE_SECRET", secret);
+            }
+            let (mut child, mut rx) = spawn_with_events(&mut cmd, &log, classify_line())
+                .map_err(|e| spawn_error("bore", "BORE_PATH", &bin, e))?;
+
+            match wait_for_address(&mut child, &mut rx).await {
+                Ok(addr) => Ok((child, format!("tcp://{addr}"))),
+                Err(e) => {
+                    let _ = child.kill().await;
+                    Err(e)
+                }
+            }
+        })
+    }
+
+    fn status(&self) -> ProviderStatus {
+        status_for(self.name(), resolve_binary(&self.binary, "BORE_PATH"), &["--version"])
+    }
+}
+
+async fn wait_for_address(
+    child: &mut Child,
+    rx: &mut UnboundedReceiver<BoreEvent>,
+) -> Result<String, TunnelError> {
+    let mut last_line: Option<String> = None;
+    let waited = wait_until_ready(child, rx, ADDRESS_TIMEOUT, |event| match event {
+        BoreEvent::Listening(addr) => Some(addr),
+        BoreEvent::Line(line) => {
+            last_line = Some(line);
+            None
+        }
+    })
+    .await;
+    waited.map_err(|e| e.into_error("bore", last_line))
+}
+
+fn classify_line() -> impl Fn(&str) -> Option<BoreEvent> + Send + Sync + 'static {
+    let ansi = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+    let listening = Regex::new(r"listening at ([A-Za-z0-9.-]+:\d+)").unwrap();
+    move |line| {
+        // bore logs through `tracing`, which colours output even when piped.
+        let line = ansi.replace_all(line, "");
+        match listening.captures(&line) {
+            Some(c) => Some(BoreEvent::Listening(c[1].to_string())),
+            None if !line.trim().is_empty() => Some(BoreEvent::Line(line.trim().to_string())),
+            None => None,
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::tunnel::testing::stub_binary;
+
+    fn provider(bin: &std::path::Path, secret: Option<&str>) -> BoreProvider {
+        BoreProvider {
+            binary: bin.to_string_lossy().to_string(),
+            config: BoreConfig {
+                server: "bore.example.com".to_string(),
+                secret: secret.map(|s| s.to_string()),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn returns_tcp_endpoint_from_coloured_log() {
+        let bin = stub_binary(
+            "bore",
+            "ok",
+            r#"[ "$1 $2 $3 $4" = "local 5432 --to bore.example.com" ] || exit 2
+[ "$BORE_SECRET" = hunter2 ] || { echo "Error: server error: invalid secret"; exit 1; }
+printf '\033[2m2024-05-01T10:00:00Z\033[0m \033[32m INFO\033[0m \033[2mbore_cli::client\033[0m\033[2m:\033[0m connected to server \033[3mremote_port\033[0m\033[2m=\033[0m41234\n'
+printf '\033[2m2024-05-01T10:00:00Z\033[0m \033[32m INFO\033[0m \033[2mbore_cli::client\033[0m\033[2m:\033[0m listening at bore.example.com:41234\n'
+exec sleep 30"#,
+        );
+
+        let p = provider(&bin, Some("hunter2"));
+        let (mut child, url) = p.start(Forward::direct(5432), TunnelLog::default()).await.unwrap();
+        child.kill().await.unwrap();
+        assert_eq!(url, "tcp://bore.example.com:41234");
+        assert_eq!(p.build_info(5432, url).kind, TunnelKind::Tcp);
+
+        let err = provider(&bin, Some("wrong"))
+            .start(Forward::direct(5432), TunnelLog::default())
+            .await
+            .unwrap_err()
+            .to_string();
+        let _ = fs::remove_dir_all(bin.parent().unwrap());
+        assert!(err.contains("invalid secret"), "{err}");
+    }
+}
@@ -1,225 +1,371 @@
 use std::{
-    io::{BufRead, BufReader},
-    process::{Child, Command, Stdio},
+    collections::BTreeMap,
+    fs,
     path::{Path, PathBuf},
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc, Mutex,
-    },
-    thread,
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    time::Duration,
 };
 
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    process::{Child, Command},
+    sync::mpsc::UnboundedReceiver,
+};
+
+use super::{
+    process::{spawn_with_events, wait_until_ready, BoxFuture, NotReady, TunnelLog},
+    provider::{Forward, Routed, TunnelError, TunnelProvider},
+    registry::{resolve_binary, spawn_error, status_for, ProviderStatus},
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(20);
+const URL_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// A named tunnel created beforehand with `cloudflared tunnel create`. Saved
+/// in `settings.cloudflare_named`; when set, `cloudflare` uses it instead of
+/// Quick Tunnels.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NamedTunnelConfig {
+    pub tunnel_id: String,
+    pub credentials_file: String,
+}
 
-use super::{provider::TunnelError, provider::TunnelProvider, TunnelInfo};
+impl NamedTunnelConfig {
+    pub fn is_configured(&self) -> bool {
+        !self.tunnel_id.trim().is_empty() && !self.credentials_file.trim().is_empty()
+    }
+}
+
+struct NamedTunnel {
+    config: NamedTunnelConfig,
+    hostnames: BTreeMap<u16, String>,
+    config_path: PathBuf,
+}
+
+impl NamedTunnel {
+    /// cloudflared's `config.yml`: one ingress rule per shared port that has a
+    /// hostname, then the mandatory catch-all. Strings are written as JSON,
+    /// which YAML reads as double-quoted scalars.
+    fn render_config(&self, routes: &[Forward]) -> String {
+        let quote = |s: &str| serde_json::to_string(s.trim()).unwrap_or_default();
+        let mut out = format!(
+            "tunnel: {}\ncredentials-file: {}\ningress:\n",
+            quote(&self.config.tunnel_id),
+            quote(&self.config.credentials_file),
+        );
+        for f in routes {
+            if let Some(hostname) = self.hostnames.get(&f.port) {
+                out.push_str(&format!(
+                    "  - hostname: {}\n    service: {}\n",
+                    quote(hostname),
+                    quote(&format!("http://localhost:{}", f.target)),
+                ));
+            }
+        }
+        out.push_str("  - service: http_status:404\n");
+        out
+    }
+}
 
 pub struct CloudflareProvider {
     pub binary: String,
+    named: Option<NamedTunnel>,
 }
 
 impl Default for CloudflareProvider {
     fn default() -> Self {
         Self {
             binary: "cloudflared".to_string(),
+            named: None,
         }
     }
 }
 
-fn resolve_cloudflared_binary(configured: &str) -> Option<String> {
-    // 1) Explicit path in config
-    if looks_like_path(configured) && Path::new(configured).exists() {
-        return Some(configured.to_string());
+impl CloudflareProvider {
+    /// Runs `cloudflared tunnel run` on a named tunnel instead of Quick
+    /// Tunnels, so each port keeps its hostname from `hostnames` across
+    /// renewals. The generated config is written to `config_dir`. Falls back
+    /// to Quick Tunnels while `config` is incomplete.
+    pub fn named(
+        config: NamedTunnelConfig,
+        hostnames: BTreeMap<u16, String>,
+        config_dir: &Path,
+    ) -> Self {
+        if !config.is_configured() {
+            return Self::default();
+        }
+        let config_path = config_dir.join(format!("cloudflared-{}.yml", config.tunnel_id.trim()));
+        Self {
+            named: Some(NamedTunnel {
+                config,
+                hostnames,
+                config_path,
+            }),
+            ..Self::default()
+        }
     }
 
-    // 2) Env override
-    if let Ok(p) = std::env::var("CLOUDFLARED_PATH") {
-        if !p.trim().is_empty() && Path::new(&p).exists() {
-            return Some(p);
+    async fn start_named(
+        &self,
+        named: &NamedTunnel,
+        routes: &[Forward],
+        log: TunnelLog,
+    ) -> Result<Routed, TunnelError> {
+        let mut urls = BTreeMap::new();
+        for f in routes {
+            let hostname = named.hostnames.get(&f.port).ok_or_else(|| {
+                TunnelError::StartFailed(format!(
+                    "no hostname is mapped to port {} on the named tunnel",
+                    f.port
+                ))
+            })?;
+            urls.insert(f.port, format!("https://{hostname}"));
         }
-    }
+        if let Some(dir) = named.config_path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        fs::write(&named.config_path, named.render_config(routes)).map_err(|e| {
+            TunnelError::StartFailed(format!(
+                "could not write {}: {e}",
+                named.config_path.display()
+            ))
+        })?;
 
-    // 3) Sidecar next to executable (Tauri externalBin pattern)
-    if let Ok(exe) = std::env::current_exe() {
-        if let Some(dir) = exe.parent() {
-            for name in candidate_names() {
-                let p = dir.join(name);
-                if p.exists() {
-                    return Some(p.to_string_lossy().to_string());
-                }
-            }
+        let bin = resolve_binary(&self.binary, "CLOUDFLARED_PATH").unwrap_or_else(|| self.binary.clone());
+        let mut cmd = Command::new(&bin);
+        cmd.args(["tunnel", "--no-autoupdate", "--config"])
+            .arg(&named.config_path)
+            .args(["run", named.config.tunnel_id.trim()]);
+        let (mut child, mut rx) =
+            spawn_with_events(&mut cmd, &log, classify_connector_line)
+                .map_err(|e| spawn_error("cloudflared", "CLOUDFLARED_PATH", &bin, e))?;
 
-            // 4) macOS app bundle Resources directory
-            // <App>.app/Contents/MacOS/<exe> -> Resources at ../Resources
-            if cfg!(target_os = "macos") {
-                if let Some(contents) = dir.parent() {
-                    let resources = contents.join("Resources");
-                    for name in candidate_names() {
-                        let p = resources.join(name);
-                        if p.exists() {
-                            return Some(p.to_string_lossy().to_string());
-                        }
-                    }
-                }
+        match wait_for_connection(&mut child, &mut rx).await {
+            Ok(()) => Ok((child, urls)),
+            Err(e) => {
+                let _ = child.kill().await;
+                Err(e)
             }
         }
     }
 
-    // 5) Common system install locations (packaged apps often don't inherit shell PATH)
-    for p in common_locations() {
-        if p.exists() {
-            return Some(p.to_string_lossy().to_string());
+    async fn start_quick(&self, port: u16, log: TunnelLog) -> Result<(Child, String), TunnelError> {
+        let bin = resolve_binary(&self.binary, "CLOUDFLARED_PATH").unwrap_or_else(|| self.binary.clone());
+        let mut cmd = Command::new(&bin);
+        cmd.args([
+            "tunnel",
+            "--url",
+            &format!("http://localhost:{port}"),
+            "--no-autoupdate",
+        ]);
+        let re = Regex::new(r"https?://[A-Za-z0-9.-]+\.trycloudflare\.com").unwrap();
+        let (mut child, mut rx) = spawn_with_events(&mut cmd, &log, move |line| {
+            Some(match re.find(line) {
+                Some(m) => QuickEvent::Url(m.as_str().to_string()),
+                None => QuickEvent::Line(line.trim().to_string()),
+            })
+        })
+        .map_err(|e| spawn_error("cloudflared", "CLOUDFLARED_PATH", &bin, e))?;
+
+        match wait_for_quick_url(&mut child, &mut rx).await {
+            Ok(url) => Ok((child, url)),
+            Err(e) => {
+                let _ = child.kill().await;
+                Err(e)
+            }
         }
     }
-
-    None
 }
 
-fn looks_like_path(s: &str) -> bool {
-    s.contains('/') || s.contains('\\')
-}
-
-fn candidate_names() -> Vec<&'static str> {
-    if cfg!(target_os = "windows") {
-        vec!["cloudflared.exe", "cloudflared"]
-    } else {
-        vec!["cloudflared"]
+impl TunnelProvider for CloudflareProvider {
+    fn name(&self) -> &'static str {
+        "cloudflare"
     }
-}
 
-fn common_locations() -> Vec<PathBuf> {
-    if cfg!(target_os = "macos") {
-        return vec![
-            PathBuf::from("/opt/homebrew/bin/cloudflared"),
-            PathBuf::from("/usr/local/bin/cloudflared"),
-            PathBuf::from("/usr/bin/cloudflared"),
-        ];
+    fn shares_routes(&self) -> bool {
+        // A named tunnel runs one `tunnel run` connector, whose ingress lists
+        // every shared port; a second one would get a share of the traffic.
+        self.named.is_some()
     }
 
-    if cfg!(target_os = "windows") {
-        let mut out = vec![
-            PathBuf::from(r"C:\ProgramData\chocolatey\bin\cloudflared.exe"),
-        ];
-        for var in ["ProgramFiles", "ProgramFiles(x86)", "LocalAppData"] {
-            if let Ok(root) = std::env::var(var) {
-                out.push(PathBuf::from(&root).join("Cloudflare").join("cloudflared.exe"));
-                out.push(PathBuf::from(&root).join("cloudflared").join("cloudflared.exe"));
+    fn start_routed<'a>(
+        &'a self,
+        routes: &'a [Forward],
+        log: TunnelLog,
+    ) -> BoxFuture<'a, Result<Routed, TunnelError>> {
+        Box::pin(async move {
+            match &self.named {
+                Some(named) => self.start_named(named, routes, log).await,
+                None => {
+                    let Some(&forward) = routes.first() else {
+                        return Err(TunnelError::StartFailed("no ports to route".to_string()));
+                    };
+                    let (child, url) = self.start_quick(forward.target, log).await?;
+                    Ok((child, BTreeMap::from([(forward.port, url)])))
+                }
             }
-        }
-        return out;
+        })
     }
 
-    vec![
-        PathBuf::from("/usr/local/bin/cloudflared"),
-        PathBuf::from("/usr/bin/cloudflared"),
-        PathBuf::from("/snap/bin/cloudflared"),
-    ]
-}
+    fn start(&self, forward: Forward, log: TunnelLog) -> BoxFuture<'_, Result<(Child, String), TunnelError>> {
+        Box::pin(async move {
+            match &self.named {
+                Some(named) => {
+                    let (child, mut urls) = self.start_named(named, &[forward], log).await?;
+                    Ok((child, urls.remove(&forward.port).unwrap_or_default()))
+                }
+                None => self.start_quick(forward.target, log).await,
+            }
+        })
+    }
 
-impl TunnelProvider for CloudflareProvider {
-    fn name(&self) -> &'static str {
-        "cloudflare"
+    fn status(&self) -> ProviderStatus {
+        let bin = resolve_binary(&self.binary, "CLOUDFLARED_PATH");
+        status_for(self.name(), bin, &["--version"])
     }
+}
 
-    fn start(&self, port: u16) -> Result<(Child, String), TunnelError> {
-        let bin = resolve_cloudflared_binary(&self.binary).unwrap_or_else(|| self.binary.clone());
-
-        let mut child = Command::new(&bin)
-            .args([
-                "tunnel",
-                "--url",
-                &format!("http://localhost:{port}"),
-                "--no-autoupdate",
-            ])
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| {
-                if e.kind() == std::io::ErrorKind::NotFound {
-                    TunnelError::StartFailed(format!(
-                        "cloudflared not found. Install it and ensure it's accessible to the app, or set CLOUDFLARED_PATH.\nTried binary: {bin}"
-                    ))
-                } else {
-                    TunnelError::StartFailed(format!("cloudflared failed to start: {e}"))
-                }
-            })?;
+enum ConnectorEvent {
+    Registered,
+    Error(String),
+    Line(String),
+}
 
-        let url_slot: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
-        let found = Arc::new(AtomicBool::new(false));
-        let re = Regex::new(r"https?://[A-Za-z0-9.-]+\.trycloudflare\.com").unwrap();
+enum QuickEvent {
+    Url(String),
+    Line(String),
+}
 
-        if let Some(stdout) = child.stdout.take() {
-            spawn_reader(stdout, re.clone(), url_slot.clone(), found.clone());
+/// A named tunnel is up once cloudflared logs its first
+/// `Registered tunnel connection` to the edge.
+async fn wait_for_connection(
+    child: &mut Child,
+    rx: &mut UnboundedReceiver<ConnectorEvent>,
+) -> Result<(), TunnelError> {
+    let mut error: Option<String> = None;
+    let mut last_line: Option<String> = None;
+    let waited = wait_until_ready(child, rx, CONNECT_TIMEOUT, |event| match event {
+        ConnectorEvent::Registered => Some(()),
+        ConnectorEvent::Error(e) => {
+            error = Some(e);
+            None
         }
-        if let Some(stderr) = child.stderr.take() {
-            spawn_reader(stderr, re.clone(), url_slot.clone(), found.clone());
+        ConnectorEvent::Line(line) => {
+            last_line = Some(line);
+            None
         }
+    })
+    .await;
+    waited.map_err(|e| match (e, error) {
+        (NotReady::TimedOut, Some(error)) => TunnelError::StartFailed(error),
+        (e, error) => e.into_error("cloudflared", error.or(last_line)),
+    })
+}
 
-        let start = SystemTime::now();
-        loop {
-            if found.load(Ordering::SeqCst) {
-                let url = url_slot.lock().ok().and_then(|x| x.clone());
-                if let Some(url) = url {
-                    return Ok((child, url));
-                }
+/// A Quick Tunnel prints its `trycloudflare.com` URL once it is assigned.
+async fn wait_for_quick_url(
+    child: &mut Child,
+    rx: &mut UnboundedReceiver<QuickEvent>,
+) -> Result<String, TunnelError> {
+    let mut last_line: Option<String> = None;
+    let waited = wait_until_ready(child, rx, URL_TIMEOUT, |event| match event {
+        QuickEvent::Url(url) => Some(url),
+        QuickEvent::Line(line) => {
+            if !line.is_empty() {
+                last_line = Some(line);
             }
-            if start.elapsed().unwrap_or_default() > Duration::from_secs(20) {
-                let _ = child.kill();
-                let _ = child.wait();
-                return Err(TunnelError::UrlTimeout);
-            }
-            thread::sleep(Duration::from_millis(80));
+            None
         }
-    }
+    })
+    .await;
+    waited.map_err(|e| e.into_error("cloudflared", last_line))
+}
 
-    fn stop(&self, child: &mut Child) -> Result<(), TunnelError> {
-        child
-            .kill()
-            .map_err(|e| TunnelError::StopFailed(e.to_string()))?;
-        let _ = child.wait();
-        Ok(())
+fn classify_connector_line(line: &str) -> Option<ConnectorEvent> {
+    let line = line.trim().to_string();
+    if line.contains("Registered tunnel connection") {
+        Some(ConnectorEvent::Registered)
+    } else if line.contains(" ERR ") {
+        Some(ConnectorEvent::Error(line))
+    } else if !line.is_empty() {
+        Some(ConnectorEvent::Line(line))
+    } else {
+        None
     }
+}
 
-    fn build_info(&self, port: u16, url: String) -> TunnelInfo {
-        let now = now_ms();
-        TunnelInfo {
-            port,
-            provider: self.name().to_string(),
-            url,
-            started_at_ms: now,
-            last_renewed_at_ms: now,
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::tunnel::testing::stub_binary;
+
+    fn named_config() -> NamedTunnelConfig {
+        NamedTunnelConfig {
+            tunnel_id: "6ff42ae2-765d-4adf-8112-31c55c1551ef".to_string(),
+            credentials_file: "/home/me/.cloudflared/6ff42ae2.json".to_string(),
         }
     }
-}
 
-fn spawn_reader<R: std::io::Read + Send + 'static>(
-    reader: R,
-    re: Regex,
-    url_slot: Arc<Mutex<Option<String>>>,
-    found: Arc<AtomicBool>,
-) {
-    thread::spawn(move || {
-        let buf = BufReader::new(reader);
-        for line in buf.lines().flatten() {
-            if found.load(Ordering::SeqCst) {
-                continue;
-            }
-            if let Some(m) = re.find(&line) {
-                if let Ok(mut slot) = url_slot.lock() {
-                    if slot.is_none() {
-                        *slot = Some(m.as_str().to_string());
-                        found.store(true, Ordering::SeqCst);
-                    }
-                }
-            }
-        }
-    });
-}
+    fn hostnames() -> BTreeMap<u16, String> {
+        BTreeMap::from([
+            (3000, "app.example.com".to_string()),
+            (8080, "hooks.example.com".to_string()),
+        ])
+    }
+
+    #[test]
+    fn renders_ingress_for_shared_ports_only() {
+        let named = NamedTunnel {
+            config: named_config(),
+            hostnames: hostnames(),
+            config_path: PathBuf::new(),
+        };
+        let routes = [
+            Forward { port: 3000, target: 41000 },
+            Forward { port: 5173, target: 5173 },
+        ];
+        assert_eq!(
+            named.render_config(&routes),
+            "tunnel: \"6ff42ae2-765d-4adf-8112-31c55c1551ef\"\n\
+             credentials-file: \"/home/me/.cloudflared/6ff42ae2.json\"\n\
+             ingress:\n\
+             \x20 - hostname: \"app.example.com\"\n\
+             \x20   service: \"http://localhost:41000\"\n\
+             \x20 - service: http_status:404\n"
+        );
+    }
 
-fn now_ms() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64
+    #[tokio::test]
+    async fn named_tunnel_runs_generated_config_and_keeps_hostname() {
+        let bin = stub_binary(
+            "cloudflared",
+            "named",
+            r#"[ "$1 $2 $3" = "tunnel --no-autoupdate --config" ] || exit 2
+[ "$5 $6" = "run 6ff42ae2-765d-4adf-8112-31c55c1551ef" ] || exit 3
+grep -q 'hostname: "hooks.example.com"' "$4" || { echo "2024-05-01T10:00:00Z ERR missing ingress rule" >&2; exit 1; }
+echo "2024-05-01T10:00:00Z INF Starting tunnel tunnelID=6ff42ae2-765d-4adf-8112-31c55c1551ef" >&2
+echo "2024-05-01T10:00:01Z INF Registered tunnel connection connIndex=0 location=ams01 protocol=quic" >&2
+exec sleep 30"#,
+        );
+        let dir = bin.parent().unwrap();
+
+        let mut p = CloudflareProvider::named(named_config(), hostnames(), dir);
+        p.binary = bin.to_string_lossy().to_string();
+        assert!(p.shares_routes());
+
+        let fwd = Forward::direct;
+        let log = TunnelLog::default();
+        let (mut child, urls) = p.start_routed(&[fwd(3000), fwd(8080)], log).await.unwrap();
+        child.kill().await.unwrap();
+        assert_eq!(urls[&3000], "https://app.example.com");
+        assert_eq!(urls[&8080], "https://hooks.example.com");
+
+        let log = TunnelLog::default();
+        let err = p.start_routed(&[fwd(3000)], log.clone()).await.unwrap_err().to_string();
+        let unmapped = p.start(Forward::direct(5173), log).await.unwrap_err().to_string();
+        let _ = fs::remove_dir_all(dir);
+        assert!(err.contains("missing ingress rule"), "{err}");
+        assert!(unmapped.contains("no hostname is mapped to port 5173"), "{unmapped}");
+    }
 }
@@ -1,98 +1,1528 @@
 use std::{
-    collections::HashMap,
-    process::Child,
-    time::{SystemTime, UNIX_EPOCH},
+    collections::{hash_map::RandomState, BTreeMap, HashMap, HashSet, VecDeque},
+    hash::BuildHasher,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::watch,
+    task::{self, JoinSet},
 };
 
 use super::{
-    cloudflare::CloudflareProvider,
-    provider::{TunnelError, TunnelProvider},
-    TunnelInfo,
+    process::{LogLine, TunnelLog, TunnelProcess},
+    provider::{Forward, Routed, TunnelError, TunnelProvider},
+    registry::{ProviderRegistry, DEFAULT_PROVIDER},
+    upstream::check_upstream,
+    TunnelInfo, TunnelKind, TunnelState,
 };
+use crate::clock::now_ms;
+
+pub const TUNNEL_STATE_EVENT: &str = "tunnels://state";
+pub const TUNNEL_EXPIRING_EVENT: &str = "tunnels://expiring";
+pub const TUNNEL_EXPIRED_EVENT: &str = "tunnels://expired";
+pub const TUNNEL_LOG_EVENT: &str = "tunnels://log";
+/// Output lines appended to the error of a failed start.
+const ERROR_OUTPUT_LINES: usize = 10;
+/// Failed starts whose output is kept per port.
+const MAX_FAILED_ATTEMPTS: usize = 3;
+/// How long before expiry a warning goes out, largest first.
+const EXPIRY_WARNINGS_MS: [u64; 2] = [5 * 60 * 1000, 60 * 1000];
+
+/// Payload of `TUNNEL_STATE_EVENT`. `from` is `None` for a tunnel that was not
+/// tracked yet, `to` is `None` once it is closed or failed to start.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TunnelTransition {
+    pub port: u16,
+    pub provider: String,
+    pub from: Option<TunnelState>,
+    pub to: Option<TunnelState>,
+    pub at_ms: u64,
+    pub restart_count: u32,
+    pub error: Option<String>,
+}
+
+type Listener = Box<dyn Fn(&TunnelTransition) + Send>;
+
+/// Payload of `TUNNEL_LOG_EVENT`: one line a tunnel's process printed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TunnelLogLine {
+    pub port: u16,
+    #[serde(flatten)]
+    pub line: LogLine,
+}
+
+type LogListener = Arc<dyn Fn(&TunnelLogLine) + Send + Sync>;
+
+/// What `get_tunnel_logs` returns for a port.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TunnelLogs {
+    pub port: u16,
+    /// Output of the running process, or of the one starting. Oldest first.
+    pub current: Vec<LogLine>,
+    /// The last failed starts and crashes, newest first.
+    pub failed: Vec<FailedAttempt>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedAttempt {
+    pub provider: String,
+    pub at_ms: u64,
+    pub error: String,
+    pub lines: Vec<LogLine>,
+}
+
+#[derive(Default)]
+struct PortLogs {
+    current: Option<TunnelLog>,
+    failed: VecDeque<FailedAttempt>,
+}
+
+/// Payload of both expiry events.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TunnelExpiry {
+    pub port: u16,
+    pub expires_at_ms: u64,
+}
+
+/// When a tunnel should close, as given to `open_tunnel`:
+/// `{ "ttl_secs": 3600 }` or `{ "at_ms": 1767225600000 }`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpirySpec {
+    TtlSecs(u64),
+    AtMs(u64),
+}
+
+impl ExpirySpec {
+    /// The deadline in epoch milliseconds; it must lie after `now_ms`.
+    pub fn resolve(self, now_ms: u64) -> Result<u64, String> {
+        let at = match self {
+            Self::TtlSecs(0) => return Err("ttl_secs must be positive".to_string()),
+            Self::TtlSecs(ttl) => now_ms.saturating_add(ttl.saturating_mul(1000)),
+            Self::AtMs(at) => at,
+        };
+        if at <= now_ms {
+            return Err("expiry is in the past".to_string());
+        }
+        Ok(at)
+    }
+}
+
+/// How dead tunnels are renewed: right away, then after exponentially
+/// growing, jittered delays, until `max_attempts` renewals in a row failed.
+/// A process that dies within `stable_ms` of coming up counts as a failed
+/// renewal too, so a provider that publishes a URL and then exits (say, on
+/// a rejected token) is not respawned in a loop.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub base_ms: u64,
+    pub max_ms: u64,
+    pub max_attempts: u32,
+    pub stable_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            base_ms: 2_000,
+            max_ms: 5 * 60 * 1000,
+            max_attempts: 8,
+            stable_ms: 60 * 1000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait after the `failures`-th failed renewal: `base * 2^(failures-1)`
+    /// capped at `max`, then scaled into its upper half by `jitter` so
+    /// tunnels that died together do not retry in lockstep.
+    pub fn delay_ms(&self, failures: u32, jitter: u64) -> u64 {
+        let exp = failures.saturating_sub(1).min(32);
+        let full = self.base_ms.saturating_mul(1u64 << exp).min(self.max_ms);
+        full - full / 2 + jitter % (full / 2 + 1)
+    }
+}
+
+struct Deadline {
+    at_ms: u64,
+    /// How many of `EXPIRY_WARNINGS_MS` were already sent.
+    warned: usize,
+}
 
 struct ActiveTunnel {
-    child: Child,
+    /// `None` when its provider's `Connector` serves it.
+    process: Option<TunnelProcess>,
     info: TunnelInfo,
+    /// When the process came up.
+    up_since_ms: u64,
+    /// Failed renewals it took to come up; counted on if it dies too soon.
+    attempts: u32,
+}
+
+/// The one process serving every port of a provider that shares routes.
+struct Connector {
+    process: TunnelProcess,
+    /// The routing table it was started with; see `TunnelManager::reroute`.
+    routes: Vec<Forward>,
+    /// The provider it was started from. Replaced when its settings change,
+    /// which makes the routing table out of date too.
+    launched_by: Arc<dyn TunnelProvider>,
 }
 
+/// Where callers for a port that is already starting wait for the outcome,
+/// instead of launching a second process.
+struct StartSlot(watch::Sender<Option<Result<TunnelInfo, TunnelError>>>);
+
+impl Default for StartSlot {
+    fn default() -> Self {
+        Self(watch::channel(None).0)
+    }
+}
+
+impl StartSlot {
+    fn fill(&self, result: &Result<TunnelInfo, TunnelError>) {
+        self.0.send_replace(Some(result.clone()));
+    }
+
+    async fn wait(&self) -> Result<TunnelInfo, TunnelError> {
+        let mut result = self.0.subscribe();
+        let done = result.wait_for(Option::is_some).await;
+        match done {
+            Ok(r) => r.clone().unwrap_or(Err(TunnelError::Cancelled)),
+            Err(_) => Err(TunnelError::Cancelled),
+        }
+    }
+}
+
+/// A port whose provider is being started outside the lock.
+struct Pending {
+    /// What `list` shows meanwhile: `Starting`, or `Reconnecting` for a renewal.
+    info: TunnelInfo,
+    /// The tunnel this replaces; it is tracked again if the start fails.
+    prev: Option<TunnelInfo>,
+    slot: Arc<StartSlot>,
+}
+
+/// A provider process to start, worked out under the lock and run outside it.
+struct Launch {
+    provider: Arc<dyn TunnelProvider>,
+    forward: Forward,
+    /// The whole routing table, for providers that share one.
+    routes: Option<Vec<Forward>>,
+    /// Where the new process's output goes.
+    log: TunnelLog,
+}
+
+impl Launch {
+    async fn run(&self) -> Result<Routed, TunnelError> {
+        let log = self.log.clone();
+        let launched = match &self.routes {
+            Some(routes) => self.provider.start_routed(routes, log).await,
+            None => {
+                let port = self.forward.port;
+                let started = self.provider.start(self.forward, log).await;
+                started.map(|(child, url)| (child, BTreeMap::from([(port, url)])))
+            }
+        };
+        let (mut child, urls) = launched.map_err(|e| e.with_output(self.log.tail(ERROR_OUTPUT_LINES)))?;
+        if !urls.contains_key(&self.forward.port) {
+            let _ = child.start_kill();
+            return Err(TunnelError::StartFailed(format!(
+                "{} published no url for port {}",
+                self.provider.name(),
+                self.forward.port
+            )));
+        }
+        Ok((child, urls))
+    }
+}
+
+/// A start or renewal that holds the pending slot for its port.
+struct StartJob {
+    port: u16,
+    launch: Launch,
+    /// Refuse to launch when nothing serves the port (`start` without `force`).
+    check_upstream: bool,
+    /// The process of the tunnel being renewed, stopped before the new one
+    /// launches.
+    replaces: Option<TunnelProcess>,
+    /// When a scheduled renewal was due; a failure backs off from there.
+    retry_at: Option<u64>,
+    slot: Arc<StartSlot>,
+}
+
+impl StartJob {
+    async fn run(&mut self) -> Result<Routed, TunnelError> {
+        if let Some(old) = self.replaces.take() {
+            old.stop().await;
+        }
+        if self.check_upstream {
+            let (port, kind) = (self.port, self.launch.provider.kind());
+            task::spawn_blocking(move || check_upstream(port, kind))
+                .await
+                .map_err(|e| TunnelError::StartFailed(e.to_string()))??;
+        }
+        self.launch.run().await
+    }
+}
+
+enum Begin {
+    Done(Result<TunnelInfo, TunnelError>),
+    Wait(Arc<StartSlot>),
+    Run(StartJob),
+}
+
+/// Tracks tunnels by port. Shared as `Arc<Mutex<TunnelManager>>`; the
+/// operations that start provider processes (`start`, `renew`, `retry_due`,
+/// `reroute`) take the mutex and only hold it while the bookkeeping changes,
+/// so a provider that takes 20 seconds to publish a URL blocks nobody else.
+/// Every running process is watched by a task that renews its tunnel as soon
+/// as it exits.
 pub struct TunnelManager {
-    provider: Box<dyn TunnelProvider>,
+    providers: ProviderRegistry,
     active: HashMap<u16, ActiveTunnel>,
+    /// Starts and renewals in flight, one per port.
+    pending: HashMap<u16, Pending>,
+    /// Connectors by provider name, for providers that share routes.
+    connectors: HashMap<&'static str, Connector>,
+    /// Providers whose connector is being relaunched by `reroute`.
+    rerouting: HashSet<&'static str>,
+    /// Proxies that tunnels are pointed at instead of the shared port.
+    fronts: HashMap<u16, u16>,
+    /// Deadlines by port; kept across renewals, dropped by `stop`.
+    expiries: HashMap<u16, Deadline>,
+    /// Tunnels whose process died, `Reconnecting` or `Failed`.
+    retries: HashMap<u16, TunnelInfo>,
+    policy: RetryPolicy,
+    jitter: RandomState,
+    listener: Option<Listener>,
+    logs: HashMap<u16, PortLogs>,
+    log_listener: Option<LogListener>,
 }
 
 impl TunnelManager {
     pub fn new() -> Self {
+        Self::with_providers(ProviderRegistry::with_builtin())
+    }
+
+    pub fn with_providers(providers: ProviderRegistry) -> Self {
         Self {
-            provider: Box::new(CloudflareProvider::default()),
+            providers,
             active: HashMap::new(),
+            pending: HashMap::new(),
+            connectors: HashMap::new(),
+            rerouting: HashSet::new(),
+            fronts: HashMap::new(),
+            expiries: HashMap::new(),
+            retries: HashMap::new(),
+            policy: RetryPolicy::default(),
+            jitter: RandomState::new(),
+            listener: None,
+            logs: HashMap::new(),
+            log_listener: None,
+        }
+    }
+
+    pub fn providers(&self) -> ProviderRegistry {
+        self.providers.clone()
+    }
+
+    /// Runs `listener` on every state change, e.g. to emit a Tauri event.
+    pub fn set_listener(&mut self, listener: impl Fn(&TunnelTransition) + Send + 'static) {
+        self.listener = Some(Box::new(listener));
+    }
+
+    /// Runs `listener` on every line a tunnel's process prints.
+    pub fn set_log_listener(&mut self, listener: impl Fn(&TunnelLogLine) + Send + Sync + 'static) {
+        self.log_listener = Some(Arc::new(listener));
+    }
+
+    /// Output of the tunnel on `port` and of its last failed starts.
+    pub fn logs(&self, port: u16) -> TunnelLogs {
+        let Some(logs) = self.logs.get(&port) else {
+            return TunnelLogs {
+                port,
+                ..TunnelLogs::default()
+            };
+        };
+        TunnelLogs {
+            port,
+            current: logs.current.as_ref().map(TunnelLog::lines).unwrap_or_default(),
+            failed: logs.failed.iter().cloned().collect(),
+        }
+    }
+
+    fn new_log(&self, port: u16) -> TunnelLog {
+        let Some(listener) = self.log_listener.clone() else {
+            return TunnelLog::default();
+        };
+        TunnelLog::new(move |line| {
+            listener(&TunnelLogLine {
+                port,
+                line: line.clone(),
+            })
+        })
+    }
+
+    /// Keeps `log` as the output of a failed attempt on `port`.
+    fn log_failure(&mut self, port: u16, provider: &str, error: String, log: &TunnelLog) {
+        let logs = self.logs.entry(port).or_default();
+        if logs.failed.len() >= MAX_FAILED_ATTEMPTS {
+            logs.failed.pop_back();
+        }
+        logs.failed.push_front(FailedAttempt {
+            provider: provider.to_string(),
+            at_ms: now_ms(),
+            error,
+            lines: log.lines(),
+        });
+    }
+
+    fn notify(&self, info: &TunnelInfo, from: Option<TunnelState>, to: Option<TunnelState>) {
+        if let Some(listener) = &self.listener {
+            listener(&TunnelTransition {
+                port: info.port,
+                provider: info.provider.clone(),
+                from,
+                to,
+                at_ms: now_ms(),
+                restart_count: info.restart_count,
+                error: info.last_error.clone(),
+            });
         }
     }
 
+    /// Moves `info` to `to`, noting `error` if given, and tells the listener.
+    fn set_state(&self, info: &mut TunnelInfo, to: TunnelState, error: Option<String>) {
+        if error.is_some() {
+            info.last_error = error;
+        }
+        if info.state == to {
+            return;
+        }
+        let from = info.state;
+        info.state = to;
+        info.state_changed_at_ms = now_ms();
+        self.notify(info, Some(from), Some(to));
+    }
+
+    /// Adds a provider, replacing any registered under the same name, e.g.
+    /// after its settings changed. Running tunnels keep going.
+    pub fn register_provider(&mut self, provider: impl TunnelProvider + 'static) {
+        self.providers.register(provider);
+    }
+
+    /// Points the tunnel for `port` at the loopback `proxy` in front of it, or
+    /// back at the port itself. Applies from the next start or renewal.
+    pub fn set_front(&mut self, port: u16, proxy: Option<u16>) {
+        match proxy {
+            Some(p) => self.fronts.insert(port, p),
+            None => self.fronts.remove(&port),
+        };
+    }
+
+    /// Closes the tunnel for `port` at `at_ms` (see `take_expired`), or never.
+    pub fn set_expiry(&mut self, port: u16, at_ms: Option<u64>) {
+        match at_ms {
+            Some(at_ms) => {
+                self.expiries.insert(port, Deadline { at_ms, warned: 0 });
+            }
+            None => {
+                self.expiries.remove(&port);
+            }
+        }
+        if let Some(t) = self.active.get_mut(&port) {
+            t.info.expires_at_ms = at_ms;
+        }
+        if let Some(info) = self.retries.get_mut(&port) {
+            info.expires_at_ms = at_ms;
+        }
+        if let Some(p) = self.pending.get_mut(&port) {
+            p.info.expires_at_ms = at_ms;
+        }
+    }
+
+    fn expires_at(&self, port: u16) -> Option<u64> {
+        self.expiries.get(&port).map(|e| e.at_ms)
+    }
+
+    /// Deadlines that passed. They are forgotten here; closing the tunnels
+    /// is up to the caller.
+    pub fn take_expired(&mut self, now_ms: u64) -> Vec<TunnelExpiry> {
+        let expired: Vec<TunnelExpiry> = self
+            .expiries
+            .iter()
+            .filter(|(_, e)| e.at_ms <= now_ms)
+            .map(|(port, e)| TunnelExpiry {
+                port: *port,
+                expires_at_ms: e.at_ms,
+            })
+            .collect();
+        for e in &expired {
+            self.expiries.remove(&e.port);
+        }
+        expired
+    }
+
+    /// Tunnels that just crossed one of the warning thresholds. Each
+    /// threshold fires once per deadline.
+    pub fn take_expiry_warnings(&mut self, now_ms: u64) -> Vec<TunnelExpiry> {
+        let mut due = vec![];
+        for (port, e) in &mut self.expiries {
+            let left = e.at_ms.saturating_sub(now_ms);
+            let crossed = EXPIRY_WARNINGS_MS.iter().filter(|w| left <= **w).count();
+            if crossed > e.warned {
+                e.warned = crossed;
+                due.push(TunnelExpiry {
+                    port: *port,
+                    expires_at_ms: e.at_ms,
+                });
+            }
+        }
+        due
+    }
+
+    fn forward(&self, port: u16) -> Forward {
+        Forward {
+            port,
+            target: self.fronts.get(&port).copied().unwrap_or(port),
+        }
+    }
+
+    fn provider(&self, name: &str) -> Result<Arc<dyn TunnelProvider>, TunnelError> {
+        self.providers
+            .get(name)
+            .ok_or_else(|| TunnelError::UnknownProvider(name.to_string()))
+    }
+
+    /// Running tunnels, the ones starting, and the ones being renewed or
+    /// given up on.
     pub fn list(&self) -> Vec<TunnelInfo> {
-        let mut v: Vec<TunnelInfo> = self.active.values().map(|t| t.info.clone()).collect();
+        let mut v: Vec<TunnelInfo> = self
+            .active
+            .values()
+            .map(|t| t.info.clone())
+            .chain(self.pending.values().map(|p| p.info.clone()))
+            .chain(self.retries.values().cloned())
+            .collect();
         v.sort_by_key(|x| x.port);
         v
     }
 
-    pub fn start(&mut self, port: u16) -> Result<TunnelInfo, TunnelError> {
-        if self.active.contains_key(&port) {
-            return Ok(self.active.get(&port).unwrap().info.clone());
+    /// The tunnel an open request for `port` finds in place: one that is
+    /// running, even with nothing listening upstream, or one still starting.
+    /// A reconnecting or failed tunnel is started afresh instead.
+    pub fn existing(&self, port: u16) -> Option<TunnelInfo> {
+        self.list().into_iter().find(|t| {
+            t.port == port && matches!(t.state, TunnelState::Up | TunnelState::Degraded | TunnelState::Starting)
+        })
+    }
+
+    /// Starts sharing `port` with `provider` (the default when `None`). Unless
+    /// `force` is set, the port must be served on loopback first. A call for a
+    /// port that is already starting waits for that start instead.
+    pub async fn start(
+        tunnels: &Arc<Mutex<Self>>,
+        port: u16,
+        provider: Option<&str>,
+        force: bool,
+    ) -> Result<TunnelInfo, TunnelError> {
+        let begin = lock(tunnels).begin_start(port, provider, force);
+        Self::complete(tunnels, begin).await
+    }
+
+    /// Restarts the tunnel on `port`. Keeps the running tunnel's provider unless
+    /// one is given. Also takes a `Failed` tunnel back to renewing, with its
+    /// attempts reset.
+    pub async fn renew(
+        tunnels: &Arc<Mutex<Self>>,
+        port: u16,
+        provider: Option<&str>,
+    ) -> Result<TunnelInfo, TunnelError> {
+        let begin = lock(tunnels).begin_renew(port, provider);
+        Self::complete(tunnels, begin).await
+    }
+
+    async fn complete(tunnels: &Arc<Mutex<Self>>, begin: Begin) -> Result<TunnelInfo, TunnelError> {
+        let slot = match begin {
+            Begin::Done(res) => return res,
+            Begin::Wait(slot) => slot,
+            Begin::Run(job) => {
+                let slot = job.slot.clone();
+                // On its own task, so the port is settled even if the caller
+                // goes away.
+                tokio::spawn(Self::run_job(tunnels.clone(), job));
+                slot
+            }
+        };
+        slot.wait().await
+    }
+
+    async fn run_job(tunnels: Arc<Mutex<Self>>, mut job: StartJob) -> Result<TunnelInfo, TunnelError> {
+        let routed = job.launch.routes.is_some();
+        let launched = job.run().await;
+        let res = lock(&tunnels).finish(&tunnels, job, launched);
+        if routed {
+            Self::reroute(&tunnels).await;
         }
-        let (child, url) = self.provider.start(port)?;
-        let info = self.provider.build_info(port, url);
-        self.active.insert(
+        res
+    }
+
+    fn begin_start(&mut self, port: u16, provider: Option<&str>, force: bool) -> Begin {
+        if let Some(t) = self.active.get(&port) {
+            return Begin::Done(Ok(t.info.clone()));
+        }
+        if let Some(p) = self.pending.get(&port) {
+            return Begin::Wait(p.slot.clone());
+        }
+        let provider = match self.provider(provider.unwrap_or(DEFAULT_PROVIDER)) {
+            Ok(p) => p,
+            Err(e) => return Begin::Done(Err(e)),
+        };
+        // A reconnecting or failed tunnel started afresh keeps its history.
+        let prev = self.retries.remove(&port);
+        let mut info = provider.build_info(port, String::new());
+        info.state = TunnelState::Starting;
+        info.expires_at_ms = self.expires_at(port);
+        self.notify(&info, prev.as_ref().map(|p| p.state), Some(TunnelState::Starting));
+        Begin::Run(self.reserve(provider, info, prev, !force))
+    }
+
+    fn begin_renew(&mut self, port: u16, provider: Option<&str>) -> Begin {
+        if let Some(p) = self.pending.get(&port) {
+            return Begin::Wait(p.slot.clone());
+        }
+        let name = provider
+            .map(|p| p.to_string())
+            .or_else(|| self.active.get(&port).map(|t| t.info.provider.clone()))
+            .or_else(|| self.retries.get(&port).map(|t| t.provider.clone()))
+            .unwrap_or_else(|| DEFAULT_PROVIDER.to_string());
+        let provider = match self.provider(&name) {
+            Ok(p) => p,
+            Err(e) => return Begin::Done(Err(e)),
+        };
+        let mut replaces = None;
+        let prev = match self.active.remove(&port) {
+            // Not `stop`: the set of shared ports stays the same, and a
+            // connector keeps serving the others until its replacement is up.
+            Some(t) => {
+                replaces = t.process;
+                Some(t.info)
+            }
+            None => self.retries.remove(&port),
+        };
+        let (info, prev) = match prev {
+            Some(mut info) => {
+                self.set_state(&mut info, TunnelState::Reconnecting, None);
+                info.retry_attempts = 0;
+                info.next_retry_at_ms = Some(now_ms());
+                (info.clone(), Some(info))
+            }
+            None => {
+                let mut info = provider.build_info(port, String::new());
+                info.state = TunnelState::Starting;
+                info.expires_at_ms = self.expires_at(port);
+                self.notify(&info, None, Some(TunnelState::Starting));
+                (info, None)
+            }
+        };
+        let mut job = self.reserve(provider, info, prev, false);
+        job.replaces = replaces;
+        Begin::Run(job)
+    }
+
+    /// Takes the pending slot for `info.port` and plans the launch.
+    fn reserve(
+        &mut self,
+        provider: Arc<dyn TunnelProvider>,
+        info: TunnelInfo,
+        prev: Option<TunnelInfo>,
+        check_upstream: bool,
+    ) -> StartJob {
+        let port = info.port;
+        let slot = Arc::new(StartSlot::default());
+        self.pending.insert(
             port,
-            ActiveTunnel {
-                child,
-                info: info.clone(),
+            Pending {
+                info,
+                prev,
+                slot: slot.clone(),
             },
         );
-        Ok(info)
+        let launch = self.plan(provider, port);
+        self.logs.entry(port).or_default().current = Some(launch.log.clone());
+        StartJob {
+            port,
+            launch,
+            check_upstream,
+            replaces: None,
+            retry_at: None,
+            slot,
+        }
+    }
+
+    /// Records how `job` went and wakes everyone waiting on its port. If the
+    /// tunnel was closed meanwhile, the new process is stopped again.
+    fn finish(
+        &mut self,
+        tunnels: &Arc<Mutex<Self>>,
+        job: StartJob,
+        launched: Result<Routed, TunnelError>,
+    ) -> Result<TunnelInfo, TunnelError> {
+        let port = job.port;
+        let provider = job.launch.provider.as_ref();
+        let res = match self.pending.remove(&port) {
+            Some(p) if Arc::ptr_eq(&p.slot, &job.slot) => match launched {
+                Ok((child, mut urls)) => {
+                    let url = urls.remove(&port).unwrap_or_default();
+                    let mut info = provider.build_info(port, url);
+                    info.state = p.info.state;
+                    info.expires_at_ms = self.expires_at(port);
+                    let attempts = p.prev.as_ref().map_or(0, |prev| prev.retry_attempts);
+                    if let Some(prev) = p.prev {
+                        info.started_at_ms = prev.started_at_ms;
+                        info.last_renewed_at_ms = now_ms();
+                        info.restart_count = prev.restart_count + 1;
+                        info.last_error = prev.last_error;
+                    }
+                    self.set_state(&mut info, TunnelState::Up, None);
+                    let process = TunnelProcess::supervise(child);
+                    let process = match job.launch.routes {
+                        Some(routes) => {
+                            let provider = job.launch.provider.clone();
+                            self.connect(tunnels, provider, routes, process, urls, &job.launch.log);
+                            None
+                        }
+                        None => {
+                            Self::watch_exit(tunnels, port, &process);
+                            Some(process)
+                        }
+                    };
+                    self.active.insert(
+                        port,
+                        ActiveTunnel {
+                            process,
+                            info: info.clone(),
+                            up_since_ms: now_ms(),
+                            attempts,
+                        },
+                    );
+                    Ok(info)
+                }
+                Err(e) => {
+                    self.log_failure(port, provider.name(), e.to_string(), &job.launch.log);
+                    if let Some(logs) = self.logs.get_mut(&port) {
+                        logs.current = None;
+                    }
+                    match p.prev {
+                        // Stays tracked, so a failed renewal never makes a tunnel vanish.
+                        Some(mut prev) => {
+                            prev.last_error = Some(e.to_string());
+                            if prev.state != p.info.state {
+                                self.notify(&prev, Some(p.info.state), Some(prev.state));
+                            }
+                            self.retries.insert(port, prev);
+                            if let Some(due) = job.retry_at {
+                                self.back_off(port, due);
+                            }
+                        }
+                        None => {
+                            let mut info = p.info;
+                            info.last_error = Some(e.to_string());
+                            self.notify(&info, Some(info.state), None);
+                        }
+                    }
+                    Err(e)
+                }
+            },
+            other => {
+                // Closed while starting; a newer start may hold the slot now.
+                if let Some(p) = other {
+                    self.pending.insert(port, p);
+                }
+                if let Ok((mut child, _)) = launched {
+                    let _ = child.start_kill();
+                }
+                Err(TunnelError::Cancelled)
+            }
+        };
+        job.slot.fill(&res);
+        res
+    }
+
+    /// Makes `process` the connector for `provider`, serving `routes`, and
+    /// stops the one it replaces. The running ports it serves get their URL
+    /// from `urls` and its output as their log.
+    fn connect(
+        &mut self,
+        tunnels: &Arc<Mutex<Self>>,
+        provider: Arc<dyn TunnelProvider>,
+        routes: Vec<Forward>,
+        process: TunnelProcess,
+        urls: BTreeMap<u16, String>,
+        log: &TunnelLog,
+    ) {
+        let name = provider.name();
+        Self::watch_connector(tunnels, name, &process);
+        let now = now_ms();
+        for (port, url) in urls {
+            let served = self.active.get_mut(&port).filter(|t| t.process.is_none() && t.info.provider == name);
+            let Some(t) = served else {
+                continue;
+            };
+            t.info.url = url;
+            t.info.last_renewed_at_ms = now;
+            t.up_since_ms = now;
+            self.logs.entry(port).or_default().current = Some(log.clone());
+        }
+        let connector = Connector {
+            process,
+            routes,
+            launched_by: provider,
+        };
+        if let Some(old) = self.connectors.insert(name, connector) {
+            old.process.kill();
+        }
     }
 
+    /// Closes the tunnel on `port`, including one that is starting or being
+    /// renewed. Other tunnels of a provider that shares routes pick up the
+    /// change with the next `reroute`; the last one takes the connector down.
     pub fn stop(&mut self, port: u16) -> Result<(), TunnelError> {
-        let mut t = self.active.remove(&port).ok_or(TunnelError::NotRunning)?;
-        self.provider.stop(&mut t.child)?;
+        self.expiries.remove(&port);
+        self.logs.remove(&port);
+        let retrying = self.retries.remove(&port);
+        if let Some(info) = &retrying {
+            self.notify(info, Some(info.state), None);
+        }
+        let starting = self.pending.remove(&port);
+        if let Some(p) = &starting {
+            self.notify(&p.info, Some(p.info.state), None);
+        }
+        let Some(t) = self.active.remove(&port) else {
+            return if retrying.is_some() || starting.is_some() {
+                Ok(())
+            } else {
+                Err(TunnelError::NotRunning)
+            };
+        };
+        self.notify(&t.info, Some(t.info.state), None);
+        match t.process {
+            Some(process) => process.kill(),
+            None if self.served(&t.info.provider).is_empty() => {
+                if let Some(c) = self.connectors.remove(t.info.provider.as_str()) {
+                    c.process.kill();
+                }
+            }
+            None => {}
+        }
         Ok(())
     }
 
-    pub fn renew(&mut self, port: u16) -> Result<TunnelInfo, TunnelError> {
-        if self.active.contains_key(&port) {
-            let _ = self.stop(port);
+    /// Notices as soon as `process` exits on its own and renews the tunnel on
+    /// `port`, right away or per the `RetryPolicy`; see `on_exit`.
+    fn watch_exit(tunnels: &Arc<Mutex<Self>>, port: u16, process: &TunnelProcess) {
+        Self::when_exited(tunnels, process, move |mgr, id, reason, now| mgr.on_exit(port, id, reason, now));
+    }
+
+    /// Like `watch_exit`, for the connector of `provider` and every port it
+    /// serves; see `on_connector_exit`.
+    fn watch_connector(tunnels: &Arc<Mutex<Self>>, provider: &'static str, process: &TunnelProcess) {
+        Self::when_exited(tunnels, process, move |mgr, id, reason, now| {
+            mgr.on_connector_exit(provider, id, reason, now)
+        });
+    }
+
+    /// Runs `handle` once `process` exits, then the renewals it made due.
+    fn when_exited(
+        tunnels: &Arc<Mutex<Self>>,
+        process: &TunnelProcess,
+        handle: impl FnOnce(&mut Self, u64, &str, u64) -> bool + Send + 'static,
+    ) {
+        let tunnels = tunnels.clone();
+        let (id, exited) = (process.id(), process.exited());
+        tokio::spawn(async move {
+            let reason = exited.await;
+            let now = now_ms();
+            let due = handle(&mut lock(&tunnels), id, &reason, now);
+            if due {
+                Self::retry_due(&tunnels, now).await;
+            }
+        });
+    }
+
+    /// Moves the tunnel on `port` to `Reconnecting` if process `id` still
+    /// serves it; stopped and replaced processes do not. Returns whether its
+    /// renewal is due right away, see `lose`.
+    fn on_exit(&mut self, port: u16, id: u64, reason: &str, now_ms: u64) -> bool {
+        let process = self.active.get(&port).and_then(|t| t.process.as_ref());
+        if process.map(|p| p.id()) != Some(id) {
+            return false;
+        }
+        self.lose(port, reason, now_ms)
+    }
+
+    /// Moves every port the connector of `provider` served to `Reconnecting`
+    /// if process `id` is still that connector.
+    fn on_connector_exit(&mut self, provider: &str, id: u64, reason: &str, now_ms: u64) -> bool {
+        if self.connectors.get(provider).map(|c| c.process.id()) != Some(id) {
+            return false;
+        }
+        self.connectors.remove(provider);
+        let mut due = false;
+        for f in self.served(provider) {
+            due |= self.lose(f.port, reason, now_ms);
+        }
+        due
+    }
+
+    /// Moves the running tunnel on `port`, whose process exited, to
+    /// `Reconnecting`. One that ran for `stable_ms` is due for renewal right
+    /// away, which is what this returns. One that died sooner counts as a
+    /// failed renewal and backs off.
+    fn lose(&mut self, port: u16, reason: &str, now_ms: u64) -> bool {
+        let Some(t) = self.active.remove(&port) else {
+            return false;
+        };
+        let mut info = t.info;
+        let exit = format!("{} exited ({reason})", info.provider);
+        if let Some(log) = self.logs.get_mut(&port).and_then(|l| l.current.take()) {
+            self.log_failure(port, &info.provider, exit.clone(), &log);
+        }
+        self.set_state(&mut info, TunnelState::Reconnecting, Some(exit));
+        if now_ms.saturating_sub(t.up_since_ms) >= self.policy.stable_ms {
+            info.retry_attempts = 0;
+            info.next_retry_at_ms = Some(now_ms);
+            self.retries.insert(port, info);
+            true
+        } else {
+            info.retry_attempts = t.attempts;
+            self.retries.insert(port, info);
+            self.back_off(port, now_ms);
+            false
+        }
+    }
+
+    fn plan(&self, provider: Arc<dyn TunnelProvider>, port: u16) -> Launch {
+        let forward = self.forward(port);
+        let routes = provider
+            .shares_routes()
+            .then(|| self.routes_for(provider.name(), port));
+        Launch {
+            provider,
+            forward,
+            routes,
+            log: self.new_log(port),
+        }
+    }
+
+    /// The routing table `provider`'s connector should serve once `port` runs
+    /// too: every port it shares.
+    fn routes_for(&self, provider: &str, port: u16) -> Vec<Forward> {
+        let mut routes: Vec<Forward> = self
+            .served(provider)
+            .into_iter()
+            .filter(|f| f.port != port)
+            .chain([self.forward(port)])
+            .collect();
+        routes.sort_unstable_by_key(|f| f.port);
+        routes
+    }
+
+    /// The running ports that `provider`'s connector serves, by port.
+    fn served(&self, provider: &str) -> Vec<Forward> {
+        let mut routes: Vec<Forward> = self
+            .active
+            .values()
+            .filter(|t| t.process.is_none() && t.info.provider == provider)
+            .map(|t| self.forward(t.info.port))
+            .collect();
+        routes.sort_unstable_by_key(|f| f.port);
+        routes
+    }
+
+    /// Restarts connectors whose routing table is out of date because a port
+    /// of their provider was added or removed, or the provider was registered
+    /// again with new settings (e.g. a cloudflared hostname). Each replacement
+    /// comes up before the old process is stopped; if it fails, the old one
+    /// keeps serving the previous routes and the next call tries again.
+    pub async fn reroute(tunnels: &Arc<Mutex<Self>>) {
+        let jobs: Vec<(u64, Launch)> = {
+            let mut mgr = lock(tunnels);
+            // A port starting or renewing reroutes once it is done.
+            let settled: Vec<&'static str> = mgr
+                .connectors
+                .keys()
+                .filter(|name| !mgr.pending.values().any(|p| p.info.provider == **name))
+                .copied()
+                .collect();
+            // Serving nothing any more, e.g. after the provider stopped
+            // sharing routes and its ports were renewed on their own.
+            for name in &settled {
+                if mgr.served(name).is_empty() {
+                    if let Some(c) = mgr.connectors.remove(name) {
+                        c.process.kill();
+                    }
+                }
+            }
+            let stale: Vec<(u64, Arc<dyn TunnelProvider>, Vec<Forward>)> = settled
+                .into_iter()
+                .filter(|name| !mgr.rerouting.contains(name))
+                .filter_map(|name| {
+                    let c = mgr.connectors.get(name)?;
+                    let provider = mgr.provider(name).ok().filter(|p| p.shares_routes())?;
+                    let routes = mgr.served(name);
+                    let replaced = !Arc::ptr_eq(&c.launched_by, &provider);
+                    (replaced || c.routes != routes).then_some((c.process.id(), provider, routes))
+                })
+                .collect();
+            stale
+                .into_iter()
+                .map(|(id, provider, routes)| {
+                    mgr.rerouting.insert(provider.name());
+                    let forward = routes[0];
+                    let log = mgr.new_log(forward.port);
+                    let launch = Launch {
+                        provider,
+                        forward,
+                        routes: Some(routes),
+                        log,
+                    };
+                    (id, launch)
+                })
+                .collect()
+        };
+        if jobs.is_empty() {
+            return;
+        }
+        let mut launches = JoinSet::new();
+        for (id, launch) in jobs {
+            launches.spawn(async move {
+                let res = launch.run().await;
+                (id, launch, res)
+            });
+        }
+
+        while let Some(launched) = launches.join_next().await {
+            let Ok((id, launch, res)) = launched else {
+                continue;
+            };
+            let mut mgr = lock(tunnels);
+            let name = launch.provider.name();
+            mgr.rerouting.remove(name);
+            let (child, urls) = match res {
+                Ok(launched) => launched,
+                Err(e) => {
+                    mgr.log_failure(launch.forward.port, name, e.to_string(), &launch.log);
+                    continue;
+                }
+            };
+            let process = TunnelProcess::supervise(child);
+            match mgr.connectors.get(name) {
+                Some(c) if c.process.id() == id => {
+                    let routes = launch.routes.unwrap_or_default();
+                    mgr.connect(tunnels, launch.provider, routes, process, urls, &launch.log);
+                }
+                // Gone, or replaced by a start meanwhile.
+                _ => process.kill(),
+            }
+        }
+    }
+
+    /// Renews every `Reconnecting` tunnel whose retry is due, all at once and
+    /// outside the lock. A failure schedules the next try per the
+    /// `RetryPolicy`, or marks the tunnel `Failed` once attempts run out.
+    pub async fn retry_due(
+        tunnels: &Arc<Mutex<Self>>,
+        now_ms: u64,
+    ) -> Vec<(u16, Result<TunnelInfo, TunnelError>)> {
+        let mut results = vec![];
+        let jobs: Vec<StartJob> = {
+            let mut mgr = lock(tunnels);
+            let due: Vec<u16> = mgr
+                .retries
+                .values()
+                .filter(|t| t.state == TunnelState::Reconnecting)
+                .filter(|t| t.next_retry_at_ms.is_some_and(|at| at <= now_ms))
+                .map(|t| t.port)
+                .collect();
+            let mut jobs = vec![];
+            for port in due {
+                let Some(mut prev) = mgr.retries.remove(&port) else {
+                    continue;
+                };
+                match mgr.provider(&prev.provider) {
+                    Ok(p) => {
+                        let mut job = mgr.reserve(p, prev.clone(), Some(prev), false);
+                        job.retry_at = Some(now_ms);
+                        jobs.push(job);
+                    }
+                    Err(e) => {
+                        prev.last_error = Some(e.to_string());
+                        mgr.retries.insert(port, prev);
+                        mgr.back_off(port, now_ms);
+                        results.push((port, Err(e)));
+                    }
+                }
+            }
+            jobs
+        };
+
+        let mut runs = JoinSet::new();
+        for job in jobs {
+            let (port, tunnels) = (job.port, tunnels.clone());
+            runs.spawn(async move { (port, Self::run_job(tunnels, job).await) });
+        }
+        while let Some(run) = runs.join_next().await {
+            if let Ok(res) = run {
+                results.push(res);
+            }
+        }
+        results
+    }
+
+    /// Counts a failed renewal of the tunnel in `retries` and schedules the
+    /// next one, or gives up.
+    fn back_off(&mut self, port: u16, now_ms: u64) {
+        let Some(mut t) = self.retries.remove(&port) else {
+            return;
+        };
+        t.retry_attempts += 1;
+        if t.retry_attempts >= self.policy.max_attempts {
+            t.next_retry_at_ms = None;
+            self.set_state(&mut t, TunnelState::Failed, None);
+        } else {
+            let jitter = self.jitter.hash_one((port, t.retry_attempts));
+            let delay = self.policy.delay_ms(t.retry_attempts, jitter);
+            t.next_retry_at_ms = Some(now_ms + delay);
+        }
+        self.retries.insert(port, t);
+    }
+
+    /// Marks running tunnels `Degraded` while nothing listens on their shared
+    /// port, and `Up` again once something does. Probes outside the lock.
+    pub async fn check_upstreams(tunnels: &Arc<Mutex<Self>>) {
+        let ports: Vec<u16> = lock(tunnels).active.keys().copied().collect();
+        let probed = task::spawn_blocking(move || {
+            ports
+                .into_iter()
+                .map(|port| (port, check_upstream(port, TunnelKind::Tcp).map_err(|e| e.to_string())))
+                .collect::<Vec<_>>()
+        })
+        .await
+        .unwrap_or_default();
+
+        let mut mgr = lock(tunnels);
+        for (port, listening) in probed {
+            let Some(mut info) = mgr.active.get(&port).map(|t| t.info.clone()) else {
+                continue;
+            };
+            match (info.state, listening) {
+                (TunnelState::Up, Err(e)) => mgr.set_state(&mut info, TunnelState::Degraded, Some(e)),
+                (TunnelState::Degraded, Ok(())) => mgr.set_state(&mut info, TunnelState::Up, None),
+                _ => continue,
+            }
+            if let Some(t) = mgr.active.get_mut(&port) {
+                t.info = info;
+            }
+        }
+    }
+}
+
+fn lock(tunnels: &Mutex<TunnelManager>) -> MutexGuard<'_, TunnelManager> {
+    tunnels.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use tokio::{process::Command, sync::Notify};
+
+    use super::*;
+    use crate::tunnel::{
+        process::{drain, spawn_with_events},
+        testing::FakeProvider,
+        TunnelKind,
+    };
+
+    #[test]
+    fn warns_once_per_threshold_then_expires() {
+        let mut mgr = TunnelManager::with_providers(ProviderRegistry::new());
+        let at = 10 * 60 * 1000;
+        mgr.set_expiry(3000, Some(at));
+
+        assert!(mgr.take_expiry_warnings(0).is_empty());
+        let warning = TunnelExpiry { port: 3000, expires_at_ms: at };
+        assert_eq!(mgr.take_expiry_warnings(at - 4 * 60 * 1000), vec![warning.clone()]);
+        assert!(mgr.take_expiry_warnings(at - 3 * 60 * 1000).is_empty());
+        assert_eq!(mgr.take_expiry_warnings(at - 30 * 1000), vec![warning.clone()]);
+
+        assert!(mgr.take_expired(at - 1).is_empty());
+        assert_eq!(mgr.take_expired(at), vec![warning]);
+        assert!(mgr.take_expired(at + 1).is_empty());
+        assert!(mgr.take_expiry_warnings(at + 1).is_empty());
+
+        assert_eq!(ExpirySpec::TtlSecs(60).resolve(1000), Ok(61_000));
+        assert!(ExpirySpec::AtMs(5).resolve(10).is_err());
+    }
+
+    #[test]
+    fn backoff_doubles_with_jitter_and_caps() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay_ms(1, 0), 1_000);
+        assert_eq!(policy.delay_ms(1, 1_000), 2_000);
+        assert_eq!(policy.delay_ms(3, 0), 4_000);
+        assert!(policy.delay_ms(40, u64::MAX) <= policy.max_ms);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let mut mgr = TunnelManager::with_providers(ProviderRegistry::new());
+        let mut info = TunnelInfo::new(3000, "gone", "https://x".to_string(), TunnelKind::Http);
+        info.state = TunnelState::Reconnecting;
+        info.next_retry_at_ms = Some(0);
+        mgr.retries.insert(3000, info);
+        let max_attempts = mgr.policy.max_attempts;
+        let mgr = Arc::new(Mutex::new(mgr));
+        let list = || mgr.lock().unwrap().list();
+
+        let mut now = 0;
+        for attempt in 1..=max_attempts {
+            let results = TunnelManager::retry_due(&mgr, now).await;
+            assert_eq!(results.len(), 1);
+            let t = &list()[0];
+            assert_eq!(t.retry_attempts, attempt);
+            assert_eq!(t.last_error.as_deref(), Some("unknown tunnel provider: gone"));
+            match t.next_retry_at_ms {
+                Some(at) => {
+                    assert!(at > now);
+                    assert!(TunnelManager::retry_due(&mgr, at - 1).await.is_empty());
+                    now = at;
+                }
+                None => assert_eq!(attempt, max_attempts),
+            }
         }
-        let (child, url) = self.provider.start(port)?;
-        let mut info = self.provider.build_info(port, url);
-        info.last_renewed_at_ms = now_ms();
-        self.active.insert(
+        assert_eq!(list()[0].state, TunnelState::Failed);
+        assert!(TunnelManager::retry_due(&mgr, u64::MAX).await.is_empty());
+        assert!(mgr.lock().unwrap().stop(3000).is_ok());
+        assert!(list().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn reports_every_transition() {
+        let seen = Arc::new(std::sync::Mutex::new(vec![]));
+        let mut mgr = TunnelManager::with_providers(ProviderRegistry::new());
+        let log = seen.clone();
+        mgr.set_listener(move |t| log.lock().unwrap().push((t.from, t.to)));
+        // The provider is unknown, so renewal fails; one attempt is allowed.
+        mgr.policy.max_attempts = 1;
+        let mgr = Arc::new(Mutex::new(mgr));
+        let state = || mgr.lock().unwrap().list()[0].state;
+
+        let child = Command::new("sleep").arg("30").kill_on_drop(true).spawn().unwrap();
+        let pid = child.id().unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let info = TunnelInfo::new(port, "gone", "https://x".to_string(), TunnelKind::Http);
+        let process = TunnelProcess::supervise(child);
+        TunnelManager::watch_exit(&mgr, port, &process);
+        mgr.lock().unwrap().active.insert(
             port,
             ActiveTunnel {
-                child,
-                info: info.clone(),
+                process: Some(process),
+                info,
+                up_since_ms: 0,
+                attempts: 0,
             },
         );
-        Ok(info)
+
+        TunnelManager::check_upstreams(&mgr).await;
+        drop(listener);
+        TunnelManager::check_upstreams(&mgr).await;
+        assert_eq!(state(), TunnelState::Degraded);
+        assert!(mgr.lock().unwrap().list()[0].last_error.is_some());
+
+        // Noticed without anyone polling.
+        std::process::Command::new("kill").arg(pid.to_string()).status().unwrap();
+        for _ in 0..500 {
+            if state() == TunnelState::Failed {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let last_error = mgr.lock().unwrap().list()[0].last_error.clone();
+        assert!(last_error.unwrap().starts_with("unknown tunnel provider"));
+        mgr.lock().unwrap().stop(port).unwrap();
+
+        use TunnelState::*;
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                (Some(Up), Some(Degraded)),
+                (Some(Degraded), Some(Reconnecting)),
+                (Some(Reconnecting), Some(Failed)),
+                (Some(Failed), None),
+            ]
+        );
     }
 
-    pub fn take_dead_ports(&mut self) -> Vec<u16> {
-        let mut dead = vec![];
-        let mut still = HashMap::new();
-        for (port, mut t) in self.active.drain() {
-            match t.child.try_wait() {
-                Ok(Some(_)) => dead.push(port),
-                Ok(None) => {
-                    still.insert(port, t);
-                }
-                Err(_) => dead.push(port),
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn starts_outside_the_lock_once_per_port() {
+        let starts = Arc::new(AtomicUsize::new(0));
+        let release = Arc::new(Notify::new());
+        let mut providers = ProviderRegistry::new();
+        let (counter, gate) = (starts.clone(), release.clone());
+        // Publishes `https://slow.example` once the test calls `release`.
+        providers.register(FakeProvider::new("slow", move |_, _| {
+            let (starts, release) = (counter.clone(), gate.clone());
+            Box::pin(async move {
+                starts.fetch_add(1, Ordering::SeqCst);
+                release.notified().await;
+                let child = Command::new("sleep").arg("30").kill_on_drop(true).spawn().unwrap();
+                Ok((child, "https://slow.example".to_string()))
+            })
+        }));
+        let mgr = Arc::new(Mutex::new(TunnelManager::with_providers(providers)));
+
+        let callers: Vec<_> = (0..2)
+            .map(|_| {
+                let mgr = mgr.clone();
+                tokio::spawn(async move { TunnelManager::start(&mgr, 4000, Some("slow"), true).await })
+            })
+            .collect();
+        while starts.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+        // The provider is still starting, yet the manager answers.
+        let listed = mgr.lock().unwrap().list();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].state, TunnelState::Starting);
+
+        release.notify_one();
+        for caller in callers {
+            assert_eq!(caller.await.unwrap().unwrap().url, "https://slow.example");
+        }
+        assert_eq!(starts.load(Ordering::SeqCst), 1);
+        assert_eq!(mgr.lock().unwrap().list()[0].state, TunnelState::Up);
+        mgr.lock().unwrap().stop(4000).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn opening_a_degraded_port_keeps_its_tunnel() {
+        let starts = Arc::new(AtomicUsize::new(0));
+        let mut providers = ProviderRegistry::new();
+        let counter = starts.clone();
+        providers.register(FakeProvider::new("steady", move |_, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async {
+                let child = Command::new("sleep").arg("30").kill_on_drop(true).spawn().unwrap();
+                Ok((child, "https://steady.example".to_string()))
+            })
+        }));
+        let mgr = Arc::new(Mutex::new(TunnelManager::with_providers(providers)));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        TunnelManager::start(&mgr, port, Some("steady"), true).await.unwrap();
+        drop(listener);
+        TunnelManager::check_upstreams(&mgr).await;
+
+        for _ in 0..2 {
+            let existing = mgr.lock().unwrap().existing(port).unwrap();
+            assert_eq!(existing.state, TunnelState::Degraded);
+            assert_eq!(existing.url, "https://steady.example");
+            let info = TunnelManager::start(&mgr, port, Some("steady"), false).await.unwrap();
+            assert_eq!(info.url, existing.url);
+        }
+        assert_eq!(starts.load(Ordering::SeqCst), 1);
+        assert_eq!(mgr.lock().unwrap().list()[0].state, TunnelState::Degraded);
+        mgr.lock().unwrap().stop(port).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn keeps_output_of_failed_starts() {
+        let mut providers = ProviderRegistry::new();
+        // Prints a few lines and exits before publishing a URL.
+        providers.register(FakeProvider::new("crashing", |_, log| {
+            Box::pin(async move {
+                let mut cmd = Command::new("sh");
+                cmd.args(["-c", "echo 'connecting to edge'; echo 'ERR invalid token' >&2; exit 1"]);
+                let (mut child, mut rx) = spawn_with_events(&mut cmd, &log, |_| None::<()>).unwrap();
+                child.wait().await.unwrap();
+                drain(&mut rx).await;
+                Err(TunnelError::UrlTimeout(vec![]))
+            })
+        }));
+        let mut mgr = TunnelManager::with_providers(providers);
+        let streamed = Arc::new(Mutex::new(vec![]));
+        let log = streamed.clone();
+        mgr.set_log_listener(move |l| log.lock().unwrap().push((l.port, l.line.text.clone())));
+        let mgr = Arc::new(Mutex::new(mgr));
+
+        for _ in 0..MAX_FAILED_ATTEMPTS + 1 {
+            let err = TunnelManager::start(&mgr, 4100, Some("crashing"), true).await.unwrap_err();
+            let err = err.to_string();
+            assert!(err.starts_with("tunnel url not detected in time"), "{err}");
+            assert!(err.contains("ERR invalid token"), "{err}");
+        }
+
+        let logs = mgr.lock().unwrap().logs(4100);
+        assert!(logs.current.is_empty());
+        assert_eq!(logs.failed.len(), MAX_FAILED_ATTEMPTS);
+        assert_eq!(logs.failed[0].provider, "crashing");
+        assert_eq!(logs.failed[0].lines.len(), 2);
+        assert_eq!(streamed.lock().unwrap().len(), 2 * (MAX_FAILED_ATTEMPTS + 1));
+        assert!(streamed.lock().unwrap().iter().all(|(port, _)| *port == 4100));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn backs_off_when_a_tunnel_dies_right_after_publishing() {
+        let starts = Arc::new(AtomicUsize::new(0));
+        let counter = starts.clone();
+        let mut providers = ProviderRegistry::new();
+        // Publishes a URL, then exits at once, like an agent whose token the
+        // server rejects after the handshake.
+        providers.register(FakeProvider::new("flaky", move |_, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                let child = Command::new("true").spawn().unwrap();
+                Ok((child, "https://flaky.example".to_string()))
+            })
+        }));
+        let mut mgr = TunnelManager::with_providers(providers);
+        mgr.policy = RetryPolicy {
+            base_ms: 20,
+            max_ms: 20,
+            max_attempts: 3,
+            stable_ms: 60_000,
+        };
+        let mgr = Arc::new(Mutex::new(mgr));
+        let tunnel = || mgr.lock().unwrap().list()[0].clone();
+
+        TunnelManager::start(&mgr, 4200, Some("flaky"), true).await.unwrap();
+        while tunnel().state != TunnelState::Reconnecting {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        // Scheduled, not restarted on the spot.
+        assert_eq!(tunnel().retry_attempts, 1);
+        assert!(tunnel().next_retry_at_ms.is_some_and(|at| at > tunnel().state_changed_at_ms));
+        assert_eq!(starts.load(Ordering::SeqCst), 1);
+
+        for _ in 0..500 {
+            if tunnel().state == TunnelState::Failed {
+                break;
             }
+            TunnelManager::retry_due(&mgr, now_ms()).await;
+            tokio::time::sleep(Duration::from_millis(10)).await;
         }
-        self.active = still;
-        dead
+        assert_eq!(tunnel().state, TunnelState::Failed);
+        assert_eq!(starts.load(Ordering::SeqCst), 3);
+        assert!(tunnel().last_error.unwrap().starts_with("flaky exited"));
+        mgr.lock().unwrap().stop(4200).unwrap();
     }
-}
 
-fn now_ms() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn reroutes_when_a_routed_provider_is_replaced() {
+        let starts = Arc::new(AtomicUsize::new(0));
+        let named = |host: &'static str| {
+            let counter = starts.clone();
+            FakeProvider::new("named", move |_, _| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async move {
+                    let child = Command::new("sleep").arg("30").kill_on_drop(true).spawn().unwrap();
+                    Ok((child, format!("https://{host}")))
+                })
+            })
+            .routed()
+        };
+        let mut providers = ProviderRegistry::new();
+        providers.register(named("old.example"));
+        let mgr = Arc::new(Mutex::new(TunnelManager::with_providers(providers)));
+        let url = || mgr.lock().unwrap().list()[0].url.clone();
+
+        TunnelManager::start(&mgr, 4300, Some("named"), true).await.unwrap();
+        TunnelManager::reroute(&mgr).await;
+        assert_eq!(starts.load(Ordering::SeqCst), 1);
+
+        // Same routes, new settings: the running connector is out of date.
+        mgr.lock().unwrap().register_provider(named("new.example"));
+        TunnelManager::reroute(&mgr).await;
+        assert_eq!(starts.load(Ordering::SeqCst), 2);
+        assert_eq!(url(), "https://new.example");
+
+        TunnelManager::reroute(&mgr).await;
+        assert_eq!(starts.load(Ordering::SeqCst), 2);
+        mgr.lock().unwrap().stop(4300).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn routed_ports_share_one_connector() {
+        let pids = Arc::new(Mutex::new(vec![]));
+        let spawned = pids.clone();
+        let mut providers = ProviderRegistry::new();
+        providers.register(
+            FakeProvider::new("named", move |_, _| {
+                let spawned = spawned.clone();
+                Box::pin(async move {
+                    let child = Command::new("sleep").arg("30").kill_on_drop(true).spawn().unwrap();
+                    spawned.lock().unwrap().push(child.id().unwrap());
+                    Ok((child, "https://named.example".to_string()))
+                })
+            })
+            .routed(),
+        );
+        let mgr = Arc::new(Mutex::new(TunnelManager::with_providers(providers)));
+        let pid = |i: usize| pids.lock().unwrap()[i];
+        let routes = || mgr.lock().unwrap().connectors.get("named").map(|c| c.routes.clone());
+        let alive = |pid: u32| {
+            std::process::Command::new("kill")
+                .args(["-0", &pid.to_string()])
+                .stderr(std::process::Stdio::null())
+                .status()
+                .unwrap()
+                .success()
+        };
+        let gone = |pid: u32| async move {
+            for _ in 0..500 {
+                if !alive(pid) {
+                    return true;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            false
+        };
+
+        TunnelManager::start(&mgr, 4400, Some("named"), true).await.unwrap();
+        TunnelManager::start(&mgr, 4401, Some("named"), true).await.unwrap();
+        // The second port relaunched the connector with both routes.
+        assert_eq!(pids.lock().unwrap().len(), 2);
+        assert!(gone(pid(0)).await);
+        assert_eq!(routes(), Some(vec![Forward::direct(4400), Forward::direct(4401)]));
+        assert!(mgr.lock().unwrap().active.values().all(|t| t.process.is_none()));
+
+        // Closing one port leaves the connector to the next `reroute`.
+        mgr.lock().unwrap().stop(4400).unwrap();
+        assert!(alive(pid(1)));
+        TunnelManager::reroute(&mgr).await;
+        assert_eq!(pids.lock().unwrap().len(), 3);
+        assert!(gone(pid(1)).await);
+        assert_eq!(routes(), Some(vec![Forward::direct(4401)]));
+
+        // The last port takes the connector down with it.
+        mgr.lock().unwrap().stop(4401).unwrap();
+        assert_eq!(routes(), None);
+        assert!(gone(pid(2)).await);
+    }
 }
@@ -1,14 +1,97 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
+use crate::clock::now_ms;
+
+pub mod bore;
 pub mod cloudflare;
 pub mod manager;
+pub mod ngrok;
+pub mod process;
 pub mod provider;
+pub mod registry;
+pub mod ssh;
+#[cfg(test)]
+mod testing;
+pub mod upstream;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct TunnelInfo {
     pub port: u16,
     pub provider: String,
     pub url: String,
+    pub kind: TunnelKind,
     pub started_at_ms: u64,
     pub last_renewed_at_ms: u64,
+    /// When the tunnel closes on its own, if it was opened with a TTL.
+    pub expires_at_ms: Option<u64>,
+    pub state: TunnelState,
+    pub state_changed_at_ms: u64,
+    /// Times the tunnel was brought back after dying or a renewal.
+    pub restart_count: u32,
+    /// What last went wrong: why it stopped, failed to restart or is
+    /// degraded. Kept after recovery.
+    pub last_error: Option<String>,
+    /// Failed renewals since the tunnel was last up.
+    pub retry_attempts: u32,
+    /// When the next renewal is tried, while `Reconnecting`.
+    pub next_retry_at_ms: Option<u64>,
+}
+
+impl TunnelInfo {
+    /// A tunnel that just came up.
+    pub fn new(port: u16, provider: &str, url: String, kind: TunnelKind) -> Self {
+        let now = now_ms();
+        Self {
+            port,
+            provider: provider.to_string(),
+            url,
+            kind,
+            started_at_ms: now,
+            last_renewed_at_ms: now,
+            expires_at_ms: None,
+            state: TunnelState::Up,
+            state_changed_at_ms: now,
+            restart_count: 0,
+            last_error: None,
+            retry_attempts: 0,
+            next_retry_at_ms: None,
+        }
+    }
+}
+
+/// Lifecycle of a tunnel:
+///
+/// ```text
+/// Starting -> Up <-> Degraded
+///              |        |
+///              v        v
+///           Reconnecting -> Failed
+/// ```
+///
+/// `Reconnecting` goes back to `Up` when a renewal succeeds; a manual renewal
+/// also takes `Failed` back to `Reconnecting`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TunnelState {
+    /// The provider is being launched. `TunnelManager::list` includes
+    /// pending starts like this one.
+    Starting,
+    Up,
+    /// The tunnel runs but nothing listens on the shared port any more.
+    Degraded,
+    /// The process exited; renewal is retried with backoff.
+    Reconnecting,
+    /// Renewal gave up. The tunnel stays listed until it is closed or renewed
+    /// by hand.
+    Failed,
+}
+
+/// What the public URL speaks: `https://…` for HTTP providers, `tcp://host:port`
+/// for raw TCP forwarding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TunnelKind {
+    Http,
+    Tcp,
 }
+
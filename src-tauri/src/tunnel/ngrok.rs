@@ -0,0 +1,305 @@
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    sync::Mutex,
+    time::Duration,
+};
+
+use tokio::{
+    process::{Child, Command},
+    sync::mpsc::UnboundedReceiver,
+    task,
+    time::{interval_at, Instant, MissedTickBehavior},
+};
+
+use super::{
+    process::{spawn_with_events, wait_until_ready, BoxFuture, TunnelLog},
+    provider::{Forward, TunnelError, TunnelProvider},
+    registry::{resolve_binary, spawn_error, status_for, ProviderStatus},
+};
+
+const URL_TIMEOUT: Duration = Duration::from_secs(20);
+const API_POLL_INTERVAL: Duration = Duration::from_secs(1);
+// Where the agent serves its inspection API unless the log says otherwise.
+const DEFAULT_WEB_ADDR: &str = "127.0.0.1:4040";
+
+pub struct NgrokProvider {
+    pub binary: String,
+    /// Passed as `NGROK_AUTHTOKEN`; without it the agent falls back to its own
+    /// config file.
+    pub authtoken: Option<String>,
+}
+
+impl Default for NgrokProvider {
+    fn default() -> Self {
+        Self {
+            binary: "ngrok".to_string(),
+            authtoken: None,
+        }
+    }
+}
+
+impl NgrokProvider {
+    pub fn new(authtoken: Option<String>) -> Self {
+        Self {
+            authtoken: authtoken.filter(|t| !t.trim().is_empty()),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum LogEvent {
+    Url(String),
+    WebAddr(String),
+    Error(String),
+}
+
+impl TunnelProvider for NgrokProvider {
+    fn name(&self) -> &'static str {
+        "ngrok"
+    }
+
+    fn start(&self, forward: Forward, log: TunnelLog) -> BoxFuture<'_, Result<(Child, String), TunnelError>> {
+        Box::pin(async move {
+            let bin = resolve_binary(&self.binary, "NGROK_PATH").unwrap_or_else(|| self.binary.clone());
+
+            let mut cmd = Command::new(&bin);
+            cmd.args([
+                "http",
+                &forward.target.to_string(),
+                "--log=stdout",
+                "--log-format=json",
+            ]);
+            // Env rather than `--authtoken` so the token doesn't show up in `ps`.
+            if let Some(token) = &self.authtoken {
+                cmd.env("NGROK_AUTHTOKEN", token);
+            }
+            let (mut child, mut rx) = spawn_with_events(&mut cmd, &log, parse_log_line)
+                .map_err(|e| spawn_error("ngrok", "NGROK_PATH", &bin, e))?;
+
+            match wait_for_url(&mut child, &mut rx, forward.target).await {
+                Ok(url) => Ok((child, url)),
+                Err(e) => {
+                    let _ = child.kill().await;
+                    Err(e)
+                }
+            }
+        })
+    }
+
+    fn status(&self) -> ProviderStatus {
+        status_for(self.name(), resolve_binary(&self.binary, "NGROK_PATH"), &["version"])
+    }
+}
+
+/// Waits for the public URL from the log, falling back to the agent's local
+/// API once it has announced where that lives.
+async fn wait_for_url(
+    child: &mut Child,
+    rx: &mut UnboundedReceiver<LogEvent>,
+    port: u16,
+) -> Result<String, TunnelError> {
+    let web_addr = Mutex::new(DEFAULT_WEB_ADDR.to_string());
+    let mut last_error: Option<String> = None;
+    let logged = wait_until_ready(child, rx, URL_TIMEOUT, |event| match event {
+        LogEvent::Url(url) => Some(url),
+        LogEvent::WebAddr(addr) => {
+            *web_addr.lock().unwrap() = addr;
+            None
+        }
+        LogEvent::Error(e) => {
+            last_error = Some(e);
+            None
+        }
+    });
+    let polled = async {
+        let mut poll = interval_at(Instant::now() + API_POLL_INTERVAL, API_POLL_INTERVAL);
+        poll.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            poll.tick().await;
+            let addr = web_addr.lock().unwrap().clone();
+            if let Ok(Some(url)) = task::spawn_blocking(move || api_tunnel_url(&addr, port)).await {
+                return url;
+            }
+        }
+    };
+    let waited = tokio::select! {
+        waited = logged => waited,
+        url = polled => Ok(url),
+    };
+    waited.map_err(|e| e.into_error("ngrok", last_error))
+}
+
+/// Picks the interesting bits out of one `--log-format=json` line. Anything
+/// that isn't JSON (stderr, panics) is treated as an error message.
+fn parse_log_line(line: &str) -> Option<LogEvent> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    let Ok(v) = serde_json::from_str::<serde_json::Value>(line) else {
+        return Some(LogEvent::Error(line.to_string()));
+    };
+    let field = |k: &str| v.get(k).and_then(|x| x.as_str());
+
+    match field("msg") {
+        Some("started tunnel") => {
+            if let Some(url) = field("url").filter(|u| u.starts_with("http")) {
+                return Some(LogEvent::Url(url.to_string()));
+            }
+        }
+        Some("starting web service") => {
+            if let Some(addr) = field("addr") {
+                return Some(LogEvent::WebAddr(addr.to_string()));
+            }
+        }
+        _ => {}
+    }
+    // ngrok spells the levels `eror` and `crit`.
+    if matches!(field("lvl"), Some("eror" | "crit")) {
+        let msg = field("err").or(field("msg")).unwrap_or(line);
+        return Some(LogEvent::Error(msg.to_string()));
+    }
+    None
+}
+
+/// Asks the agent's `/api/tunnels` for the public URL forwarding to `port`.
+fn api_tunnel_url(web_addr: &str, port: u16) -> Option<String> {
+    let mut stream = TcpStream::connect(web_addr).ok()?;
+    stream.set_read_timeout(Some(Duration::from_secs(2))).ok()?;
+    stream
+        .write_all(b"GET /api/tunnels HTTP/1.0\r\nHost: localhost\r\nAccept: application/json\r\n\r\n")
+        .ok()?;
+    let mut raw = String::new();
+    stream.read_to_string(&mut raw).ok()?;
+    let (_, body) = raw.split_once("\r\n\r\n")?;
+    tunnel_url_from_api(body, port)
+}
+
+fn tunnel_url_from_api(body: &str, port: u16) -> Option<String> {
+    let v: serde_json::Value = serde_json::from_str(body).ok()?;
+    let suffix = format!(":{port}");
+    let mut urls: Vec<&str> = v
+        .get("tunnels")?
+        .as_array()?
+        .iter()
+        .filter(|t| {
+            t.pointer("/config/addr")
+                .and_then(|a| a.as_str())
+                .is_some_and(|a| a.ends_with(&suffix) || a == port.to_string())
+        })
+        .filter_map(|t| t.get("public_url")?.as_str())
+        .collect();
+    urls.sort_by_key(|u| !u.starts_with("https://"));
+    urls.first().map(|u| u.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_structured_log_lines() {
+        assert_eq!(
+            parse_log_line(
+                r#"{"addr":"http://localhost:5173","lvl":"info","msg":"started tunnel","name":"command_line","obj":"tunnels","t":"2024-05-01T10:00:00Z","url":"https://a1b2.ngrok-free.app"}"#
+            ),
+            Some(LogEvent::Url("https://a1b2.ngrok-free.app".to_string()))
+        );
+        assert_eq!(
+            parse_log_line(r#"{"addr":"127.0.0.1:4041","lvl":"info","msg":"starting web service","obj":"web"}"#),
+            Some(LogEvent::WebAddr("127.0.0.1:4041".to_string()))
+        );
+        assert_eq!(
+            parse_log_line(
+                r#"{"err":"authentication failed: The authtoken you specified is invalid.\n\nERR_NGROK_107","lvl":"eror","msg":"failed to reconnect session"}"#
+            ),
+            Some(LogEvent::Error(
+                "authentication failed: The authtoken you specified is invalid.\n\nERR_NGROK_107".to_string()
+            ))
+        );
+        assert_eq!(parse_log_line(r#"{"lvl":"info","msg":"client session established"}"#), None);
+    }
+
+    #[test]
+    fn picks_https_tunnel_for_port() {
+        let body = r#"{"tunnels":[
+            {"public_url":"http://x.ngrok.app","config":{"addr":"http://localhost:3000"}},
+            {"public_url":"https://y.ngrok.app","config":{"addr":"http://localhost:8080"}},
+            {"public_url":"https://x.ngrok.app","config":{"addr":"http://localhost:3000"}}
+        ]}"#;
+        assert_eq!(tunnel_url_from_api(body, 3000).as_deref(), Some("https://x.ngrok.app"));
+        assert_eq!(tunnel_url_from_api(body, 9999), None);
+    }
+
+    #[cfg(unix)]
+    mod fake_agent {
+        use std::{fs, net::TcpListener, path::Path, thread};
+
+        use super::*;
+        use crate::tunnel::testing::stub_binary;
+
+        fn provider(path: &Path, authtoken: Option<&str>) -> NgrokProvider {
+            NgrokProvider {
+                binary: path.to_string_lossy().to_string(),
+                authtoken: authtoken.map(|t| t.to_string()),
+            }
+        }
+
+        #[tokio::test]
+        async fn reads_url_from_log_and_passes_authtoken() {
+            let bin = stub_binary(
+                "ngrok",
+                "log",
+                r#"[ "$1" = http ] && [ "$3" = --log=stdout ] && [ "$4" = --log-format=json ] || exit 2
+if [ "$NGROK_AUTHTOKEN" != secret ]; then
+  echo '{"lvl":"eror","msg":"session closing","err":"authentication failed: ERR_NGROK_4018"}'
+  exit 1
+fi
+echo "{\"lvl\":\"info\",\"msg\":\"started tunnel\",\"obj\":\"tunnels\",\"addr\":\"http://localhost:$2\",\"url\":\"https://fake-$2.ngrok-free.app\"}"
+exec sleep 30"#,
+            );
+
+            let p = provider(&bin, Some("secret"));
+            let (mut child, url) = p.start(Forward::direct(5173), TunnelLog::default()).await.unwrap();
+            assert_eq!(url, "https://fake-5173.ngrok-free.app");
+            child.kill().await.unwrap();
+
+            let err = provider(&bin, Some("wrong"))
+                .start(Forward::direct(5173), TunnelLog::default())
+                .await
+                .unwrap_err();
+            let _ = fs::remove_dir_all(bin.parent().unwrap());
+            assert!(err.to_string().contains("ERR_NGROK_4018"), "{err}");
+        }
+
+        #[tokio::test]
+        async fn falls_back_to_local_api() {
+            let api = TcpListener::bind("127.0.0.1:0").unwrap();
+            let api_addr = api.local_addr().unwrap();
+            thread::spawn(move || {
+                for mut conn in api.incoming().flatten() {
+                    let mut buf = [0u8; 1024];
+                    let _ = conn.read(&mut buf);
+                    let body = r#"{"tunnels":[{"public_url":"https://from-api.ngrok.app","config":{"addr":"http://localhost:8080"}}]}"#;
+                    let _ = write!(conn, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{body}", body.len());
+                }
+            });
+
+            let bin = stub_binary(
+                "ngrok",
+                "api",
+                &format!(
+                    r#"echo '{{"lvl":"info","msg":"starting web service","obj":"web","addr":"{api_addr}"}}'
+exec sleep 30"#
+                ),
+            );
+            let p = provider(&bin, None);
+            let (mut child, url) = p.start(Forward::direct(8080), TunnelLog::default()).await.unwrap();
+            child.kill().await.unwrap();
+            let _ = fs::remove_dir_all(bin.parent().unwrap());
+            assert_eq!(url, "https://from-api.ngrok.app");
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    io,
+    pin::Pin,
+    process::{ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use serde::Serialize;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    process::{Child, Command},
+    sync::{mpsc, oneshot, watch},
+    time::timeout,
+};
+
+use super::provider::TunnelError;
+use crate::clock::now_ms;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+// Output a process prints on its way out usually says why it exited.
+const EXIT_DRAIN: Duration = Duration::from_millis(500);
+
+/// Output lines kept per process.
+pub const MAX_LOG_LINES: usize = 500;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LogLine {
+    pub at_ms: u64,
+    pub stream: LogStream,
+    pub text: String,
+}
+
+type OnLine = Box<dyn Fn(&LogLine) + Send + Sync>;
+
+#[derive(Default)]
+struct LogBuffer {
+    lines: Mutex<VecDeque<LogLine>>,
+    on_line: Option<OnLine>,
+}
+
+/// Ring buffer of what one provider process printed. Clones share it.
+#[derive(Clone, Default)]
+pub struct TunnelLog(Arc<LogBuffer>);
+
+impl TunnelLog {
+    /// `on_line` runs for every line, e.g. to emit a Tauri event.
+    pub fn new(on_line: impl Fn(&LogLine) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(LogBuffer {
+            lines: Mutex::default(),
+            on_line: Some(Box::new(on_line)),
+        }))
+    }
+
+    fn push(&self, stream: LogStream, text: &str) {
+        let line = LogLine {
+            at_ms: now_ms(),
+            stream,
+            text: text.trim_end().to_string(),
+        };
+        if let Ok(mut lines) = self.0.lines.lock() {
+            if lines.len() >= MAX_LOG_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line.clone());
+        }
+        if let Some(on_line) = &self.0.on_line {
+            on_line(&line);
+        }
+    }
+
+    /// Oldest first.
+    pub fn lines(&self) -> Vec<LogLine> {
+        match self.0.lines.lock() {
+            Ok(lines) => lines.iter().cloned().collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    /// The text of the last `n` non-empty lines, oldest first.
+    pub fn tail(&self, n: usize) -> Vec<String> {
+        let Ok(lines) = self.0.lines.lock() else {
+            return vec![];
+        };
+        let mut tail: Vec<String> = lines
+            .iter()
+            .rev()
+            .filter(|l| !l.text.is_empty())
+            .take(n)
+            .map(|l| l.text.clone())
+            .collect();
+        tail.reverse();
+        tail
+    }
+}
+
+/// Spawns `cmd` with stdout and stderr piped, records every line in `log`
+/// and sends the ones `classify` keeps to the returned channel. The readers
+/// keep draining after the caller stops listening, so the process never
+/// blocks on a full pipe, and its output stays in `log` while it runs.
+pub fn spawn_with_events<E, F>(
+    cmd: &mut Command,
+    log: &TunnelLog,
+    classify: F,
+) -> io::Result<(Child, mpsc::UnboundedReceiver<E>)>
+where
+    E: Send + 'static,
+    F: Fn(&str) -> Option<E> + Send + Sync + 'static,
+{
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    let classify = Arc::new(classify);
+    if let Some(stdout) = child.stdout.take() {
+        let log = (log.clone(), LogStream::Stdout);
+        spawn_reader(stdout, log, tx.clone(), classify.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        spawn_reader(stderr, (log.clone(), LogStream::Stderr), tx, classify);
+    }
+    Ok((child, rx))
+}
+
+fn spawn_reader<R, E, F>(
+    reader: R,
+    (log, stream): (TunnelLog, LogStream),
+    tx: mpsc::UnboundedSender<E>,
+    classify: Arc<F>,
+) where
+    R: AsyncRead + Unpin + Send + 'static,
+    E: Send + 'static,
+    F: Fn(&str) -> Option<E> + Send + Sync + 'static,
+{
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            log.push(stream, &line);
+            if let Some(event) = classify(&line) {
+                let _ = tx.send(event);
+            }
+        }
+    });
+}
+
+/// Events delivered shortly after the process exited.
+pub async fn drain<E>(rx: &mut mpsc::UnboundedReceiver<E>) -> Vec<E> {
+    let mut events = vec![];
+    let _ = timeout(EXIT_DRAIN, async {
+        while let Some(e) = rx.recv().await {
+            events.push(e);
+        }
+    })
+    .await;
+    events
+}
+
+/// Why a provider process never became ready.
+pub enum NotReady {
+    /// It exited, with this status.
+    Exited(String),
+    TimedOut,
+}
+
+impl NotReady {
+    /// `StartFailed` saying `program` exited and why, or `UrlTimeout`.
+    pub fn into_error(self, program: &str, reason: Option<String>) -> TunnelError {
+        match self {
+            Self::Exited(status) => {
+                let reason = reason.unwrap_or_else(|| "no error was printed".to_string());
+                TunnelError::StartFailed(format!("{program} exited ({status}): {reason}"))
+            }
+            Self::TimedOut => TunnelError::UrlTimeout(vec![]),
+        }
+    }
+}
+
+/// Feeds `child`'s events to `on_event` until it returns what the provider
+/// waits for, the process exits or `limit` passes. Events printed on the
+/// way out still reach `on_event`, so it can note why.
+pub async fn wait_until_ready<E, T>(
+    child: &mut Child,
+    rx: &mut mpsc::UnboundedReceiver<E>,
+    limit: Duration,
+    mut on_event: impl FnMut(E) -> Option<T>,
+) -> Result<T, NotReady> {
+    let waited = timeout(limit, async {
+        let mut open = true;
+        loop {
+            tokio::select! {
+                event = rx.recv(), if open => match event {
+                    Some(event) => {
+                        if let Some(ready) = on_event(event) {
+                            return Ok(ready);
+                        }
+                    }
+                    None => open = false,
+                },
+                status = child.wait() => {
+                    for event in drain(rx).await {
+                        on_event(event);
+                    }
+                    return Err(NotReady::Exited(status.map_or_else(|e| e.to_string(), |s| s.to_string())));
+                }
+            }
+        }
+    })
+    .await;
+    waited.unwrap_or(Err(NotReady::TimedOut))
+}
+
+/// A running provider process, owned by a task that waits for it to exit
+/// or kills it on request. Dropping the handle kills the process too.
+pub struct TunnelProcess {
+    id: u64,
+    stop: Option<oneshot::Sender<()>>,
+    exit: watch::Receiver<Option<String>>,
+}
+
+impl TunnelProcess {
+    pub fn supervise(mut child: Child) -> Self {
+        let (stop, stop_rx) = oneshot::channel::<()>();
+        let (exit_tx, exit) = watch::channel(None);
+        tokio::spawn(async move {
+            let status = tokio::select! {
+                status = child.wait() => status,
+                _ = stop_rx => {
+                    let _ = child.start_kill();
+                    child.wait().await
+                }
+            };
+            exit_tx.send_replace(Some(describe(status)));
+        });
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            stop: Some(stop),
+            exit,
+        }
+    }
+
+    /// Tells processes apart across restarts of the same port.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Resolves with how the process ended, e.g. `exit status: 1`.
+    pub fn exited(&self) -> impl Future<Output = String> + Send + 'static {
+        let mut exit = self.exit.clone();
+        async move {
+            match exit.wait_for(Option::is_some).await {
+                Ok(reason) => reason.clone().unwrap_or_default(),
+                Err(_) => "supervisor stopped".to_string(),
+            }
+        }
+    }
+
+    /// Kills the process without waiting for it to be gone.
+    pub fn kill(mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+    }
+
+    /// Kills the process and waits until it is gone.
+    pub async fn stop(self) {
+        let exited = self.exited();
+        self.kill();
+        exited.await;
+    }
+}
+
+fn describe(status: io::Result<ExitStatus>) -> String {
+    match status {
+        Ok(status) => status.to_string(),
+        Err(e) => format!("could not be waited on: {e}"),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn keeps_output_after_the_url_was_found() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let on_line = seen.clone();
+        let log = TunnelLog::new(move |l| on_line.lock().unwrap().push(l.text.clone()));
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo url; echo 'conn 1' >&2; echo; echo 'conn 2'"]);
+        let (mut child, mut rx) =
+            spawn_with_events(&mut cmd, &log, |l| (l == "url").then_some(())).unwrap();
+
+        assert_eq!(rx.recv().await, Some(()));
+        child.wait().await.unwrap();
+        drain(&mut rx).await;
+
+        let stderr: Vec<String> = log
+            .lines()
+            .into_iter()
+            .filter(|l| l.stream == LogStream::Stderr)
+            .map(|l| l.text)
+            .collect();
+        assert_eq!(stderr, ["conn 1"]);
+        assert_eq!(log.lines().len(), 4);
+        assert_eq!(seen.lock().unwrap().len(), 4);
+        // Empty lines are left out; the two pipes may interleave either way.
+        let mut tail = log.tail(10);
+        tail.sort();
+        assert_eq!(tail, ["conn 1", "conn 2", "url"]);
+    }
+}
@@ -1,22 +1,108 @@
+use std::collections::BTreeMap;
+
 use thiserror::Error;
+use tokio::process::Child;
 
-use super::TunnelInfo;
+use super::{
+    process::{BoxFuture, TunnelLog},
+    registry::ProviderStatus,
+    TunnelInfo, TunnelKind,
+};
 
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Error)]
 pub enum TunnelError {
-    #[error("cloudflared not found or failed to start: {0}")]
+    #[error("tunnel provider failed to start: {0}")]
     StartFailed(String),
-    #[error("tunnel url not detected in time")]
-    UrlTimeout,
+    /// Carries the last lines the provider printed, see `with_output`.
+    #[error("tunnel url not detected in time{}", output_tail(.0))]
+    UrlTimeout(Vec<String>),
     #[error("tunnel not running")]
     NotRunning,
+    #[error("tunnel was closed while starting")]
+    Cancelled,
     #[error("stop failed: {0}")]
     StopFailed(String),
+    #[error("unknown tunnel provider: {0}")]
+    UnknownProvider(String),
+    #[error("nothing is listening on localhost:{0}")]
+    UpstreamNotListening(u16),
+    #[error("localhost:{0} is listening but did not answer an HTTP request")]
+    UpstreamNotHttp(u16),
+}
+
+impl TunnelError {
+    /// Adds what the provider printed last to a failed start, which usually
+    /// says more than the error alone.
+    pub fn with_output(self, lines: Vec<String>) -> Self {
+        match self {
+            Self::StartFailed(msg) => Self::StartFailed(format!("{msg}{}", output_tail(&lines))),
+            Self::UrlTimeout(_) => Self::UrlTimeout(lines),
+            other => other,
+        }
+    }
+}
+
+fn output_tail(lines: &[String]) -> String {
+    if lines.is_empty() {
+        return String::new();
+    }
+    format!("\nlast output:\n{}", lines.join("\n"))
 }
 
+/// A shared port and the loopback port its traffic is sent to: the port
+/// itself, or a proxy in front of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Forward {
+    pub port: u16,
+    pub target: u16,
+}
+
+impl Forward {
+    /// Traffic for `port` goes straight to it.
+    pub fn direct(port: u16) -> Self {
+        Self { port, target: port }
+    }
+}
+
+/// A process serving several ports, and the public URL of each.
+pub type Routed = (Child, BTreeMap<u16, String>);
+
 pub trait TunnelProvider: Send + Sync {
     fn name(&self) -> &'static str;
-    fn start(&self, port: u16) -> Result<(std::process::Child, String), TunnelError>;
-    fn stop(&self, child: &mut std::process::Child) -> Result<(), TunnelError>;
-    fn build_info(&self, port: u16, url: String) -> TunnelInfo;
+    /// Whether the upstream has to speak HTTP or just accept TCP.
+    fn kind(&self) -> TunnelKind {
+        TunnelKind::Http
+    }
+    /// Publishes `forward.port`, sending its traffic to loopback
+    /// `forward.target`; resolves once the public URL is known. The process's
+    /// output goes to `log`, see `spawn_with_events`.
+    fn start(&self, forward: Forward, log: TunnelLog) -> BoxFuture<'_, Result<(Child, String), TunnelError>>;
+    /// Whether all of this provider's tunnels are served by one process with
+    /// one routing table, like a named Cloudflare tunnel's connector and its
+    /// ingress rules. The manager then starts that process with `start_routed`
+    /// and restarts it whenever a port is added or removed.
+    fn shares_routes(&self) -> bool {
+        false
+    }
+    /// Starts the one process serving everything in `routes`; resolves with
+    /// the public URL of each port once it is up. The default serves only the
+    /// first route.
+    fn start_routed<'a>(
+        &'a self,
+        routes: &'a [Forward],
+        log: TunnelLog,
+    ) -> BoxFuture<'a, Result<Routed, TunnelError>> {
+        Box::pin(async move {
+            let Some(&forward) = routes.first() else {
+                return Err(TunnelError::StartFailed("no ports to route".to_string()));
+            };
+            let (child, url) = self.start(forward, log).await?;
+            Ok((child, BTreeMap::from([(forward.port, url)])))
+        })
+    }
+    fn build_info(&self, port: u16, url: String) -> TunnelInfo {
+        TunnelInfo::new(port, self.name(), url, self.kind())
+    }
+    /// Whether the provider's binary can be found, and which version it is.
+    fn status(&self) -> ProviderStatus;
 }
@@ -0,0 +1,204 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Arc,
+};
+
+use serde::Serialize;
+
+use super::{
+    bore::BoreProvider,
+    cloudflare::CloudflareProvider,
+    ngrok::NgrokProvider,
+    provider::{TunnelError, TunnelProvider},
+    ssh::SshProvider,
+};
+
+pub const DEFAULT_PROVIDER: &str = "cloudflare";
+
+/// What `list_providers` reports for each registered provider.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderStatus {
+    pub name: String,
+    /// The binary was found and answered `--version`.
+    pub available: bool,
+    pub binary: Option<String>,
+    pub version: Option<String>,
+}
+
+/// Tunnel providers keyed by the name stored in `DesiredTunnel.provider`.
+/// Cheap to clone, so availability checks can run without holding the
+/// `TunnelManager` lock.
+#[derive(Clone)]
+pub struct ProviderRegistry {
+    providers: BTreeMap<&'static str, Arc<dyn TunnelProvider>>,
+}
+
+impl ProviderRegistry {
+    pub fn new() -> Self {
+        Self {
+            providers: BTreeMap::new(),
+        }
+    }
+
+    /// Every provider this build knows about.
+    pub fn with_builtin() -> Self {
+        let mut r = Self::new();
+        r.register(CloudflareProvider::default());
+        r.register(NgrokProvider::default());
+        r.register(SshProvider::default());
+        r.register(BoreProvider::default());
+        r
+    }
+
+    pub fn register(&mut self, provider: impl TunnelProvider + 'static) {
+        self.providers.insert(provider.name(), Arc::new(provider));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn TunnelProvider>> {
+        self.providers.get(name).cloned()
+    }
+
+    pub fn statuses(&self) -> Vec<ProviderStatus> {
+        self.providers.values().map(|p| p.status()).collect()
+    }
+}
+
+/// Runs `<binary> <args>` and returns the first version-looking token of its
+/// output, e.g. `2024.8.2` from `cloudflared version 2024.8.2 (built ...)`.
+pub fn binary_version(binary: &str, args: &[&str]) -> Option<String> {
+    let out = Command::new(binary).args(args).output().ok()?;
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&out.stdout),
+        String::from_utf8_lossy(&out.stderr)
+    );
+    text.split_whitespace()
+        .map(|w| w.trim_start_matches('v').trim_end_matches(','))
+        .find(|w| w.starts_with(|c: char| c.is_ascii_digit()) && w.contains('.'))
+        .map(|w| w.to_string())
+}
+
+/// Builds a `ProviderStatus` from a resolved binary path.
+pub fn status_for(name: &str, binary: Option<String>, version_args: &[&str]) -> ProviderStatus {
+    let version = binary.as_deref().and_then(|b| binary_version(b, version_args));
+    ProviderStatus {
+        name: name.to_string(),
+        available: version.is_some(),
+        binary,
+        version,
+    }
+}
+
+/// Finds a provider binary: an explicit path, then `env_var`, then next to
+/// our own executable (or in a macOS bundle's `Resources`), `PATH`, and
+/// common install locations. Packaged GUI apps often don't inherit the
+/// shell's `PATH`, hence the extra places.
+pub fn resolve_binary(configured: &str, env_var: &str) -> Option<String> {
+    if (configured.contains('/') || configured.contains('\\')) && Path::new(configured).exists() {
+        return Some(configured.to_string());
+    }
+    if let Ok(p) = std::env::var(env_var) {
+        if !p.trim().is_empty() && Path::new(&p).exists() {
+            return Some(p);
+        }
+    }
+    let name = Path::new(configured).file_name()?.to_string_lossy().to_string();
+    let names = executable_names(&name);
+    let mut dirs = vec![];
+    if let Some(dir) = std::env::current_exe().ok().and_then(|e| e.parent().map(|d| d.to_path_buf())) {
+        // <App>.app/Contents/MacOS/<exe> keeps resources in ../Resources.
+        if cfg!(target_os = "macos") {
+            dirs.extend(dir.parent().map(|contents| contents.join("Resources")));
+        }
+        dirs.insert(0, dir);
+    }
+    let found = |dirs: &[PathBuf]| {
+        dirs.iter()
+            .flat_map(|dir| names.iter().map(move |n| dir.join(n)))
+            .find(|p| p.is_file())
+            .map(|p| p.to_string_lossy().to_string())
+    };
+    found(&dirs)
+        .or_else(|| find_in_path(&name))
+        .or_else(|| found(&install_dirs(&name)))
+}
+
+/// What `name` is called on disk: with `.exe` first on Windows.
+fn executable_names(name: &str) -> Vec<String> {
+    if cfg!(target_os = "windows") && !name.ends_with(".exe") {
+        vec![format!("{name}.exe"), name.to_string()]
+    } else {
+        vec![name.to_string()]
+    }
+}
+
+fn install_dirs(name: &str) -> Vec<PathBuf> {
+    if cfg!(target_os = "windows") {
+        let mut dirs = vec![PathBuf::from(r"C:\ProgramData\chocolatey\bin")];
+        for var in ["ProgramFiles", "ProgramFiles(x86)", "LocalAppData"] {
+            if let Ok(root) = std::env::var(var) {
+                dirs.push(PathBuf::from(root).join(name));
+            }
+        }
+        return dirs;
+    }
+    ["/opt/homebrew/bin", "/usr/local/bin", "/usr/bin", "/snap/bin"]
+        .iter()
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// The error for a provider binary that could not be spawned.
+pub fn spawn_error(program: &str, env_var: &str, bin: &str, e: std::io::Error) -> TunnelError {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        TunnelError::StartFailed(format!(
+            "{program} not found. Install it and ensure it's accessible to the app, or set {env_var}.\nTried binary: {bin}"
+        ))
+    } else {
+        TunnelError::StartFailed(format!("{program} failed to start: {e}"))
+    }
+}
+
+/// Looks `name` up on `PATH`, the way `Command::new` would.
+pub fn find_in_path(name: &str) -> Option<String> {
+    let path = std::env::var_os("PATH")?;
+    let names = executable_names(name);
+    std::env::split_paths(&path)
+        .flat_map(|dir| names.iter().map(move |n| dir.join(n)))
+        .find(|p| p.is_file())
+        .map(|p| p.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::tunnel::{manager::TunnelManager, provider::TunnelError};
+
+    #[cfg(unix)]
+    #[test]
+    fn extracts_version_from_output() {
+        let v = binary_version("sh", &["-c", "echo 'cloudflared version 2024.8.2 (built 2024-08-05)'"]);
+        assert_eq!(v.as_deref(), Some("2024.8.2"));
+        let v = binary_version("sh", &["-c", "echo 'ngrok version v3.18.4' >&2"]);
+        assert_eq!(v.as_deref(), Some("3.18.4"));
+        assert_eq!(binary_version("/nonexistent/tunnel-bin", &["--version"]), None);
+    }
+
+    #[tokio::test]
+    async fn unknown_provider_is_rejected() {
+        let mgr = Arc::new(Mutex::new(TunnelManager::with_providers(ProviderRegistry::new())));
+        assert!(matches!(
+            TunnelManager::start(&mgr, 8080, Some("carrier-pigeon"), true).await,
+            Err(TunnelError::UnknownProvider(name)) if name == "carrier-pigeon"
+        ));
+    }
+
+    #[test]
+    fn builtin_registry_has_default_provider() {
+        assert!(ProviderRegistry::with_builtin().get(DEFAULT_PROVIDER).is_some());
+    }
+}
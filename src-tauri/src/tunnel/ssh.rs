@@ -0,0 +1,360 @@
+use std::{process::Command as StdCommand, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    process::{Child, Command},
+    sync::mpsc::UnboundedReceiver,
+};
+
+use super::{
+    process::{spawn_with_events, wait_until_ready, BoxFuture, NotReady, TunnelLog},
+    provider::{Forward, TunnelError, TunnelProvider},
+    registry::{resolve_binary, spawn_error, ProviderStatus},
+};
+
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Where `ssh -R` should forward to. Saved in `settings.ssh`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SshConfig {
+    pub host: String,
+    pub port: u16,
+    pub user: Option<String>,
+    pub identity_file: Option<String>,
+    /// Public URL for a shared port; `{port}` and `{host}` are substituted,
+    /// e.g. `https://{port}.dev.example.com`.
+    pub url_template: String,
+}
+
+impl Default for SshConfig {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: 22,
+            user: None,
+            identity_file: None,
+            url_template: "https://{port}.{host}".to_string(),
+        }
+    }
+}
+
+impl SshConfig {
+    pub fn is_configured(&self) -> bool {
+        !self.host.trim().is_empty()
+    }
+
+    pub fn public_url(&self, port: u16) -> String {
+        self.url_template
+            .replace("{port}", &port.to_string())
+            .replace("{host}", self.host.trim())
+    }
+}
+
+/// Reverse-forwards the port to a bastion host with `ssh -N -R`. Nothing goes
+/// through a third party; the bastion's own proxy serves the public URL.
+pub struct SshProvider {
+    pub binary: String,
+    pub config: SshConfig,
+}
+
+impl Default for SshProvider {
+    fn default() -> Self {
+        Self::new(SshConfig::default())
+    }
+}
+
+impl SshProvider {
+    pub fn new(config: SshConfig) -> Self {
+        Self {
+            binary: "ssh".to_string(),
+            config,
+        }
+    }
+
+    fn args(&self, forward: Forward) -> Vec<String> {
+        let c = &self.config;
+        let mut args: Vec<String> = [
+            "-N",
+            "-T",
+            // `-v` is how we learn the forward was accepted.
+            "-v",
+            "-o",
+            "ExitOnForwardFailure=yes",
+            "-o",
+            "ServerAliveInterval=15",
+            "-o",
+            "ServerAliveCountMax=3",
+            // Never prompt: there is no terminal to answer on.
+            "-o",
+            "BatchMode=yes",
+            "-o",
+            "StrictHostKeyChecking=accept-new",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        args.extend(["-p".to_string(), c.port.to_string()]);
+        if let Some(key) = c.identity_file.as_deref().filter(|k| !k.trim().is_empty()) {
+            args.extend(["-i".to_string(), key.to_string()]);
+        }
+        // The bastion listens on the shared port, whatever serves it locally.
+        let Forward { port, target } = forward;
+        args.extend(["-R".to_string(), format!("{port}:localhost:{target}")]);
+        args.push(match c.user.as_deref().filter(|u| !u.trim().is_empty()) {
+            Some(user) => format!("{user}@{}", c.host.trim()),
+            None => c.host.trim().to_string(),
+        });
+        args
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum SshEvent {
+    Forwarded,
+    Failed(String),
+    Other(String),
+}
+
+impl TunnelProvider for SshProvider {
+    fn name(&self) -> &'static str {
+        "ssh"
+    }
+
+    fn start(&self, forward: Forward, log: TunnelLog) -> BoxFuture<'_, Result<(Child, String), TunnelError>> {
+        Box::pin(async move {
+            if !self.config.is_configured() {
+                return Err(TunnelError::StartFailed(
+                    "ssh provider is not configured: set a bastion host first".to_string(),
+                ));
+            }
+            let bin = resolve_binary(&self.binary, "SSH_PATH").unwrap_or_else(|| self.binary.clone());
+
+            let (mut child, mut rx) =
+                spawn_with_events(Command::new(&bin).args(self.args(forward)), &log, classify_line)
+                    .map_err(|e| spawn_error("ssh", "SSH_PATH", &bin, e))?;
+
+            match wait_for_forward(&mut child, &mut rx).await {
+                Ok(()) => Ok((child, self.config.public_url(forward.port))),
+                Err(e) => {
+                    let _ = child.kill().await;
+                    Err(e)
+                }
+            }
+        })
+    }
+
+    fn status(&self) -> ProviderStatus {
+        let binary = resolve_binary(&self.binary, "SSH_PATH");
+        let version = binary.as_deref().and_then(openssh_version);
+        ProviderStatus {
+            name: self.name().to_string(),
+            available: version.is_some() && self.config.is_configured(),
+            binary,
+            version,
+        }
+    }
+}
+
+/// `ssh -V` prints e.g. `OpenSSH_9.6p1 Ubuntu-3ubuntu13, OpenSSL 3.0.13` on stderr.
+fn openssh_version(binary: &str) -> Option<String> {
+    let out = StdCommand::new(binary).arg("-V").output().ok()?;
+    let text = String::from_utf8_lossy(&out.stderr).to_string() + &String::from_utf8_lossy(&out.stdout);
+    let rest = &text[text.find("OpenSSH_")? + "OpenSSH_".len()..];
+    let end = rest.find([' ', ',']).unwrap_or(rest.len());
+    Some(rest[..end].trim().to_string())
+}
+
+async fn wait_for_forward(
+    child: &mut Child,
+    rx: &mut UnboundedReceiver<SshEvent>,
+) -> Result<(), TunnelError> {
+    let mut failure: Option<String> = None;
+    let mut last_line: Option<String> = None;
+    let waited = wait_until_ready(child, rx, FORWARD_TIMEOUT, |event| match event {
+        SshEvent::Forwarded => Some(()),
+        SshEvent::Failed(e) => {
+            failure = Some(e);
+            None
+        }
+        SshEvent::Other(line) => {
+            last_line = Some(line);
+            None
+        }
+    })
+    .await;
+    waited.map_err(|e| match (e, failure) {
+        (NotReady::TimedOut, Some(failure)) => TunnelError::StartFailed(failure),
+        (e, failure) => e.into_error("ssh", failure.or(last_line)),
+    })
+}
+
+/// Sorts one line of `ssh -v` stderr into success, a known failure, or noise.
+fn classify_line(line: &str) -> Option<SshEvent> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    if line.contains("remote forward success") {
+        return Some(SshEvent::Forwarded);
+    }
+
+    const AUTH: [&str; 3] = [
+        "Permission denied",
+        "Too many authentication failures",
+        "no mutual signature algorithm",
+    ];
+    const CONNECT: [&str; 6] = [
+        "Could not resolve hostname",
+        "Connection refused",
+        "Connection timed out",
+        "No route to host",
+        "Host key verification failed",
+        "Connection closed by",
+    ];
+    if AUTH.iter().any(|p| line.contains(p)) {
+        return Some(SshEvent::Failed(format!("ssh authentication failed: {line}")));
+    }
+    if line.contains("remote port forwarding failed") {
+        return Some(SshEvent::Failed(format!(
+            "bastion refused the port forward (port in use or forwarding disabled): {line}"
+        )));
+    }
+    if CONNECT.iter().any(|p| line.contains(p)) {
+        return Some(SshEvent::Failed(format!("cannot reach bastion: {line}")));
+    }
+    (!line.starts_with("debug")).then(|| SshEvent::Other(line.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SshConfig {
+        SshConfig {
+            host: "bastion.example.com".to_string(),
+            port: 2222,
+            user: Some("dev".to_string()),
+            identity_file: Some("/home/dev/.ssh/id_ed25519".to_string()),
+            url_template: "https://{port}.dev.example.com".to_string(),
+        }
+    }
+
+    #[test]
+    fn builds_reverse_forward_command() {
+        let args = SshProvider::new(config()).args(Forward::direct(5173));
+        let joined = args.join(" ");
+        assert!(joined.starts_with("-N -T -v"));
+        assert!(joined.contains("-o ExitOnForwardFailure=yes"));
+        assert!(joined.contains("-o ServerAliveInterval=15"));
+        assert!(joined.ends_with("-p 2222 -i /home/dev/.ssh/id_ed25519 -R 5173:localhost:5173 dev@bastion.example.com"));
+        assert_eq!(config().public_url(5173), "https://5173.dev.example.com");
+    }
+
+    #[test]
+    fn classifies_stderr() {
+        assert_eq!(
+            classify_line("debug1: remote forward success for: listen 5173, connect localhost:5173"),
+            Some(SshEvent::Forwarded)
+        );
+        assert!(matches!(
+            classify_line("dev@bastion.example.com: Permission denied (publickey)."),
+            Some(SshEvent::Failed(m)) if m.starts_with("ssh authentication failed")
+        ));
+        assert!(matches!(
+            classify_line("Error: remote port forwarding failed for listen port 5173"),
+            Some(SshEvent::Failed(m)) if m.starts_with("bastion refused the port forward")
+        ));
+        assert_eq!(classify_line("debug1: Authenticating to bastion:22 as 'dev'"), None);
+    }
+
+    #[tokio::test]
+    async fn refuses_to_start_unconfigured() {
+        let err = SshProvider::default()
+            .start(Forward::direct(5173), TunnelLog::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not configured"));
+    }
+
+    #[cfg(unix)]
+    mod fake_ssh {
+        use std::fs;
+
+        use super::*;
+        use crate::tunnel::testing::stub_binary;
+
+        fn provider(bin: &std::path::Path) -> SshProvider {
+            SshProvider {
+                binary: bin.to_string_lossy().to_string(),
+                config: config(),
+            }
+        }
+
+        #[tokio::test]
+        async fn returns_templated_url_once_forwarded() {
+            let bin = stub_binary(
+                "ssh",
+                "ok",
+                r#"echo "debug1: Authentication succeeded (publickey)." >&2
+echo "debug1: remote forward success for: listen 8080, connect localhost:8080" >&2
+exec sleep 30"#,
+            );
+            let p = provider(&bin);
+            let (mut child, url) = p.start(Forward::direct(8080), TunnelLog::default()).await.unwrap();
+            child.kill().await.unwrap();
+            let _ = fs::remove_dir_all(bin.parent().unwrap());
+            assert_eq!(url, "https://8080.dev.example.com");
+        }
+
+        #[tokio::test]
+        async fn publishes_the_shared_port_in_front_of_a_proxy() {
+            let bin = stub_binary(
+                "ssh",
+                "front",
+                r#"case "$*" in
+  *" -R 8080:localhost:41000 "*) ;;
+  *) echo "unexpected arguments: $*" >&2; exit 1 ;;
+esac
+echo "debug1: remote forward success for: listen 8080, connect localhost:41000" >&2
+exec sleep 30"#,
+            );
+            let forward = Forward { port: 8080, target: 41000 };
+            let (mut child, url) = provider(&bin).start(forward, TunnelLog::default()).await.unwrap();
+            child.kill().await.unwrap();
+            let _ = fs::remove_dir_all(bin.parent().unwrap());
+            assert_eq!(url, "https://8080.dev.example.com");
+        }
+
+        #[tokio::test]
+        async fn reports_auth_and_forward_failures() {
+            let denied = stub_binary(
+                "ssh",
+                "denied",
+                r#"echo "dev@bastion.example.com: Permission denied (publickey)." >&2
+exit 255"#,
+            );
+            let err = provider(&denied)
+                .start(Forward::direct(8080), TunnelLog::default())
+                .await
+                .unwrap_err()
+                .to_string();
+            let _ = fs::remove_dir_all(denied.parent().unwrap());
+            assert!(err.contains("ssh authentication failed"), "{err}");
+
+            let busy = stub_binary(
+                "ssh",
+                "busy",
+                r#"echo "Error: remote port forwarding failed for listen port 8080" >&2
+exit 255"#,
+            );
+            let err = provider(&busy)
+                .start(Forward::direct(8080), TunnelLog::default())
+                .await
+                .unwrap_err()
+                .to_string();
+            let _ = fs::remove_dir_all(busy.parent().unwrap());
+            assert!(err.contains("refused the port forward"), "{err}");
+        }
+    }
+}
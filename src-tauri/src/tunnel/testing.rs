@@ -0,0 +1,86 @@
+//! Fixtures shared by the tunnel tests.
+
+use std::path::PathBuf;
+
+use tokio::process::Child;
+
+use super::{
+    process::{BoxFuture, TunnelLog},
+    provider::{Forward, Routed, TunnelError, TunnelProvider},
+    registry::{status_for, ProviderStatus},
+};
+
+pub type Started = Result<(Child, String), TunnelError>;
+
+/// A provider named `name` whose `start` runs the given closure.
+pub struct FakeProvider<F> {
+    name: &'static str,
+    start: F,
+    routed: bool,
+}
+
+impl<F> FakeProvider<F>
+where
+    F: Fn(Forward, TunnelLog) -> BoxFuture<'static, Started> + Send + Sync,
+{
+    pub fn new(name: &'static str, start: F) -> Self {
+        Self {
+            name,
+            start,
+            routed: false,
+        }
+    }
+
+    /// Makes its tunnels share one routing table.
+    pub fn routed(mut self) -> Self {
+        self.routed = true;
+        self
+    }
+}
+
+impl<F> TunnelProvider for FakeProvider<F>
+where
+    F: Fn(Forward, TunnelLog) -> BoxFuture<'static, Started> + Send + Sync,
+{
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn start(&self, forward: Forward, log: TunnelLog) -> BoxFuture<'_, Started> {
+        (self.start)(forward, log)
+    }
+
+    fn shares_routes(&self) -> bool {
+        self.routed
+    }
+
+    /// Starts the first route and gives every port its URL.
+    fn start_routed<'a>(
+        &'a self,
+        routes: &'a [Forward],
+        log: TunnelLog,
+    ) -> BoxFuture<'a, Result<Routed, TunnelError>> {
+        Box::pin(async move {
+            let (child, url) = (self.start)(routes[0], log).await?;
+            Ok((child, routes.iter().map(|f| (f.port, url.clone())).collect()))
+        })
+    }
+
+    fn status(&self) -> ProviderStatus {
+        status_for(self.name, None, &[])
+    }
+}
+
+/// Writes a stand-in for `binary` that runs `body` as sh, in a temp
+/// directory of its own per `test`. Remove the directory when done.
+#[cfg(unix)]
+pub fn stub_binary(binary: &str, test: &str, body: &str) -> PathBuf {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    let dir = std::env::temp_dir().join(format!("jule-{binary}-{test}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(binary);
+    fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
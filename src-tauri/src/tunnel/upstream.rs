@@ -0,0 +1,76 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use crate::port::probe::{http_status, is_listening};
+
+use super::{provider::TunnelError, TunnelKind};
+
+// Generous: dev servers often build the page on the first request.
+const HTTP_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Refuses to share a port that nothing on loopback serves, which would
+/// otherwise only show up as 502s for visitors. HTTP tunnels also need the
+/// port to answer an HTTP request; TCP tunnels only need a listener.
+pub fn check_upstream(port: u16, kind: TunnelKind) -> Result<(), TunnelError> {
+    let loopback: [IpAddr; 2] = [Ipv4Addr::LOCALHOST.into(), Ipv6Addr::LOCALHOST.into()];
+    let addr = loopback
+        .into_iter()
+        .map(|ip| SocketAddr::new(ip, port))
+        .find(|a| is_listening(*a))
+        .ok_or(TunnelError::UpstreamNotListening(port))?;
+    if kind == TunnelKind::Tcp {
+        return Ok(());
+    }
+
+    http_status(addr, HTTP_CHECK_TIMEOUT)
+        .map(|_| ())
+        .ok_or(TunnelError::UpstreamNotHttp(port))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    fn serve_once(reply: &'static [u8]) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for mut conn in listener.incoming().flatten() {
+                let mut buf = [0u8; 1024];
+                let _ = conn.read(&mut buf);
+                let _ = conn.write_all(reply);
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn accepts_any_http_status() {
+        let port = serve_once(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+        assert!(check_upstream(port, TunnelKind::Http).is_ok());
+    }
+
+    #[test]
+    fn rejects_closed_and_non_http_ports() {
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        assert!(matches!(
+            check_upstream(closed, TunnelKind::Tcp),
+            Err(TunnelError::UpstreamNotListening(p)) if p == closed
+        ));
+
+        let redis = serve_once(b"-ERR unknown command 'HEAD'\r\n");
+        assert!(matches!(
+            check_upstream(redis, TunnelKind::Http),
+            Err(TunnelError::UpstreamNotHttp(p)) if p == redis
+        ));
+        assert!(check_upstream(redis, TunnelKind::Tcp).is_ok());
+    }
+}
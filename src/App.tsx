@@ -16,7 +16,9 @@ import {
   filterPorts,
   portsWithPlaceholders,
 } from "@/features/ports/ports-selectors";
+import { parseShareRoutes } from "@/features/tunnels/share-routes";
 import { TunnelsPane } from "@/features/tunnels/tunnels-pane";
+import { toast } from "@/lib/toast";
 import { useAppStore } from "@/store/app-store";
 
 export default function App() {
@@ -44,7 +46,25 @@ export default function App() {
   const setSort = useAppStore((s) => s.setSort);
   const toggleFavorite = useAppStore((s) => s.toggleFavorite);
   const toggleWatched = useAppStore((s) => s.toggleWatched);
-  const refresh = useAppStore((s) => s.refresh);
+  const subscribePorts = useAppStore((s) => s.subscribePorts);
+  const subscribeTunnelEvents = useAppStore((s) => s.subscribeTunnelEvents);
+  const refreshTunnels = useAppStore((s) => s.refreshTunnels);
+  const refreshProviders = useAppStore((s) => s.refreshProviders);
+  const providers = useAppStore((s) => s.providers);
+  const tunnelProvider = useAppStore((s) => s.tunnelProvider);
+  const setTunnelProvider = useAppStore((s) => s.setTunnelProvider);
+  const setNgrokAuthtoken = useAppStore((s) => s.setNgrokAuthtoken);
+  const inspectRequests = useAppStore((s) => s.inspectRequests);
+  const setInspectRequests = useAppStore((s) => s.setInspectRequests);
+  const tunnelGuard = useAppStore((s) => s.tunnelGuard);
+  const setTunnelGuard = useAppStore((s) => s.setTunnelGuard);
+  const tunnelTtlSecs = useAppStore((s) => s.tunnelTtlSecs);
+  const setTunnelTtlSecs = useAppStore((s) => s.setTunnelTtlSecs);
+  const setTunnelExpiry = useAppStore((s) => s.setTunnelExpiry);
+  const shareGroups = useAppStore((s) => s.shareGroups);
+  const refreshShareGroups = useAppStore((s) => s.refreshShareGroups);
+  const setShareGroup = useAppStore((s) => s.setShareGroup);
+  const removeShareGroup = useAppStore((s) => s.removeShareGroup);
   const refreshNow = useAppStore((s) => s.refreshNow);
   const openTunnel = useAppStore((s) => s.openTunnel);
   const renewTunnel = useAppStore((s) => s.renewTunnel);
@@ -56,12 +76,28 @@ export default function App() {
   const isRefreshing = useAppStore((s) => s.isRefreshing);
 
   useEffect(() => {
-    refresh().catch(() => {});
+    let unlisten: (() => void) | undefined;
+    let disposed = false;
+    let unlistenTunnels: (() => void) | undefined;
+    subscribePorts()
+      .then((fn) => (disposed ? fn() : (unlisten = fn)))
+      .catch(() => {});
+    subscribeTunnelEvents()
+      .then((fn) => (disposed ? fn() : (unlistenTunnels = fn)))
+      .catch(() => {});
+    refreshTunnels().catch(() => {});
+    refreshProviders().catch(() => {});
+    refreshShareGroups().catch(() => {});
     const timer = window.setInterval(() => {
-      refresh().catch(() => {});
+      refreshTunnels().catch(() => {});
     }, 5000);
-    return () => window.clearInterval(timer);
-  }, [refresh]);
+    return () => {
+      disposed = true;
+      unlisten?.();
+      unlistenTunnels?.();
+      window.clearInterval(timer);
+    };
+  }, [subscribePorts, subscribeTunnelEvents, refreshTunnels, refreshProviders, refreshShareGroups]);
 
   const tunnelsByPort = useMemo(() => buildTunnelsByPort(tunnels), [tunnels]);
 
@@ -178,11 +214,31 @@ export default function App() {
                   {sidebar === "tunnels" ? (
                     <TunnelsPane
                       tunnels={tunnels}
+                      shareGroups={shareGroups}
+                      onNewShareGroup={() => {
+                        const name = window.prompt("Share group name:");
+                        if (!name?.trim()) return;
+                        const raw = window.prompt(
+                          "Routes as path=port, e.g. /api=8080 /=5173\n(prefix with a subdomain for named tunnels: api:/=8080)",
+                        );
+                        if (!raw) return;
+                        const routes = parseShareRoutes(raw);
+                        if (!routes) {
+                          toast.error("Could not parse routes", { description: raw });
+                          return;
+                        }
+                        setShareGroup(name.trim(), routes);
+                      }}
                       onStopAll={() => stopAllTunnels()}
                       onCopy={(url) => copyText(url)}
                       onOpenUrl={(url) => openExternalUrl(url)}
+                      onSetExpiry={(port, expiry) => setTunnelExpiry(port, expiry)}
                       onRenew={(port) => renewTunnel(port)}
-                      onClose={(port) => closeTunnel(port)}
+                      onClose={(port) => {
+                        // A group's tunnel goes away together with its proxy.
+                        const group = shareGroups.find((g) => g.listen_port === port);
+                        return group ? removeShareGroup(group.name) : closeTunnel(port);
+                      }}
                     />
                   ) : (
                     <div className="min-h-0 flex-1">
@@ -246,6 +302,16 @@ export default function App() {
                   onToggleWatched={(p) => toggleWatched(p)}
                   onCopy={(v) => copyText(v)}
                   onOpenUrl={(url) => openExternalUrl(url)}
+                  providers={providers}
+                  provider={tunnelProvider}
+                  onProviderChange={(name) => setTunnelProvider(name)}
+                  onSetNgrokAuthtoken={(token) => setNgrokAuthtoken(token)}
+                  inspectRequests={inspectRequests}
+                  onInspectRequestsChange={(on) => setInspectRequests(on)}
+                  guard={tunnelGuard}
+                  onGuardChange={(g) => setTunnelGuard(g)}
+                  ttlSecs={tunnelTtlSecs}
+                  onTtlSecsChange={(secs) => setTunnelTtlSecs(secs)}
                   onOpenTunnel={(p) => openTunnel(p)}
                   onRenewTunnel={(p) => renewTunnel(p)}
                   onCloseTunnel={(p) => closeTunnel(p)}
@@ -1,5 +1,30 @@
+import type { ServiceInfo, TunnelInfo } from "@/store/app-store";
+
 export function fmtTime(ms: number) {
   const d = new Date(ms);
   return d.toLocaleString();
 }
 
+// TCP endpoints are pasted into clients as host:port, not as a URL.
+export function tunnelCopyText(tunnel: TunnelInfo) {
+  return tunnel.kind === "tcp" ? tunnel.url.replace(/^tcp:\/\//, "") : tunnel.url;
+}
+
+export function fmtService(service: ServiceInfo) {
+  switch (service.kind) {
+    case "http":
+      return `HTTP ${service.status}${service.server ? ` · ${service.server}` : ""}`;
+    case "tls":
+      return service.subject ? `TLS · ${service.subject}` : "TLS";
+    case "mysql":
+      return service.version ? `MySQL ${service.version}` : "MySQL";
+    case "ssh":
+      return service.banner;
+    case "redis":
+      return "Redis";
+    case "postgres":
+      return "Postgres";
+    case "unknown":
+      return "Unknown protocol";
+  }
+}
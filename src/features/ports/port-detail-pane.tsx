@@ -7,22 +7,35 @@ import {
   EyeOff,
   Star,
 } from "lucide-react";
+import { useState } from "react";
 import { ClipLoader } from "react-spinners";
 
 import { Badge } from "@/components/ui/badge";
 import { Button } from "@/components/ui/button";
 import { Card } from "@/components/ui/card";
 import { ScrollArea, ScrollBar } from "@/components/ui/scroll-area";
-import { fmtTime } from "@/features/ports/format";
+import { fmtService, fmtTime, tunnelCopyText } from "@/features/ports/format";
 import {
   detectProcessType,
   processTypeLabel,
 } from "@/features/ports/process-type";
-import type { PortInfo, TunnelInfo } from "@/store/app-store";
+import { describeGuard, promptGuard } from "@/features/tunnels/guard";
+import { LogsPanel } from "@/features/tunnels/logs-panel";
+import type { PortInfo, ProviderStatus, TunnelGuard, TunnelInfo } from "@/store/app-store";
+
+const TTL_CHOICES: [string, number | null][] = [
+  ["never", null],
+  ["after 15 min", 15 * 60],
+  ["after 1 hour", 60 * 60],
+  ["after 4 hours", 4 * 60 * 60],
+  ["after 1 day", 24 * 60 * 60],
+];
 
 export function PortDetailPane({
   port,
   tunnel,
+  providers,
+  provider,
   isBusy,
   isFavorite,
   isWatched,
@@ -30,12 +43,22 @@ export function PortDetailPane({
   onToggleWatched,
   onCopy,
   onOpenUrl,
+  onProviderChange,
+  onSetNgrokAuthtoken,
+  inspectRequests,
+  onInspectRequestsChange,
+  guard,
+  onGuardChange,
+  ttlSecs,
+  onTtlSecsChange,
   onOpenTunnel,
   onRenewTunnel,
   onCloseTunnel,
 }: {
   port: PortInfo | null;
   tunnel: TunnelInfo | null;
+  providers: ProviderStatus[];
+  provider: string;
   isBusy: boolean;
   isFavorite: boolean;
   isWatched: boolean;
@@ -43,10 +66,21 @@ export function PortDetailPane({
   onToggleWatched: (port: number) => void;
   onCopy: (value: string) => void;
   onOpenUrl: (url: string) => void;
+  onProviderChange: (name: string) => void;
+  onSetNgrokAuthtoken: (token: string) => void;
+  inspectRequests: boolean;
+  onInspectRequestsChange: (on: boolean) => void;
+  guard: TunnelGuard | null;
+  onGuardChange: (guard: TunnelGuard | null) => void;
+  ttlSecs: number | null;
+  onTtlSecsChange: (secs: number | null) => void;
   onOpenTunnel: (port: number) => void;
   onRenewTunnel: (port: number) => void;
   onCloseTunnel: (port: number) => void;
 }) {
+  // Output of attempts that failed before a tunnel showed up.
+  const [showLogs, setShowLogs] = useState(false);
+
   if (!port) {
     return (
       <div className="flex h-full flex-col items-center justify-center p-6 text-center text-muted-foreground">
@@ -61,7 +95,7 @@ export function PortDetailPane({
     );
   }
 
-  const processType = detectProcessType(port.process_name);
+  const processType = detectProcessType(port);
 
   return (
     <ScrollArea className="h-full">
@@ -93,6 +127,14 @@ export function PortDetailPane({
               <Badge variant="secondary">
                 {processTypeLabel(processType)}
               </Badge>
+              {port.service && (
+                <Badge
+                  variant="outline"
+                  title={port.service.kind === "http" ? port.service.title ?? undefined : undefined}
+                >
+                  {fmtService(port.service)}
+                </Badge>
+              )}
             </div>
           </div>
           <div className="flex shrink-0 items-center gap-1">
@@ -130,7 +172,7 @@ export function PortDetailPane({
           <div className="mb-4 flex items-center justify-between">
             <div className="flex items-center gap-2 text-sm font-semibold">
               <Cloud className="h-4 w-4" />
-              Cloudflare Tunnel
+              Share Tunnel
             </div>
             {tunnel && (
               <Badge variant="outline" className="bg-background">
@@ -146,8 +188,68 @@ export function PortDetailPane({
           ) : !tunnel ? (
             <div className="flex flex-col items-center justify-center gap-3 py-4">
               <p className="max-w-[280px] text-center text-sm text-muted-foreground">
-                Expose this port to the internet securely using Cloudflare Tunnel.
+                Expose this port to the internet through a tunnel provider.
               </p>
+              <select
+                className="h-8 rounded-md border bg-background px-2 text-sm"
+                value={provider}
+                onChange={(e) => onProviderChange(e.target.value)}
+              >
+                {providers.length === 0 && <option value={provider}>{provider}</option>}
+                {providers.map((p) => (
+                  <option key={p.name} value={p.name} disabled={!p.available}>
+                    {p.name}
+                    {p.version ? ` ${p.version}` : ""}
+                    {p.available ? "" : " (not installed)"}
+                  </option>
+                ))}
+              </select>
+              {provider === "ngrok" && (
+                <Button
+                  variant="link"
+                  size="sm"
+                  className="h-auto p-0 text-xs text-muted-foreground"
+                  onClick={() => {
+                    const token = window.prompt("ngrok authtoken (leave empty to clear)");
+                    if (token !== null) onSetNgrokAuthtoken(token.trim());
+                  }}
+                >
+                  Set ngrok authtoken
+                </Button>
+              )}
+              <label className="flex items-center gap-2 text-xs text-muted-foreground">
+                <input
+                  type="checkbox"
+                  checked={inspectRequests}
+                  onChange={(e) => onInspectRequestsChange(e.target.checked)}
+                />
+                Record requests (inspector)
+              </label>
+              <Button
+                variant="link"
+                size="sm"
+                className="h-auto justify-start p-0 text-xs text-muted-foreground"
+                onClick={() => {
+                  const next = promptGuard(guard);
+                  if (next !== undefined) onGuardChange(next);
+                }}
+              >
+                {guard ? `Protected: ${describeGuard(guard)}` : "Protect access…"}
+              </Button>
+              <label className="flex items-center gap-2 text-xs text-muted-foreground">
+                Auto-close
+                <select
+                  className="rounded border bg-background px-1 py-0.5"
+                  value={ttlSecs ?? ""}
+                  onChange={(e) => onTtlSecsChange(e.target.value ? Number(e.target.value) : null)}
+                >
+                  {TTL_CHOICES.map(([label, secs]) => (
+                    <option key={label} value={secs ?? ""}>
+                      {label}
+                    </option>
+                  ))}
+                </select>
+              </label>
               <Button onClick={() => onOpenTunnel(port.port)} disabled={isBusy}>
                 {isBusy ? (
                   <ClipLoader size={16} color="currentColor" className="mr-2" />
@@ -156,38 +258,65 @@ export function PortDetailPane({
                 )}
                 {isBusy ? "Creating Tunnel..." : "Create Share Link"}
               </Button>
+              <Button
+                variant="link"
+                size="sm"
+                className="h-auto p-0 text-xs text-muted-foreground"
+                onClick={() => setShowLogs(!showLogs)}
+              >
+                {showLogs ? "Hide logs" : "Show logs"}
+              </Button>
+              {showLogs && (
+                <div className="w-full">
+                  <LogsPanel port={port.port} />
+                </div>
+              )}
             </div>
           ) : (
             <div className="space-y-4">
               <div className="rounded-md border bg-background p-3">
                 <div className="mb-1 text-xs font-medium text-muted-foreground">
-                  Public URL
+                  {tunnel.kind === "tcp" ? "Public Address" : "Public URL"}
+                  {tunnel.state !== "up" && (
+                    <span className={tunnel.state === "failed" ? "text-destructive" : ""}>
+                      {" "}
+                      · {tunnel.state}
+                      {tunnel.last_error ? `: ${tunnel.last_error}` : ""}
+                    </span>
+                  )}
                 </div>
                 <div className="flex items-center justify-between gap-2">
-                  <span className="font-mono text-sm">{tunnel.url}</span>
+                  <span className="font-mono text-sm">{tunnel.url || "waiting for the provider…"}</span>
                   <div className="flex gap-1">
                      <Button
                       variant="ghost"
                       size="icon"
                       className="h-6 w-6"
-                      onClick={() => onCopy(tunnel.url)}
+                      disabled={!tunnel.url}
+                      onClick={() => onCopy(tunnelCopyText(tunnel))}
                     >
                       <Copy className="h-3 w-3" />
                     </Button>
-                    <Button
-                      variant="ghost"
-                      size="icon"
-                      className="h-6 w-6"
-                      onClick={() => onOpenUrl(tunnel.url)}
-                    >
-                      <ExternalLink className="h-3 w-3" />
-                    </Button>
+                    {tunnel.kind === "http" && (
+                      <Button
+                        variant="ghost"
+                        size="icon"
+                        className="h-6 w-6"
+                        disabled={!tunnel.url}
+                        onClick={() => onOpenUrl(tunnel.url)}
+                      >
+                        <ExternalLink className="h-3 w-3" />
+                      </Button>
+                    )}
                   </div>
                 </div>
               </div>
 
               <div className="flex items-center justify-between text-xs text-muted-foreground">
-                <span>Renewed: {fmtTime(tunnel.last_renewed_at_ms)}</span>
+                <span>
+                  Renewed: {fmtTime(tunnel.last_renewed_at_ms)}
+                  {tunnel.expires_at_ms ? ` · Closes: ${fmtTime(tunnel.expires_at_ms)}` : ""}
+                </span>
                 <div className="flex gap-2">
                    <Button
                     variant="ghost"
@@ -9,12 +9,17 @@ export function buildTunnelsByPort(tunnels: TunnelInfo[]) {
 }
 
 export function portsWithPlaceholders(ports: PortInfo[], favorites: number[], watched: number[]) {
-  const byPort = new Map<number, PortInfo>();
-  for (const p of ports) byPort.set(p.port, p);
+  const byKey = new Map<string, PortInfo>();
+  const seenPorts = new Set<number>();
+  for (const p of ports) {
+    byKey.set(`${p.protocol}:${p.port}`, p);
+    seenPorts.add(p.port);
+  }
 
   const ensure = (port: number) => {
-    if (byPort.has(port)) return;
-    byPort.set(port, {
+    if (seenPorts.has(port)) return;
+    seenPorts.add(port);
+    byKey.set(`tcp:${port}`, {
       port,
       pid: 0,
       protocol: "tcp",
@@ -24,14 +29,18 @@ export function portsWithPlaceholders(ports: PortInfo[], favorites: number[], wa
       fd: "-",
       command: "",
       is_active: false,
+      addresses: [],
+      owners: [],
+      loopback_only: false,
+      connection_count: 0,
     });
   };
 
   for (const p of favorites) ensure(p);
   for (const p of watched) ensure(p);
 
-  const out = [...byPort.values()];
-  out.sort((a, b) => a.port - b.port);
+  const out = [...byKey.values()];
+  out.sort((a, b) => a.port - b.port || a.protocol.localeCompare(b.protocol));
   return out;
 }
 
@@ -54,7 +63,7 @@ export function filterPorts(args: {
     list = list.filter((p) => watchedSet.has(p.port));
   } else if (args.sidebar.startsWith("type:")) {
     const type = args.sidebar.slice("type:".length);
-    list = list.filter((p) => detectProcessType(p.process_name) === type);
+    list = list.filter((p) => detectProcessType(p) === type);
   }
 
   const s = args.searchText.trim().toLowerCase();
@@ -92,7 +101,7 @@ export function computeCounts(ports: PortInfo[], favorites: number[], watched: n
 
   const nextByType: Record<keyof typeof byType, number> = { ...byType };
   for (const p of ports) {
-    const t = detectProcessType(p.process_name);
+    const t = detectProcessType(p);
     nextByType[t]++;
   }
 
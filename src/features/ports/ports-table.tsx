@@ -215,13 +215,13 @@ export function PortsTable({
           />
         ),
         cell: ({ row }) => {
-          const type = detectProcessType(row.original.process_name);
+          const type = detectProcessType(row.original);
           return <Badge variant="secondary">{processTypeLabel(type)}</Badge>;
         },
         enableSorting: true,
         sortingFn: (a, b) => {
-          const at = detectProcessType(a.original.process_name);
-          const bt = detectProcessType(b.original.process_name);
+          const at = detectProcessType(a.original);
+          const bt = detectProcessType(b.original);
           return at.localeCompare(bt);
         },
       },
@@ -288,9 +288,9 @@ export function PortsTable({
                 variant="ghost"
                 size="sm"
                 className="h-8 px-2 text-red-600 hover:bg-amber-500/10 hover:text-red-700 dark:text-red-500 dark:hover:text-red-400"
-                disabled={!p.is_active || !p.pid || !!busyPorts[p.port]}
+                disabled={!p.pid || !!busyPorts[p.port]}
                 title={
-                  !p.is_active || !p.pid
+                  !p.pid
                     ? "No process to quit"
                     : busyPorts[p.port]
                     ? "Busy"
@@ -298,7 +298,7 @@ export function PortsTable({
                 }
                 onClick={(e) => {
                   e.stopPropagation();
-                  if (!p.is_active || !p.pid) return;
+                  if (!p.pid) return;
                   const ok = window.confirm(
                     `Quit process PID ${p.pid} on port :${p.port}?`
                   );
@@ -354,7 +354,7 @@ export function PortsTable({
     onColumnVisibilityChange: setColumnVisibility,
     getCoreRowModel: getCoreRowModel(),
     getSortedRowModel: getSortedRowModel(),
-    getRowId: (row) => String(row.port),
+    getRowId: (row) => `${row.protocol}:${row.port}`,
   });
 
   return (
@@ -1,6 +1,23 @@
+import type { PortInfo } from "@/store/app-store";
+
 export type ProcessType = "webServer" | "database" | "development" | "system" | "other";
 
-export function detectProcessType(processName: string): ProcessType {
+const WEB_FRAMEWORKS = new Set(["vite", "next", "nuxt", "sveltekit", "astro", "django", "rails", "express", "fastify"]);
+
+export function detectProcessType(port: Pick<PortInfo, "process_name" | "project">): ProcessType {
+  // A database or system daemon started from a project directory is still one.
+  const byName = detectProcessTypeByName(port.process_name);
+  if (byName === "database" || byName === "system") return byName;
+  // Otherwise prefer what the backend found in the listener's working directory.
+  const project = port.project;
+  if (project) {
+    if (project.framework && WEB_FRAMEWORKS.has(project.framework)) return "webServer";
+    return "development";
+  }
+  return byName;
+}
+
+function detectProcessTypeByName(processName: string): ProcessType {
   const n = processName.toLowerCase();
   if (
     n.includes("node") ||
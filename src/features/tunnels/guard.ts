@@ -0,0 +1,34 @@
+import type { TunnelGuard } from "@/store/app-store";
+
+// "basic auth, token, 2 IPs"; empty when nothing is protected.
+export function describeGuard(guard: TunnelGuard | null): string {
+  if (!guard) return "";
+  const parts = [
+    guard.basic_auth ? "basic auth" : "",
+    guard.token ? "token" : "",
+    guard.allow_ips.length ? `${guard.allow_ips.length} IP${guard.allow_ips.length > 1 ? "s" : ""}` : "",
+  ];
+  return parts.filter(Boolean).join(", ");
+}
+
+// Asks for each setting in turn; `undefined` when the user cancels.
+export function promptGuard(current: TunnelGuard | null): TunnelGuard | null | undefined {
+  const basic = window.prompt("Basic auth as user:password (leave empty for none)", current?.basic_auth ?? "");
+  if (basic === null) return undefined;
+  const token = window.prompt(
+    "Access token, sent as Authorization: Bearer <token> or ?access_token=<token> (leave empty for none)",
+    current?.token ?? "",
+  );
+  if (token === null) return undefined;
+  const ips = window.prompt(
+    "Allowed IPs or CIDRs, comma separated (Cloudflare only; leave empty for any)",
+    current?.allow_ips.join(", ") ?? "",
+  );
+  if (ips === null) return undefined;
+  const guard: TunnelGuard = {
+    basic_auth: basic.trim() || null,
+    token: token.trim() || null,
+    allow_ips: ips.split(/[\s,]+/).filter(Boolean),
+  };
+  return describeGuard(guard) ? guard : null;
+}
@@ -0,0 +1,94 @@
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import { useEffect, useState } from "react";
+
+import { fmtTime } from "@/features/ports/format";
+import type { LogLine, TunnelLogLine, TunnelLogs } from "@/store/app-store";
+
+// Same bound as the backend's ring buffer.
+const MAX_LINES = 500;
+
+const lineKey = (l: LogLine) => `${l.at_ms}:${l.stream}:${l.text}`;
+
+// Adds the lines that arrived while `snapshot` was fetched and are not in it
+// yet, in the order they were printed.
+function mergeLines(snapshot: LogLine[], arrived: LogLine[]): LogLine[] {
+  const seen = new Map<string, number>();
+  for (const l of snapshot) seen.set(lineKey(l), (seen.get(lineKey(l)) ?? 0) + 1);
+  const missed = arrived.filter((l) => {
+    const n = seen.get(lineKey(l)) ?? 0;
+    seen.set(lineKey(l), n - 1);
+    return n <= 0;
+  });
+  return [...snapshot, ...missed].sort((a, b) => a.at_ms - b.at_ms).slice(-MAX_LINES);
+}
+
+function LogLines({ lines }: { lines: LogLine[] }) {
+  return (
+    <pre className="max-h-64 overflow-auto rounded bg-muted p-2 font-mono text-xs whitespace-pre-wrap">
+      {lines.map((l, i) => (
+        <div key={i} className={l.stream === "stderr" ? "text-muted-foreground" : undefined}>
+          {l.text}
+        </div>
+      ))}
+    </pre>
+  );
+}
+
+export function LogsPanel({ port }: { port: number }) {
+  const [logs, setLogs] = useState<TunnelLogs | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  // Listen before fetching, and hold on to the lines that arrive until the
+  // snapshot does, so none printed in between get lost.
+  useEffect(() => {
+    let cancelled = false;
+    let latest = 0;
+    let arrived: LogLine[] | null = null;
+    const load = () => {
+      const id = ++latest;
+      arrived = [];
+      invoke<TunnelLogs>("get_tunnel_logs", { port })
+        .then((l) => {
+          if (cancelled || id !== latest) return;
+          setLogs({ ...l, current: mergeLines(l.current, arrived ?? []) });
+          arrived = null;
+        })
+        .catch((e) => setError(String(e)));
+    };
+    const unlistenLog = listen<TunnelLogLine>("tunnels://log", (e) => {
+      if (e.payload.port !== port) return;
+      arrived?.push(e.payload);
+      setLogs((l) => l && { ...l, current: [...l.current, e.payload].slice(-MAX_LINES) });
+    });
+    // A new attempt starts a fresh log; a failed one moves to `failed`.
+    const unlistenState = listen<{ port: number }>("tunnels://state", (e) => {
+      if (e.payload.port === port) load();
+    });
+    load();
+    return () => {
+      cancelled = true;
+      unlistenLog.then((f) => f());
+      unlistenState.then((f) => f());
+    };
+  }, [port]);
+
+  return (
+    <div className="mt-3 grid gap-2 border-t pt-3">
+      {error && <div className="text-xs text-destructive">{error}</div>}
+      {logs?.current.length ? (
+        <LogLines lines={logs.current} />
+      ) : (
+        <div className="text-xs text-muted-foreground">No output yet.</div>
+      )}
+      {logs?.failed.map((f) => (
+        <div key={f.at_ms} className="grid gap-1">
+          <div className="text-xs text-destructive">
+            {f.provider} failed at {fmtTime(f.at_ms)}: {f.error.split("\n")[0]}
+          </div>
+          {f.lines.length > 0 && <LogLines lines={f.lines} />}
+        </div>
+      ))}
+    </div>
+  );
+}
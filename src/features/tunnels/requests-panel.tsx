@@ -0,0 +1,193 @@
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import { useEffect, useState } from "react";
+
+import { Badge } from "@/components/ui/badge";
+import { Button } from "@/components/ui/button";
+import { fmtTime } from "@/features/ports/format";
+import type {
+  CapturedBody,
+  CapturedRequest,
+  ReplayOverrides,
+  RequestSummary,
+} from "@/store/app-store";
+
+function fmtHeaders(headers: [string, string][]) {
+  return headers.map(([k, v]) => `${k}: ${v}`).join("\n");
+}
+
+function fmtBody(body: CapturedBody) {
+  if (!body.size) return "";
+  const text = body.text ?? `<${body.size} bytes of binary data>`;
+  return body.truncated ? `${text}\n… (${body.size} bytes total)` : text;
+}
+
+/** Truncated and binary bodies are not editable; leaving the field empty resends the original. */
+function editableBody(body: CapturedBody) {
+  return body.truncated ? "" : (body.text ?? "");
+}
+
+function parseHeaders(text: string): [string, string][] {
+  return text
+    .split("\n")
+    .map((line) => line.split(/:(.*)/s))
+    .filter(([k]) => k.trim())
+    .map(([k, v]) => [k.trim(), (v ?? "").trim()]);
+}
+
+/** What changed between the recorded request and the edited one. */
+function replayOverrides(req: CapturedRequest, headersText: string, body: string): ReplayOverrides {
+  const edited = parseHeaders(headersText);
+  const headers: Record<string, string | null> = {};
+  for (const [k] of req.request_headers) {
+    if (!edited.some(([e]) => e.toLowerCase() === k.toLowerCase())) headers[k] = null;
+  }
+  for (const [k, v] of edited) {
+    const old = req.request_headers.find(([o]) => o.toLowerCase() === k.toLowerCase());
+    if (old?.[1] !== v) headers[k] = v;
+  }
+  return { headers, body: body !== editableBody(req.request_body) ? body : undefined };
+}
+
+export function RequestsPanel({ port }: { port: number }) {
+  const [requests, setRequests] = useState<RequestSummary[]>([]);
+  const [selected, setSelected] = useState<CapturedRequest | null>(null);
+  const [error, setError] = useState<string | null>(null);
+  const [draft, setDraft] = useState<{ headers: string; body: string } | null>(null);
+  const [replaying, setReplaying] = useState(false);
+
+  // Listen before listing so requests finished in between still show up.
+  useEffect(() => {
+    let cancelled = false;
+    const unlisten = listen<RequestSummary>("tunnels://request", (e) => {
+      if (e.payload.port !== port) return;
+      setRequests((rs) => [e.payload, ...rs.filter((r) => r.id !== e.payload.id)].slice(0, 200));
+    });
+    invoke<RequestSummary[]>("list_requests", { port })
+      .then((rs) => {
+        if (!cancelled) setRequests(rs);
+      })
+      .catch((e) => setError(String(e)));
+    return () => {
+      cancelled = true;
+      unlisten.then((f) => f());
+    };
+  }, [port]);
+
+  const select = async (id: number) => {
+    try {
+      setSelected(await invoke<CapturedRequest>("get_request", { id }));
+      setDraft(null);
+      setError(null);
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const replay = async () => {
+    if (!selected || !draft) return;
+    setReplaying(true);
+    try {
+      const overrides = replayOverrides(selected, draft.headers, draft.body);
+      const result = await invoke<CapturedRequest>("replay_request", { id: selected.id, overrides });
+      setSelected(result);
+      setDraft(null);
+      setError(null);
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      setReplaying(false);
+    }
+  };
+
+  return (
+    <div className="mt-3 grid gap-2 border-t pt-3">
+      {error && <div className="text-xs text-destructive">{error}</div>}
+      {requests.length ? (
+        <div className="grid max-h-64 gap-1 overflow-auto">
+          {requests.map((r) => (
+            <button
+              key={r.id}
+              className={`flex items-center gap-2 rounded px-2 py-1 text-left font-mono text-xs hover:bg-muted ${
+                selected?.id === r.id ? "bg-muted" : ""
+              }`}
+              onClick={() => select(r.id)}
+            >
+              <Badge variant={r.error || (r.status ?? 0) >= 500 ? "destructive" : "secondary"}>
+                {r.status ?? "ERR"}
+              </Badge>
+              <span className="w-14 shrink-0">{r.method}</span>
+              <span className="min-w-0 flex-1 truncate">{r.target}</span>
+              {r.replay_of != null && (
+                <span className="shrink-0 text-muted-foreground">replay of #{r.replay_of}</span>
+              )}
+              <span className="shrink-0 text-muted-foreground">{r.duration_ms} ms</span>
+            </button>
+          ))}
+        </div>
+      ) : (
+        <div className="text-xs text-muted-foreground">No requests recorded yet.</div>
+      )}
+      {selected && (
+        <div className="flex gap-2">
+          <Button
+            variant="outline"
+            size="sm"
+            disabled={replaying}
+            onClick={() =>
+              setDraft(
+                draft
+                  ? null
+                  : {
+                      headers: fmtHeaders(selected.request_headers),
+                      body: editableBody(selected.request_body),
+                    },
+              )
+            }
+          >
+            {draft ? "Cancel" : "Edit & Replay"}
+          </Button>
+          {draft && (
+            <Button size="sm" disabled={replaying} onClick={replay}>
+              {replaying ? "Sending…" : "Send"}
+            </Button>
+          )}
+        </div>
+      )}
+      {selected && draft && (
+        <div className="grid gap-2">
+          <textarea
+            className="h-32 rounded border bg-background p-2 font-mono text-xs"
+            value={draft.headers}
+            onChange={(e) => setDraft({ ...draft, headers: e.target.value })}
+          />
+          <textarea
+            className="h-32 rounded border bg-background p-2 font-mono text-xs"
+            placeholder={
+              selected.request_body.truncated
+                ? "Body was truncated; paste the full body to replay"
+                : "Body"
+            }
+            value={draft.body}
+            onChange={(e) => setDraft({ ...draft, body: e.target.value })}
+          />
+        </div>
+      )}
+      {selected && !draft && (
+        <pre className="max-h-96 overflow-auto whitespace-pre-wrap break-all rounded bg-muted p-2 text-xs">
+          {[
+            `${selected.method} ${selected.target}  (${fmtTime(selected.started_at_ms)})`,
+            fmtHeaders(selected.request_headers),
+            fmtBody(selected.request_body),
+            "",
+            selected.error ? `error: ${selected.error}` : `HTTP ${selected.status}`,
+            fmtHeaders(selected.response_headers),
+            fmtBody(selected.response_body),
+          ]
+            .filter((line, i) => line || i === 3)
+            .join("\n")}
+        </pre>
+      )}
+    </div>
+  );
+}
@@ -0,0 +1,14 @@
+import type { ShareRoute } from "@/store/app-store";
+
+// Parses "/api=8080 /=5173" (optionally "api:/=8080" for a subdomain route).
+export function parseShareRoutes(raw: string): ShareRoute[] | null {
+  const routes: ShareRoute[] = [];
+  for (const part of raw.split(/[\s,]+/).filter(Boolean)) {
+    const m = /^(?:([A-Za-z0-9-]+):)?(\/[^=]*)?=(\d+)$/.exec(part);
+    if (!m) return null;
+    const port = Number(m[3]);
+    if (port < 1 || port > 65535) return null;
+    routes.push({ path: m[2] ?? "/", subdomain: m[1] ?? null, port });
+  }
+  return routes.length ? routes : null;
+}
@@ -1,4 +1,5 @@
-import { Cloud, Copy, ExternalLink, RotateCw, X } from "lucide-react";
+import { Cloud, Copy, ExternalLink, Layers, List, RotateCw, ScrollText, X } from "lucide-react";
+import { useState } from "react";
 
 import { Badge } from "@/components/ui/badge";
 import { Button } from "@/components/ui/button";
@@ -10,34 +11,51 @@ import {
   TooltipProvider,
   TooltipTrigger,
 } from "@/components/ui/tooltip";
-import { fmtTime } from "@/features/ports/format";
-import type { TunnelInfo } from "@/store/app-store";
+import { fmtTime, tunnelCopyText } from "@/features/ports/format";
+import { LogsPanel } from "@/features/tunnels/logs-panel";
+import { RequestsPanel } from "@/features/tunnels/requests-panel";
+import type { ExpirySpec, ShareGroup, TunnelInfo } from "@/store/app-store";
 
 export function TunnelsPane({
   tunnels,
+  shareGroups,
+  onNewShareGroup,
   onStopAll,
   onCopy,
   onRenew,
   onClose,
-  onOpenUrl
+  onOpenUrl,
+  onSetExpiry,
 }: {
   tunnels: TunnelInfo[];
+  shareGroups: ShareGroup[];
+  onNewShareGroup: () => void;
   onStopAll: () => void;
   onCopy: (url: string) => void;
   onRenew: (port: number) => void;
   onClose: (port: number) => void;
   onOpenUrl: (url: string) => void;
+  onSetExpiry: (port: number, expiry: ExpirySpec | null) => void;
 }) {
+  const [inspecting, setInspecting] = useState<number | null>(null);
+  const [viewingLogs, setViewingLogs] = useState<number | null>(null);
+
   return (
     <div className="flex h-full flex-col">
       <ScrollArea className="min-h-0 flex-1">
         <div className="p-4">
           <div className="mb-3 flex items-center justify-between gap-2">
             <div className="text-sm font-semibold">Active tunnels</div>
-            <Button variant="destructive" size="sm" onClick={onStopAll} disabled={!tunnels.length}>
-              <X className="h-4 w-4" />
-              Stop All
-            </Button>
+            <div className="flex gap-2">
+              <Button variant="outline" size="sm" onClick={onNewShareGroup}>
+                <Layers className="h-4 w-4" />
+                Share Group
+              </Button>
+              <Button variant="destructive" size="sm" onClick={onStopAll} disabled={!tunnels.length}>
+                <X className="h-4 w-4" />
+                Stop All
+              </Button>
+            </div>
           </div>
           <div className="grid gap-2">
             {tunnels.length ? (
@@ -54,11 +72,60 @@ export function TunnelsPane({
                           <Badge variant="secondary" className="font-mono">
                             {t.provider}
                           </Badge>
+                          {t.state !== "up" && (
+                            <Badge
+                              variant={t.state === "failed" ? "destructive" : "outline"}
+                              title={t.last_error ?? undefined}
+                            >
+                              {t.state === "reconnecting"
+                                ? `reconnecting (${t.retry_attempts})`
+                                : t.state}
+                            </Badge>
+                          )}
+                          {shareGroups
+                            .filter((g) => g.listen_port === t.port)
+                            .map((g) => (
+                              <Badge
+                                key={g.name}
+                                variant="outline"
+                                title={g.routes
+                                  .map((r) => `${r.subdomain ? `${r.subdomain}.` : ""}${r.path || "/"} → :${r.port}`)
+                                  .join("\n")}
+                              >
+                                {g.name}
+                              </Badge>
+                            ))}
+                        </div>
+                        <div className="mt-1 break-all font-mono text-sm">
+                          {t.url || "waiting for the provider…"}
                         </div>
-                        <div className="mt-1 break-all font-mono text-sm">{t.url}</div>
                         <div className="mt-1 text-xs text-muted-foreground">
                           renewed: {fmtTime(t.last_renewed_at_ms)}
+                          {t.restart_count ? ` · restarts: ${t.restart_count}` : ""}
                         </div>
+                        {t.state !== "up" && t.last_error && (
+                          <div className="mt-1 break-all text-xs text-destructive">
+                            {t.last_error}
+                            {t.next_retry_at_ms ? ` · next try ${fmtTime(t.next_retry_at_ms)}` : ""}
+                          </div>
+                        )}
+                        {t.expires_at_ms ? (
+                          <div className="mt-1 flex items-center gap-2 text-xs text-muted-foreground">
+                            closes: {fmtTime(t.expires_at_ms)}
+                            <button
+                              className="underline hover:text-foreground"
+                              onClick={() => onSetExpiry(t.port, { at_ms: t.expires_at_ms! + 60 * 60 * 1000 })}
+                            >
+                              +1 hour
+                            </button>
+                            <button
+                              className="underline hover:text-foreground"
+                              onClick={() => onSetExpiry(t.port, null)}
+                            >
+                              keep open
+                            </button>
+                          </div>
+                        ) : null}
                       </div>
                       <div className="flex gap-2">
                         <TooltipProvider>
@@ -68,13 +135,13 @@ export function TunnelsPane({
                                 variant="outline"
                                 size="icon"
                                 className="h-8 w-8"
-                                onClick={() => onCopy(t.url)}
+                                onClick={() => onCopy(tunnelCopyText(t))}
                               >
                                 <Copy className="size-4" />
                               </Button>
                             </TooltipTrigger>
                             <TooltipContent>
-                              <p>Copy URL</p>
+                              <p>{t.kind === "tcp" ? "Copy Address" : "Copy URL"}</p>
                             </TooltipContent>
                           </Tooltip>
 
@@ -94,19 +161,56 @@ export function TunnelsPane({
                             </TooltipContent>
                           </Tooltip>
 
+                          {t.kind === "http" && (
+                            <Tooltip>
+                              <TooltipTrigger asChild>
+                                <Button
+                                  variant="secondary"
+                                  size="icon"
+                                  className="h-8 w-8"
+                                  disabled={!t.url}
+                                  onClick={() => onOpenUrl(t.url)}
+                                >
+                                  <ExternalLink className="size-4" />
+                                </Button>
+                              </TooltipTrigger>
+                              <TooltipContent>
+                                <p>Open URL</p>
+                              </TooltipContent>
+                            </Tooltip>
+                          )}
+
+                          {t.kind === "http" && (
+                            <Tooltip>
+                              <TooltipTrigger asChild>
+                                <Button
+                                  variant={inspecting === t.port ? "default" : "secondary"}
+                                  size="icon"
+                                  className="h-8 w-8"
+                                  onClick={() => setInspecting(inspecting === t.port ? null : t.port)}
+                                >
+                                  <List className="size-4" />
+                                </Button>
+                              </TooltipTrigger>
+                              <TooltipContent>
+                                <p>Requests</p>
+                              </TooltipContent>
+                            </Tooltip>
+                          )}
+
                           <Tooltip>
                             <TooltipTrigger asChild>
                               <Button
-                                variant="secondary"
+                                variant={viewingLogs === t.port ? "default" : "secondary"}
                                 size="icon"
                                 className="h-8 w-8"
-                                onClick={() => onOpenUrl(t.url)}
+                                onClick={() => setViewingLogs(viewingLogs === t.port ? null : t.port)}
                               >
-                                <ExternalLink className="size-4" />
+                                <ScrollText className="size-4" />
                               </Button>
                             </TooltipTrigger>
                             <TooltipContent>
-                              <p>Open URL</p>
+                              <p>Logs</p>
                             </TooltipContent>
                           </Tooltip>
 
@@ -128,6 +232,8 @@ export function TunnelsPane({
                         </TooltipProvider>
                       </div>
                     </div>
+                    {inspecting === t.port && <RequestsPanel port={t.port} />}
+                    {viewingLogs === t.port && <LogsPanel port={t.port} />}
                   </Card>
                 ))
             ) : (
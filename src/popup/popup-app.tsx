@@ -5,6 +5,7 @@ import { useEffect, useState } from "react";
 import { Button } from "@/components/ui/button";
 import { Input } from "@/components/ui/input";
 import { ScrollArea } from "@/components/ui/scroll-area";
+import { tunnelCopyText } from "@/features/ports/format";
 import { toast } from "@/lib/toast";
 import { useAppStore } from "@/store/app-store";
 
@@ -24,16 +25,27 @@ export default function PopupApp() {
   const tunnels = useAppStore((s) => s.tunnels);
   const busyPorts = useAppStore((s) => s.busyPorts);
   const refresh = useAppStore((s) => s.refresh);
+  const subscribePorts = useAppStore((s) => s.subscribePorts);
+  const refreshTunnels = useAppStore((s) => s.refreshTunnels);
   const closeTunnel = useAppStore((s) => s.closeTunnel);
   const copyText = useAppStore((s) => s.copyText);
 
   const [searchQuery, setSearchQuery] = useState("");
 
   useEffect(() => {
-    refresh().catch(() => {});
-    const timer = window.setInterval(() => refresh().catch(() => {}), 5000);
-    return () => window.clearInterval(timer);
-  }, [refresh]);
+    let unlisten: (() => void) | undefined;
+    let disposed = false;
+    subscribePorts()
+      .then((fn) => (disposed ? fn() : (unlisten = fn)))
+      .catch(() => {});
+    refreshTunnels().catch(() => {});
+    const timer = window.setInterval(() => refreshTunnels().catch(() => {}), 5000);
+    return () => {
+      disposed = true;
+      unlisten?.();
+      window.clearInterval(timer);
+    };
+  }, [subscribePorts, refreshTunnels]);
 
   useEffect(() => {
     const onBlur = () => {
@@ -122,7 +134,7 @@ export default function PopupApp() {
                     size="icon"
                     variant="ghost"
                     className="size-6 shrink-0"
-                    onClick={() => copyText(t.url)}
+                    onClick={() => copyText(tunnelCopyText(t))}
                   >
                     <Copy className="size-3.5" />
                   </Button>
@@ -1,4 +1,5 @@
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { create } from "zustand";
 import { persist } from "zustand/middleware";
 
@@ -19,21 +20,196 @@ export type PortInfo = {
   fd: string;
   command?: string | null;
   is_active: boolean;
+  addresses: string[];
+  owners: PortOwner[];
+  loopback_only: boolean;
+  details?: ProcessDetails | null;
+  project?: ProjectInfo | null;
+  connection_count: number;
+  service?: ServiceInfo | null;
+};
+
+export type ServiceInfo =
+  | { kind: "http"; status: number; server?: string | null; title?: string | null }
+  | { kind: "tls"; subject?: string | null }
+  | { kind: "redis" }
+  | { kind: "postgres" }
+  | { kind: "mysql"; version?: string | null }
+  | { kind: "ssh"; banner: string }
+  | { kind: "unknown" };
+
+export type Connection = {
+  local_address: string;
+  local_port: number;
+  remote_ip: string;
+  remote_port: number;
+  state: string;
+};
+
+export type ProjectInfo = {
+  root: string;
+  name: string;
+  kind: string;
+  framework?: string | null;
+  git_branch?: string | null;
+};
+
+export type ProcessDetails = {
+  pid: number;
+  name: string;
+  command_line?: string | null;
+  args: string[];
+  cwd?: string | null;
+  ppid?: number | null;
+  start_time_ms?: number | null;
+  rss_bytes?: number | null;
+  cpu_percent?: number | null;
+};
+
+export type PortOwner = {
+  pid: number;
+  process_name: string;
+  user: string;
+  fd: string;
 };
 
 export type TunnelInfo = {
   port: number;
   provider: string;
   url: string;
+  kind: "http" | "tcp";
   started_at_ms: number;
   last_renewed_at_ms: number;
+  expires_at_ms?: number | null;
+  state: TunnelState;
+  state_changed_at_ms: number;
+  restart_count: number;
+  last_error?: string | null;
+  retry_attempts: number;
+  next_retry_at_ms?: number | null;
+};
+
+export type TunnelState = "starting" | "up" | "degraded" | "reconnecting" | "failed";
+
+/** A lifecycle step; `from`/`to` are `null` before start and after stop. */
+type TunnelTransition = {
+  port: number;
+  provider: string;
+  from: TunnelState | null;
+  to: TunnelState | null;
+  at_ms: number;
+  restart_count: number;
+  error?: string | null;
+};
+
+/** `{ ttl_secs }` from now or an absolute `{ at_ms }`. */
+export type ExpirySpec = { ttl_secs: number } | { at_ms: number };
+
+type TunnelExpiry = { port: number; expires_at_ms: number };
+
+export type ProviderStatus = {
+  name: string;
+  available: boolean;
+  binary?: string | null;
+  version?: string | null;
+};
+
+export type TunnelGuard = {
+  basic_auth?: string | null;
+  token?: string | null;
+  allow_ips: string[];
+};
+
+export type TunnelOptions = { inspect: boolean; guard?: TunnelGuard | null };
+
+export type LogLine = { at_ms: number; stream: "stdout" | "stderr"; text: string };
+
+/** Payload of `tunnels://log`. */
+export type TunnelLogLine = LogLine & { port: number };
+
+export type FailedAttempt = { provider: string; at_ms: number; error: string; lines: LogLine[] };
+
+export type TunnelLogs = { port: number; current: LogLine[]; failed: FailedAttempt[] };
+
+export type CapturedBody = { size: number; truncated: boolean; text?: string | null };
+
+export type RequestSummary = {
+  id: number;
+  port: number;
+  started_at_ms: number;
+  duration_ms: number;
+  method: string;
+  target: string;
+  status?: number | null;
+  request_size: number;
+  response_size: number;
+  error?: string | null;
+  replay_of?: number | null;
 };
 
+export type CapturedRequest = {
+  id: number;
+  port: number;
+  started_at_ms: number;
+  duration_ms: number;
+  method: string;
+  target: string;
+  request_headers: [string, string][];
+  request_body: CapturedBody;
+  status?: number | null;
+  response_headers: [string, string][];
+  response_body: CapturedBody;
+  error?: string | null;
+  replay_of?: number | null;
+};
+
+/** Header values to set (`null` removes) and an optional replacement body. */
+export type ReplayOverrides = {
+  headers?: Record<string, string | null>;
+  body?: string;
+};
+
+export type ShareRoute = { path: string; subdomain?: string | null; port: number };
+
+export type ShareGroup = { name: string; routes: ShareRoute[]; listen_port: number };
+
+export type PortChange =
+  | { kind: "opened"; info: PortInfo }
+  | { kind: "closed"; info: PortInfo }
+  | { kind: "owner_changed"; info: PortInfo; previous: PortInfo }
+  | { kind: "updated"; info: PortInfo };
+
+type PortsChanged = { scanned_at_ms: number; changes: PortChange[] };
+type PortSnapshot = { scanned_at_ms: number; ports: PortInfo[]; interval_ms: number };
+
+const portKey = (p: PortInfo) => `${p.protocol}:${p.port}`;
+
+function applyPortChanges(ports: PortInfo[], changes: PortChange[]) {
+  const byKey = new Map(ports.map((p) => [portKey(p), p] as const));
+  for (const c of changes) {
+    if (c.kind === "closed") byKey.delete(portKey(c.info));
+    else byKey.set(portKey(c.info), c.info);
+  }
+  return [...byKey.values()].sort((a, b) => a.port - b.port || a.protocol.localeCompare(b.protocol));
+}
+
+// Matches TunnelError::UpstreamNotListening / UpstreamNotHttp.
+const isUpstreamError = (msg: string) =>
+  msg.includes("nothing is listening on") || msg.includes("did not answer an HTTP request");
+
 export type SortKey = "actions" | "active" | "port" | "process" | "pid" | "type" | "address" | "user";
 
 type State = {
   ports: PortInfo[];
   tunnels: TunnelInfo[];
+  providers: ProviderStatus[];
+  tunnelProvider: string;
+  inspectRequests: boolean;
+  // Not persisted here: it holds secrets. Tunnels keep theirs in state.json.
+  tunnelGuard: TunnelGuard | null;
+  // Auto-close new tunnels after this long; null keeps them until closed.
+  tunnelTtlSecs: number | null;
+  shareGroups: ShareGroup[];
   busyPorts: Record<number, boolean>;
   isRefreshing: boolean;
   error: string | null;
@@ -65,8 +241,21 @@ type Actions = {
   toggleWatched: (port: number) => void;
 
   refresh: () => Promise<void>;
+  refreshTunnels: () => Promise<void>;
+  refreshProviders: () => Promise<void>;
+  setTunnelProvider: (name: string) => void;
+  setInspectRequests: (on: boolean) => void;
+  setTunnelGuard: (guard: TunnelGuard | null) => void;
+  setTunnelTtlSecs: (secs: number | null) => void;
+  setTunnelExpiry: (port: number, expiry: ExpirySpec | null) => Promise<void>;
+  subscribeTunnelEvents: () => Promise<() => void>;
+  setNgrokAuthtoken: (token: string) => Promise<void>;
+  refreshShareGroups: () => Promise<void>;
+  setShareGroup: (name: string, routes: ShareRoute[]) => Promise<void>;
+  removeShareGroup: (name: string) => Promise<void>;
+  subscribePorts: () => Promise<() => void>;
   refreshNow: () => Promise<void>;
-  openTunnel: (port: number) => Promise<void>;
+  openTunnel: (port: number, force?: boolean) => Promise<void>;
   renewTunnel: (port: number) => Promise<void>;
   closeTunnel: (port: number) => Promise<void>;
   stopAllTunnels: () => Promise<void>;
@@ -89,6 +278,12 @@ export const useAppStore = create<State & Actions>()(
     (set, get) => ({
       ports: [],
       tunnels: [],
+      providers: [],
+      tunnelProvider: "cloudflare",
+      inspectRequests: false,
+      tunnelGuard: null,
+      tunnelTtlSecs: null,
+      shareGroups: [],
       busyPorts: {},
       isRefreshing: false,
       error: null,
@@ -135,6 +330,111 @@ export const useAppStore = create<State & Actions>()(
 
       clearError: () => set({ error: null }),
 
+      refreshTunnels: async () => {
+        const tunnels = await invoke<TunnelInfo[]>("list_tunnels");
+        set({ tunnels });
+      },
+
+      refreshProviders: async () => {
+        const providers = await invoke<ProviderStatus[]>("list_providers");
+        set({ providers });
+      },
+
+      setTunnelProvider: (name) => set({ tunnelProvider: name }),
+
+      setInspectRequests: (on) => set({ inspectRequests: on }),
+
+      setTunnelGuard: (guard) => set({ tunnelGuard: guard }),
+
+      setTunnelTtlSecs: (secs) => set({ tunnelTtlSecs: secs }),
+
+      setTunnelExpiry: async (port, expiry) => {
+        try {
+          await invoke<number | null>("set_tunnel_expiry", { port, expiry });
+          await get().refreshTunnels();
+        } catch (e) {
+          toast.error("Update expiry failed", { description: String(e) });
+        }
+      },
+
+      subscribeTunnelEvents: async () => {
+        const offExpiring = await listen<TunnelExpiry>("tunnels://expiring", (e) => {
+          const mins = Math.max(1, Math.round((e.payload.expires_at_ms - Date.now()) / 60000));
+          toast.info(`Tunnel :${e.payload.port} closes in ${mins} min`, {
+            id: `expiry-${e.payload.port}`,
+          });
+        });
+        const offExpired = await listen<TunnelExpiry>("tunnels://expired", (e) => {
+          toast.info(`Tunnel :${e.payload.port} expired and was closed`, {
+            id: `expiry-${e.payload.port}`,
+          });
+          get().refreshTunnels().catch(() => {});
+        });
+        const offState = await listen<TunnelTransition>("tunnels://state", (e) => {
+          const { port, to, error } = e.payload;
+          if (to === "failed") {
+            toast.error(`Tunnel :${port} failed`, { id: `state-${port}`, description: error ?? undefined });
+          } else if (to === "degraded") {
+            toast.info(`Tunnel :${port}: local service stopped answering`, { id: `state-${port}` });
+          }
+          get().refreshTunnels().catch(() => {});
+        });
+        return () => {
+          offExpiring();
+          offExpired();
+          offState();
+        };
+      },
+
+      setNgrokAuthtoken: async (token) => {
+        try {
+          await invoke("set_ngrok_authtoken", { token });
+          toast.success(token ? "ngrok authtoken saved" : "ngrok authtoken cleared");
+        } catch (e) {
+          toast.error("Save ngrok authtoken failed", { description: String(e) });
+        }
+      },
+
+      refreshShareGroups: async () => {
+        const shareGroups = await invoke<ShareGroup[]>("list_share_groups");
+        set({ shareGroups });
+      },
+
+      setShareGroup: async (name, routes) => {
+        try {
+          const url = await invoke<string>("set_share_group", {
+            name,
+            routes,
+            provider: get().tunnelProvider,
+          });
+          toast.success(`Share group "${name}" is live`, { description: url });
+        } catch (e) {
+          toast.error("Share group failed", { description: String(e) });
+        } finally {
+          await Promise.all([get().refreshShareGroups(), get().refreshTunnels()]).catch(() => {});
+        }
+      },
+
+      removeShareGroup: async (name) => {
+        try {
+          await invoke("remove_share_group", { name });
+        } catch (e) {
+          toast.error("Remove share group failed", { description: String(e) });
+        } finally {
+          await Promise.all([get().refreshShareGroups(), get().refreshTunnels()]).catch(() => {});
+        }
+      },
+
+      // Listen before fetching the snapshot so no delta emitted in between is lost.
+      subscribePorts: async () => {
+        const unlisten = await listen<PortsChanged>("ports://changed", (e) => {
+          set((s) => ({ ports: applyPortChanges(s.ports, e.payload.changes) }));
+        });
+        const snap = await invoke<PortSnapshot>("subscribe_ports");
+        set({ ports: snap.ports });
+        return unlisten;
+      },
+
       refresh: async () => {
         set({ error: null });
         const [ports, tunnels] = await Promise.all([
@@ -159,12 +459,22 @@ export const useAppStore = create<State & Actions>()(
         }
       },
 
-      openTunnel: async (port) => {
+      openTunnel: async (port, force = false) => {
         set((s) => ({ busyPorts: { ...s.busyPorts, [port]: true }, error: null }));
         const id = toast.loading(`Creating share link for :${port}...`);
         await nextFrame();
+        const ttl = get().tunnelTtlSecs;
         try {
-          const url = await invoke<string>("open_tunnel", { port });
+          const url = await invoke<string>("open_tunnel", {
+            port,
+            provider: get().tunnelProvider,
+            force,
+            options: {
+              inspect: get().inspectRequests,
+              guard: get().tunnelGuard,
+            } satisfies TunnelOptions,
+            expiry: ttl ? ({ ttl_secs: ttl } satisfies ExpirySpec) : null,
+          });
           await get().refresh();
           toast.success(`Share link ready (:${port})`, {
             id,
@@ -172,9 +482,17 @@ export const useAppStore = create<State & Actions>()(
           });
         } catch (e) {
           const msg = String(e);
+          // The upstream check is advisory: let the user share anyway.
+          if (!force && isUpstreamError(msg)) {
+            toast.dismiss(id);
+            if (window.confirm(`${msg}\n\nShare :${port} anyway?`)) {
+              await get().openTunnel(port, true);
+            }
+            return;
+          }
           toast.error(`Create share link failed (:${port})`, {
             id,
-            description: msg,
+            description: `${msg}\nProvider output: Show logs in the port details.`,
           });
           set({ error: msg });
         } finally {
@@ -304,6 +622,9 @@ export const useAppStore = create<State & Actions>()(
         watched: s.watched,
         sortKey: s.sortKey,
         sortAsc: s.sortAsc,
+        tunnelProvider: s.tunnelProvider,
+        inspectRequests: s.inspectRequests,
+        tunnelTtlSecs: s.tunnelTtlSecs,
       }),
     }
   )